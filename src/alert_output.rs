@@ -0,0 +1,143 @@
+//! Alert channel output
+//!
+//! This module streams the tracker alerts (ACAS resolution advisories,
+//! emergency and SPI alerts, geofence and watchlist events) to TCP clients,
+//! one JSON object per line, in the same format as the control port alert
+//! log. Regular state updates are not sent, so that a notification service
+//! only has to handle what needs attention.
+
+use crate::output_module::{
+    LineBroadcaster, LineMessage, LineServer, OutputModuleBase, OutputModuleConfig, StateOutputModule,
+};
+use crate::{AircraftAddress, AircraftRecord, TrackerEvent};
+use anyhow::Result;
+use tracing::error;
+
+/// An alert serialized as a line of JSON
+#[derive(Debug, Clone)]
+pub struct AlertMessage {
+    pub line: String,
+}
+
+impl AlertMessage {
+    /// Create a message for a tracker event, `None` if it is not an alert
+    pub fn from_event(event: &TrackerEvent) -> Option<Self> {
+        event.kind.is_alert().then(|| Self {
+            line: format!("{}\n", event.to_json()),
+        })
+    }
+}
+
+impl LineMessage for AlertMessage {
+    const FORMAT: &'static str = "Alert";
+
+    fn encode(&self) -> String {
+        self.line.clone()
+    }
+}
+
+/// Alert channel TCP server
+pub type AlertServer = LineServer<AlertMessage>;
+
+/// Alert message broadcaster
+pub type AlertBroadcaster = LineBroadcaster<AlertMessage>;
+
+/// Alert channel output module
+pub struct AlertOutput {
+    name: String,
+    port: u16,
+    broadcaster: AlertBroadcaster,
+    is_running: bool,
+}
+
+impl AlertOutput {
+    /// Create a new alert output module
+    pub async fn new(config: OutputModuleConfig) -> Result<Self> {
+        let (broadcaster, receiver) = AlertBroadcaster::new(config.buffer_capacity);
+
+        // Start the server
+        let server = AlertServer::new(config.port, receiver).await?;
+        tokio::spawn(async move {
+            if let Err(e) = server.run().await {
+                error!("Alert server error: {}", e);
+            }
+        });
+
+        Ok(Self {
+            name: config.name,
+            port: config.port,
+            broadcaster,
+            is_running: true,
+        })
+    }
+}
+
+// Implement the base trait for common functionality
+impl OutputModuleBase for AlertOutput {
+    fn name(&self) -> &str {
+        &self.name
+    }
+
+    fn description(&self) -> &str {
+        "JSON lines stream of tracker alerts"
+    }
+
+    fn port(&self) -> u16 {
+        self.port
+    }
+
+    fn client_count(&self) -> usize {
+        self.broadcaster.client_count()
+    }
+
+    fn is_running(&self) -> bool {
+        self.is_running
+    }
+
+    fn stop(&mut self) -> Result<()> {
+        self.is_running = false;
+        Ok(())
+    }
+}
+
+// Only alerts are sent, state updates are ignored
+impl StateOutputModule for AlertOutput {
+    fn broadcast_aircraft_update(&self, _address: &AircraftAddress, _record: &AircraftRecord) -> Result<()> {
+        Ok(())
+    }
+
+    fn broadcast_event(&self, event: &TrackerEvent) -> Result<()> {
+        match AlertMessage::from_event(event) {
+            Some(message) => self.broadcaster.broadcast_message(message),
+            None => Ok(()),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{FlightEnd, TrackerEventKind};
+
+    #[test]
+    fn test_alert_message() {
+        let address = AircraftAddress::icao(adsb_deku::ICAO([0x48, 0x40, 0xd6]));
+        let alert = TrackerEvent::new(
+            address,
+            TrackerEventKind::WatchlistDisappeared {
+                entry: "Air Force One".to_string(),
+            },
+        );
+        let message = AlertMessage::from_event(&alert).unwrap();
+        assert!(message.line.starts_with("{\"address\":\"4840d6\""));
+        assert!(message.line.contains("\"type\":\"watchlist_disappeared\""));
+        assert!(message.line.ends_with("}\n"));
+
+        let finished = TrackerEventKind::FlightFinished {
+            flight: 1,
+            callsign: None,
+            end: FlightEnd::Lost,
+        };
+        assert!(AlertMessage::from_event(&TrackerEvent::new(address, finished)).is_none());
+    }
+}
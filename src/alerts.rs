@@ -0,0 +1,194 @@
+//! Special-condition alerts
+//!
+//! The tracker raises an alert when an aircraft squawks 7500, 7600 or 7700,
+//! declares an emergency in its ADS-B status, or sets the special position
+//! identification (SPI) flag. An alert lasts from the first report of the
+//! condition until a report without it, or until the condition was not
+//! reported for a while. Repeated reports of an active condition are merged
+//! into the same alert.
+
+use crate::{AircraftAddress, EmergencyState};
+use serde::Serialize;
+use serde_with::DisplayFromStr;
+use serde_with::serde_as;
+use std::collections::HashMap;
+use std::time::{Duration, SystemTime};
+
+/// An alert ends if its condition was not reported for this long
+pub const ALERT_TIMEOUT: Duration = Duration::from_secs(60);
+
+/// Kind of a special condition, an aircraft has at most one alert per kind
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub enum AlertKind {
+    Squawk,
+    EmergencyStatus,
+    Spi,
+}
+
+/// A special condition of an aircraft
+#[derive(Serialize, Clone, Copy, Debug, PartialEq, Eq)]
+#[serde(tag = "kind", rename_all = "snake_case")]
+pub enum AlertCondition {
+    /// Squawk 7500 (unlawful interference), 7600 (radio failure) or 7700
+    /// (general emergency)
+    EmergencySquawk { squawk: u16 },
+    /// Emergency declared in the ADS-B aircraft status
+    EmergencyStatus { state: EmergencyState },
+    /// Special position identification, set while the crew presses IDENT
+    Spi,
+}
+
+impl AlertCondition {
+    pub fn kind(&self) -> AlertKind {
+        match self {
+            Self::EmergencySquawk { .. } => AlertKind::Squawk,
+            Self::EmergencyStatus { .. } => AlertKind::EmergencyStatus,
+            Self::Spi => AlertKind::Spi,
+        }
+    }
+}
+
+/// An alert of an aircraft
+#[serde_as]
+#[derive(Serialize, Clone, Debug, PartialEq)]
+pub struct Alert {
+    #[serde_as(as = "DisplayFromStr")]
+    pub address: AircraftAddress,
+    pub condition: AlertCondition,
+    pub start: SystemTime,
+    /// When the condition ended, `None` while it is active
+    pub end: Option<SystemTime>,
+    /// When the condition was last reported
+    pub last_seen: SystemTime,
+}
+
+/// An alert that started or ended
+#[derive(Clone, Debug, PartialEq)]
+pub enum AlertChange {
+    Started(Alert),
+    Ended(Alert),
+}
+
+/// Keeps the active alerts of all aircraft
+#[derive(Debug, Default)]
+pub struct AlertManager {
+    active: HashMap<(AircraftAddress, AlertKind), Alert>,
+}
+
+impl AlertManager {
+    /// Report whether a condition of a kind is present for an aircraft
+    ///
+    /// Returns the alert that ended if the condition is gone or changed, and
+    /// the alert that started if it is new.
+    pub fn report(
+        &mut self,
+        address: AircraftAddress,
+        kind: AlertKind,
+        condition: Option<AlertCondition>,
+        now: SystemTime,
+    ) -> Vec<AlertChange> {
+        let key = (address, kind);
+        if let Some(alert) = self.active.get_mut(&key).filter(|alert| Some(alert.condition) == condition) {
+            alert.last_seen = now;
+            return Vec::new();
+        }
+
+        let mut changes = Vec::new();
+        if let Some(mut alert) = self.active.remove(&key) {
+            alert.end = Some(now);
+            changes.push(AlertChange::Ended(alert));
+        }
+        if let Some(condition) = condition {
+            let alert = Alert {
+                address,
+                condition,
+                start: now,
+                end: None,
+                last_seen: now,
+            };
+            self.active.insert(key, alert.clone());
+            changes.push(AlertChange::Started(alert));
+        }
+        changes
+    }
+
+    /// End the alerts whose condition was not reported within the timeout,
+    /// as of their last report
+    pub fn expire(&mut self, now: SystemTime) -> Vec<AlertChange> {
+        let expired: Vec<_> = self
+            .active
+            .iter()
+            .filter(|(_, alert)| alert.last_seen + ALERT_TIMEOUT < now)
+            .map(|(key, _)| *key)
+            .collect();
+        expired
+            .iter()
+            .filter_map(|key| self.active.remove(key))
+            .map(|mut alert| {
+                alert.end = Some(alert.last_seen);
+                AlertChange::Ended(alert)
+            })
+            .collect()
+    }
+
+    /// Whether an aircraft has an active alert of a kind
+    pub fn is_active(&self, address: AircraftAddress, kind: AlertKind) -> bool {
+        self.active.contains_key(&(address, kind))
+    }
+
+    /// The active alerts, oldest first
+    pub fn active(&self) -> Vec<&Alert> {
+        let mut alerts: Vec<&Alert> = self.active.values().collect();
+        alerts.sort_by_key(|alert| alert.start);
+        alerts
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn address() -> AircraftAddress {
+        AircraftAddress::icao(adsb_deku::ICAO([0x48, 0x40, 0xd6]))
+    }
+
+    #[test]
+    fn test_alert_lifecycle() {
+        let mut alerts = AlertManager::default();
+        let now = SystemTime::now();
+        let squawk = |squawk| Some(AlertCondition::EmergencySquawk { squawk });
+
+        let changes = alerts.report(address(), AlertKind::Squawk, squawk(7700), now);
+        assert!(matches!(&changes[..], [AlertChange::Started(alert)] if alert.start == now));
+
+        // Repeated reports are merged into the active alert
+        let later = now + Duration::from_secs(5);
+        assert!(alerts.report(address(), AlertKind::Squawk, squawk(7700), later).is_empty());
+        assert!(alerts.is_active(address(), AlertKind::Squawk));
+
+        // A different emergency code ends the alert and starts a new one
+        let changes = alerts.report(address(), AlertKind::Squawk, squawk(7600), later);
+        assert!(matches!(
+            &changes[..],
+            [AlertChange::Ended(ended), AlertChange::Started(started)]
+                if ended.end == Some(later) && started.condition == AlertCondition::EmergencySquawk { squawk: 7600 }
+        ));
+
+        let changes = alerts.report(address(), AlertKind::Squawk, None, later);
+        assert!(matches!(&changes[..], [AlertChange::Ended(_)]));
+        assert!(alerts.active().is_empty());
+        assert!(alerts.report(address(), AlertKind::Squawk, None, later).is_empty());
+    }
+
+    #[test]
+    fn test_alert_expiry() {
+        let mut alerts = AlertManager::default();
+        let now = SystemTime::now();
+        alerts.report(address(), AlertKind::Spi, Some(AlertCondition::Spi), now);
+
+        assert!(alerts.expire(now + ALERT_TIMEOUT).is_empty());
+        let changes = alerts.expire(now + ALERT_TIMEOUT * 2);
+        assert!(matches!(&changes[..], [AlertChange::Ended(alert)] if alert.end == Some(now)));
+        assert!(!alerts.is_active(address(), AlertKind::Spi));
+    }
+}
@@ -168,12 +168,15 @@ pub struct AvrOutput {
     port: u16,
     broadcaster: AvrBroadcaster,
     is_running: bool,
+    /// Also relay duplicate frames (`keep_duplicates` extra option), for MLAT
+    keep_duplicates: bool,
 }
 
 impl AvrOutput {
     /// Create a new AVR output module
     pub async fn new(config: crate::output_module::OutputModuleConfig) -> Result<Self> {
         let (broadcaster, receiver) = AvrBroadcaster::new(config.buffer_capacity);
+        let keep_duplicates = config.extra.get("keep_duplicates").is_some_and(|v| v == "true");
         
         // Start the server
         let server = AvrServer::new(config.port, receiver).await?;
@@ -188,6 +191,7 @@ impl AvrOutput {
             port: config.port,
             broadcaster,
             is_running: true,
+            keep_duplicates,
         })
     }
 }
@@ -226,6 +230,10 @@ impl crate::output_module::RawOutputModule for AvrOutput {
     fn broadcast_raw_packet(&self, data: &[u8], metadata: &DecoderMetaData) -> Result<()> {
         self.broadcaster.broadcast_packet(data, metadata)
     }
+
+    fn keeps_duplicates(&self) -> bool {
+        self.keep_duplicates
+    }
 }
 
 // Keep legacy trait implementation for backward compatibility during migration
@@ -274,6 +282,7 @@ mod tests {
             preamble_index: 12345,
             preamble_correlation: 15.5,
             crc_passed: true,
+            duplicate: false,
             timestamp: SystemTime::now(),
         };
         
@@ -293,6 +302,7 @@ mod tests {
             preamble_index: 12345,
             preamble_correlation: 25.0,
             crc_passed: true,
+            duplicate: false,
             timestamp: SystemTime::now(),
         };
         
@@ -309,6 +319,7 @@ mod tests {
             preamble_index: 0,
             preamble_correlation: 25.0, // Should map to -25 dBFS
             crc_passed: true,
+            duplicate: false,
             timestamp: SystemTime::now(),
         };
         
@@ -20,10 +20,12 @@ const BEAST_ESCAPE: u8 = 0x1A;
 #[repr(u8)]
 #[derive(Debug, Clone, Copy)]
 pub enum BeastMessageType {
+    /// Mode-A/C reply
+    ModeAC = 0x31,
     /// Mode-S short frame (56 bits)
-    ModeS = 0x31,
+    ModeS = 0x32,
     /// Mode-S long frame (112 bits) 
-    ModeSLong = 0x32,
+    ModeSLong = 0x33,
     /// Status message
     Status = 0x34,
 }
@@ -206,12 +208,15 @@ pub struct BeastOutput {
     port: u16,
     broadcaster: BeastBroadcaster,
     is_running: bool,
+    /// Also relay duplicate frames (`keep_duplicates` extra option), for MLAT
+    keep_duplicates: bool,
 }
 
 impl BeastOutput {
     /// Create a new BEAST output module
     pub async fn new(config: crate::output_module::OutputModuleConfig) -> Result<Self> {
         let (broadcaster, receiver) = BeastBroadcaster::new(config.buffer_capacity);
+        let keep_duplicates = config.extra.get("keep_duplicates").is_some_and(|v| v == "true");
         
         // Start the server
         let server = BeastServer::new(config.port, receiver).await?;
@@ -226,6 +231,7 @@ impl BeastOutput {
             port: config.port,
             broadcaster,
             is_running: true,
+            keep_duplicates,
         })
     }
 }
@@ -264,6 +270,10 @@ impl crate::output_module::RawOutputModule for BeastOutput {
     fn broadcast_raw_packet(&self, data: &[u8], metadata: &DecoderMetaData) -> Result<()> {
         self.broadcaster.broadcast_packet(data, metadata)
     }
+
+    fn keeps_duplicates(&self) -> bool {
+        self.keep_duplicates
+    }
 }
 
 // Keep legacy trait implementation for backward compatibility during migration
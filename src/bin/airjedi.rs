@@ -1,10 +1,23 @@
+use airjedi::AircraftDatabase;
 use airjedi::DEMOD_SAMPLE_RATE;
+use airjedi::RouteDatabase;
+use airjedi::SnapshotConfig;
+use airjedi::Geofence;
+use airjedi::IntegrityPolicy;
+use airjedi::KalmanConfig;
 use airjedi::OutputModuleManager;
-use airjedi::{BeastOutput, AvrOutput, RawOutput, Sbs1Output, WebSocketOutput};
+use airjedi::{AlertOutput, BeastOutput, AvrOutput, RawOutput, Sbs1Output, WebSocketOutput};
+use airjedi::CoverageConfig;
 use airjedi::Decoder;
+use airjedi::DecoderConfig;
 use airjedi::Demodulator;
 use airjedi::PreambleDetector;
+use airjedi::QuarantineConfig;
+use airjedi::ReceiverLocation;
+use airjedi::RetentionConfig;
 use airjedi::Tracker;
+use airjedi::TrackerConfig;
+use airjedi::Watchlist;
 use airjedi::RateLimitConfig;
 use anyhow::Result;
 use clap::Parser;
@@ -45,6 +58,87 @@ struct Args {
     /// Remove aircrafts when no packets have been received for the specified number of seconds
     #[arg(short, long)]
     lifetime: Option<u64>,
+    /// Maximum number of positions and velocities kept per aircraft
+    #[arg(long)]
+    max_points: Option<usize>,
+    /// Drop positions and velocities older than the specified number of seconds
+    #[arg(long)]
+    max_history_age: Option<u64>,
+    /// Simplify position trails, dropping points within this many nautical miles of the line
+    #[arg(long)]
+    simplify_nm: Option<f64>,
+    /// Maximum number of tracked aircraft, the least recently seen is evicted first
+    #[arg(long)]
+    max_aircraft: Option<usize>,
+    /// Mark fields of the current aircraft state as stale after this many seconds
+    #[arg(long, default_value_t = 60)]
+    stale_after: u64,
+    /// Smooth aircraft tracks with a Kalman filter and predict positions between fixes
+    #[arg(long)]
+    kalman: bool,
+    /// Start a new flight after an aircraft was not heard for this many seconds
+    #[arg(long, default_value_t = 1800)]
+    flight_gap: u64,
+    /// Receiver latitude in degrees, used to decode positions and compute ranges
+    #[arg(long, requires = "lon", allow_hyphen_values = true)]
+    lat: Option<f64>,
+    /// Receiver longitude in degrees, used to decode positions and compute ranges
+    #[arg(long, requires = "lat", allow_hyphen_values = true)]
+    lon: Option<f64>,
+    /// Receiver antenna altitude in feet above mean sea level
+    #[arg(long, requires = "lat", default_value_t = 0.0, allow_hyphen_values = true)]
+    alt: f64,
+    /// Range of the receiver in nautical miles, positions decoded relative to it further away are rejected
+    #[arg(long, requires = "lat", default_value_t = 180.0)]
+    max_range: f64,
+    /// Persist the receiver coverage outline to this JSON file
+    #[arg(long, requires = "lat")]
+    coverage: Option<std::path::PathBuf>,
+    /// Number of bearing sectors of the coverage outline
+    #[arg(long, requires = "lat", default_value_t = 360, value_parser = clap::value_parser!(u64).range(1..=3600))]
+    coverage_sectors: u64,
+    /// Save the aircraft register to this JSON file periodically and restore it on startup
+    #[arg(long)]
+    snapshot: Option<std::path::PathBuf>,
+    /// Seconds between snapshots of the aircraft register
+    #[arg(long, requires = "snapshot", default_value_t = 60, value_parser = clap::value_parser!(u64).range(1..))]
+    snapshot_interval: u64,
+    /// Load geofences from this GeoJSON file, checked on every position
+    #[arg(long)]
+    geofences: Option<std::path::PathBuf>,
+    /// Load a watchlist of ICAO addresses, address ranges and callsign patterns from this file
+    #[arg(long)]
+    watchlist: Option<std::path::PathBuf>,
+    /// Add registrations, types and operators from this aircraft database (tar1090-db CSV, or JSON)
+    #[arg(long)]
+    aircraft_db: Option<std::path::PathBuf>,
+    /// Add origin and destination to flights from this CSV file of routes by callsign
+    #[arg(long)]
+    routes: Option<std::path::PathBuf>,
+    /// Add the airline to flights from this CSV file of airlines by ICAO designator
+    #[arg(long)]
+    airlines: Option<std::path::PathBuf>,
+    /// Positions with a lower Navigation Integrity Category are marked as low integrity
+    #[arg(long, value_parser = clap::value_parser!(u8).range(0..=11))]
+    min_nic: Option<u8>,
+    /// Drop low integrity positions instead of marking them
+    #[arg(long, requires = "min_nic")]
+    filter_low_integrity: bool,
+    /// Write frames that pass the CRC but cannot be parsed to this JSON lines file
+    #[arg(long)]
+    quarantine: Option<std::path::PathBuf>,
+    /// Size in MB after which the quarantine file is rotated
+    #[arg(long, default_value_t = 10)]
+    quarantine_max_mb: u64,
+    /// Number of rotated quarantine files to keep
+    #[arg(long, default_value_t = 5)]
+    quarantine_files: usize,
+    /// Identical frames received within this many milliseconds are duplicates, e.g. 5 (0 disables)
+    #[arg(long, default_value_t = 0)]
+    dedup_window_ms: u64,
+    /// Raw outputs that also relay duplicate frames, e.g. for MLAT (beast, avr, raw)
+    #[arg(long, value_delimiter = ',')]
+    keep_duplicates: Vec<String>,
 
     // Rate limiting arguments
     /// Enable rate limiting to reduce CPU usage on high-frequency updates
@@ -97,6 +191,9 @@ struct Args {
     /// Port for SBS-1/BaseStation format output
     #[arg(long, default_value_t = 30003)]
     sbs1_port: u16,
+    /// Append autopilot intent columns to SBS-1 messages
+    #[arg(long)]
+    sbs1_extended: bool,
 
     /// Enable WebSocket output for real-time web application streaming
     #[arg(long)]
@@ -105,6 +202,13 @@ struct Args {
     #[arg(long, default_value_t = 30008)]
     websocket_port: u16,
 
+    /// Enable the alert channel, a JSON lines stream of tracker alerts
+    #[arg(long)]
+    alerts: bool,
+    /// Port for the alert channel
+    #[arg(long, default_value_t = 30009)]
+    alerts_port: u16,
+
     /// List available RTL-SDR devices and exit
     #[arg(long)]
     list_devices: bool,
@@ -437,7 +541,19 @@ async fn main() -> Result<()> {
     let adsb_demod = fg.add_block(Demodulator::new())?;
     fg.connect_stream(preamble_detector, "out", adsb_demod, "in")?;
 
-    let adsb_decoder = fg.add_block(Decoder::new(false))?;
+    let decoder_config = DecoderConfig {
+        quarantine: args.quarantine.map(|path| {
+            println!("Quarantining unparseable frames to {}", path.display());
+            QuarantineConfig {
+                path,
+                max_bytes: args.quarantine_max_mb * 1024 * 1024,
+                max_files: args.quarantine_files,
+            }
+        }),
+        dedup_window: (args.dedup_window_ms > 0).then(|| Duration::from_millis(args.dedup_window_ms)),
+        ..Default::default()
+    };
+    let adsb_decoder = fg.add_block(Decoder::with_config(decoder_config))?;
     fg.connect_message(adsb_demod, "out", adsb_decoder, "in")?;
 
     // Set up dynamic output module system
@@ -445,7 +561,10 @@ async fn main() -> Result<()> {
 
     // Register raw output modules (BEAST, Raw, AVR)
     if args.beast && !args.no_beast {
-        let config = airjedi::OutputModuleConfig::new("beast", args.beast_port).with_buffer_capacity(1024);
+        let mut config = airjedi::OutputModuleConfig::new("beast", args.beast_port).with_buffer_capacity(1024);
+        if args.keep_duplicates.iter().any(|m| m == "beast") {
+            config = config.with_extra("keep_duplicates", "true");
+        }
         match BeastOutput::new(config).await {
             Ok(module) => {
                 println!("BEAST mode server started on port {}", args.beast_port);
@@ -456,7 +575,10 @@ async fn main() -> Result<()> {
     }
 
     if args.avr {
-        let config = airjedi::OutputModuleConfig::new("avr", args.avr_port).with_buffer_capacity(1024);
+        let mut config = airjedi::OutputModuleConfig::new("avr", args.avr_port).with_buffer_capacity(1024);
+        if args.keep_duplicates.iter().any(|m| m == "avr") {
+            config = config.with_extra("keep_duplicates", "true");
+        }
         match AvrOutput::new(config).await {
             Ok(module) => {
                 println!("AVR format server started on port {}", args.avr_port);
@@ -467,7 +589,10 @@ async fn main() -> Result<()> {
     }
 
     if args.raw && !args.no_raw {
-        let config = airjedi::OutputModuleConfig::new("raw", args.raw_port).with_buffer_capacity(1024);
+        let mut config = airjedi::OutputModuleConfig::new("raw", args.raw_port).with_buffer_capacity(1024);
+        if args.keep_duplicates.iter().any(|m| m == "raw") {
+            config = config.with_extra("keep_duplicates", "true");
+        }
         match RawOutput::new(config).await {
             Ok(module) => {
                 println!("Raw format server started on port {}", args.raw_port);
@@ -490,7 +615,10 @@ async fn main() -> Result<()> {
 
     // Register state output modules (SBS-1, WebSocket)
     if args.sbs1 {
-        let config = airjedi::OutputModuleConfig::new("sbs1", args.sbs1_port).with_buffer_capacity(1024);
+        let mut config = airjedi::OutputModuleConfig::new("sbs1", args.sbs1_port).with_buffer_capacity(1024);
+        if args.sbs1_extended {
+            config = config.with_extra("extended", "true");
+        }
         match Sbs1Output::new(config).await {
             Ok(module) => {
                 println!("SBS-1/BaseStation format server started on port {}", args.sbs1_port);
@@ -500,29 +628,140 @@ async fn main() -> Result<()> {
         }
     }
 
+    if args.alerts {
+        let config = airjedi::OutputModuleConfig::new("alerts", args.alerts_port).with_buffer_capacity(1024);
+        match AlertOutput::new(config).await {
+            Ok(module) => {
+                println!("Alert channel started on port {}", args.alerts_port);
+                output_manager.add_state_module(Box::new(module));
+            }
+            Err(e) => eprintln!("Failed to start alert channel: {}", e),
+        }
+    }
+
     // Create tracker with dynamic output module system and optional rate limiting
-    let prune_after = args.lifetime.map(Duration::from_secs);
-    let tracker = if args.rate_limit {
-        let rate_config = RateLimitConfig {
+    let mut tracker_config = TrackerConfig {
+        prune_after: args.lifetime.map(Duration::from_secs),
+        retention: RetentionConfig {
+            max_points: args.max_points,
+            max_age: args.max_history_age.map(Duration::from_secs),
+            simplify_tolerance_nm: args.simplify_nm,
+        },
+        max_aircraft: args.max_aircraft,
+        stale_after: Some(Duration::from_secs(args.stale_after)),
+        kalman: args.kalman.then(KalmanConfig::default),
+        flight_gap: Some(Duration::from_secs(args.flight_gap)),
+        ..Default::default()
+    };
+    if args.rate_limit {
+        tracker_config.rate_limit = Some(RateLimitConfig {
             position_interval: Duration::from_millis(args.position_rate_ms),
             velocity_interval: Duration::from_millis(args.velocity_rate_ms),
             identification_interval: Duration::from_millis(args.identification_rate_ms),
             metadata_interval: Duration::from_millis(args.metadata_rate_ms),
-        };
+        });
         println!(
             "Rate limiting enabled: Position {}ms, Velocity {}ms, ID {}ms, Metadata {}ms",
             args.position_rate_ms, args.velocity_rate_ms, args.identification_rate_ms, args.metadata_rate_ms
         );
-        Tracker::new_with_modules_and_rate_limiting(prune_after, output_manager, Some(rate_config))
-    } else {
-        Tracker::new_with_modules(prune_after, output_manager)
-    };
-    
+    }
+    if let (Some(latitude), Some(longitude)) = (args.lat, args.lon) {
+        tracker_config.receiver = Some(ReceiverLocation {
+            latitude,
+            longitude,
+            altitude: args.alt,
+        });
+        println!("Receiver location: {:.4}, {:.4}, {:.0} ft", latitude, longitude, args.alt);
+        tracker_config.max_receiver_range_nm = Some(args.max_range);
+        tracker_config.coverage = Some(CoverageConfig {
+            path: args.coverage.clone(),
+            sectors: args.coverage_sectors as usize,
+            ..Default::default()
+        });
+        if let Some(path) = &args.coverage {
+            println!("Coverage persisted to {}", path.display());
+        }
+    }
+    if let Some(path) = &args.snapshot {
+        tracker_config.snapshot = Some(SnapshotConfig {
+            path: path.clone(),
+            interval: Duration::from_secs(args.snapshot_interval),
+        });
+        println!("Aircraft register persisted to {}", path.display());
+    }
+    if let Some(path) = &args.geofences {
+        tracker_config.geofences = Geofence::load(path)?;
+        println!("Loaded {} geofences from {}", tracker_config.geofences.len(), path.display());
+    }
+    if let Some(path) = &args.watchlist {
+        tracker_config.watchlist = Watchlist::load(path)?;
+        println!("Loaded {} watchlist entries from {}", tracker_config.watchlist.entries().len(), path.display());
+    }
+    if let Some(path) = &args.aircraft_db {
+        tracker_config.database = AircraftDatabase::load(path)?;
+        println!("Loaded {} aircraft from {}", tracker_config.database.len(), path.display());
+    }
+    if let Some(path) = &args.routes {
+        tracker_config.routes.load_routes(path)?;
+        println!("Loaded {} routes from {}", tracker_config.routes.route_count(), path.display());
+    }
+    if let Some(path) = &args.airlines {
+        tracker_config.routes.load_airlines(path)?;
+        println!("Loaded {} airlines from {}", tracker_config.routes.airline_count(), path.display());
+    }
+    if let Some(min_nic) = args.min_nic {
+        tracker_config.min_nic = Some(min_nic);
+        if args.filter_low_integrity {
+            tracker_config.integrity_policy = IntegrityPolicy::Filter;
+        }
+        println!(
+            "Positions with NIC below {} are {}",
+            min_nic,
+            if args.filter_low_integrity { "dropped" } else { "marked as low integrity" }
+        );
+    }
+    let tracker = Tracker::with_config(tracker_config, output_manager);
+
     let adsb_tracker = fg.add_block(tracker)?;
     fg.connect_message(adsb_decoder, "out", adsb_tracker, "in")?;
 
     println!("Please open the map in the browser: http://127.0.0.1:1337/");
-    Runtime::new().run(fg)?;
+    let runtime = Runtime::new();
+    let (flowgraph, mut handle) = runtime.start(fg).await?;
+
+    // Stop the flowgraph on SIGTERM or Ctrl-C, so that the tracker saves the
+    // register and the coverage before the process exits
+    tokio::spawn(async move {
+        if let Err(e) = shutdown_signal().await {
+            warn!("Could not wait for a shutdown signal: {}", e);
+            return;
+        }
+        println!("Stopping");
+        if let Err(e) = handle.terminate().await {
+            warn!("Could not stop the flowgraph: {}", e);
+        }
+    });
+    flowgraph.await?;
+
+    Ok(())
+}
+
+/// Wait for SIGTERM, as sent by systemd to stop the service, or Ctrl-C
+#[cfg(unix)]
+async fn shutdown_signal() -> Result<()> {
+    use tokio::signal::unix::{SignalKind, signal};
+
+    let mut terminate = signal(SignalKind::terminate())?;
+    tokio::select! {
+        result = tokio::signal::ctrl_c() => result?,
+        _ = terminate.recv() => {}
+    }
+    Ok(())
+}
 
+/// Wait for Ctrl-C
+#[cfg(not(unix))]
+async fn shutdown_signal() -> Result<()> {
+    tokio::signal::ctrl_c().await?;
     Ok(())
 }
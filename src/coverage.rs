@@ -0,0 +1,412 @@
+//! Polar coverage of the receiver
+//!
+//! The coverage keeps the longest range at which a position was received in
+//! each bearing sector, separately per altitude band. Comparing the outline
+//! before and after a change shows how an antenna installation performs.
+//!
+//! Ranges are kept in hourly buckets, so that the outline of a recent time
+//! window can be queried, and in an all-time grid. The data is saved to disk
+//! periodically and loaded again on startup.
+//!
+//! ## JSON Format
+//! Sectors without a position have a `null` range:
+//! ```json
+//! {"sectors":360,"bands":[{"min_altitude":null,"max_altitude":10000,"ranges":[12.3,null,...]},...]}
+//! ```
+
+use crate::ReceiverLocation;
+use crate::geo;
+use serde::{Deserialize, Serialize};
+use serde_json::{Value, json};
+use std::collections::BTreeMap;
+use std::fs::{self, File};
+use std::io::{self, Write};
+use std::path::PathBuf;
+use std::time::{Duration, Instant, SystemTime, UNIX_EPOCH};
+
+/// How often the coverage is saved to disk when it changed
+const SAVE_INTERVAL: Duration = Duration::from_secs(60);
+
+/// Configuration of the coverage
+#[derive(Debug, Clone)]
+pub struct CoverageConfig {
+    /// File the coverage is persisted to, kept in memory only if `None`
+    pub path: Option<PathBuf>,
+    /// Number of bearing sectors
+    pub sectors: usize,
+    /// Upper altitudes in feet of all but the highest altitude band
+    pub band_edges: Vec<i32>,
+    /// Number of hourly buckets kept for time window queries
+    pub retain_hours: u64,
+}
+
+impl Default for CoverageConfig {
+    fn default() -> Self {
+        Self {
+            path: None,
+            sectors: 360,
+            band_edges: vec![10000, 20000, 30000],
+            retain_hours: 7 * 24,
+        }
+    }
+}
+
+/// Maximum range in NM per altitude band and bearing sector, zero if no
+/// position was received
+type Grid = Vec<Vec<f32>>;
+
+/// The persisted coverage data
+#[derive(Serialize, Deserialize, Debug, Default)]
+struct CoverageData {
+    sectors: usize,
+    band_edges: Vec<i32>,
+    all_time: Grid,
+    /// Grids by hours since the epoch
+    hourly: BTreeMap<u64, Grid>,
+}
+
+impl CoverageData {
+    /// Whether the data, including the size of every grid, has the sectors
+    /// and altitude bands of the configuration
+    fn matches(&self, config: &CoverageConfig) -> bool {
+        let grid_matches = |grid: &Grid| {
+            grid.len() == config.band_edges.len() + 1 && grid.iter().all(|ranges| ranges.len() == config.sectors)
+        };
+        self.sectors == config.sectors
+            && self.band_edges == config.band_edges
+            && grid_matches(&self.all_time)
+            && self.hourly.values().all(grid_matches)
+    }
+}
+
+/// The polar coverage of the receiver
+pub struct Coverage {
+    config: CoverageConfig,
+    data: CoverageData,
+    /// Whether the data changed since it was last saved
+    dirty: bool,
+    last_save: Instant,
+}
+
+impl Coverage {
+    /// Create the coverage, loading it from disk if it was saved before
+    pub fn new(config: CoverageConfig) -> Self {
+        let mut coverage = Self {
+            data: CoverageData::default(),
+            config,
+            dirty: false,
+            last_save: Instant::now(),
+        };
+        coverage.reset();
+        coverage.dirty = false;
+
+        if let Some(path) = &coverage.config.path {
+            match fs::read_to_string(path) {
+                Ok(content) => match serde_json::from_str::<CoverageData>(&content) {
+                    Ok(data) if data.matches(&coverage.config) => {
+                        coverage.data = data;
+                        // Forget the buckets that expired while the tracker was stopped
+                        coverage.forget_expired(Self::hour(SystemTime::now()));
+                    }
+                    Ok(_) => tracing::warn!(
+                        "Coverage in {} does not match the sectors and altitude bands, starting over",
+                        path.display()
+                    ),
+                    Err(e) => tracing::warn!("Could not parse coverage in {}: {}", path.display(), e),
+                },
+                Err(e) if e.kind() == io::ErrorKind::NotFound => {}
+                Err(e) => tracing::warn!("Could not read coverage from {}: {}", path.display(), e),
+            }
+        }
+        coverage
+    }
+
+    fn empty_grid(&self) -> Grid {
+        vec![vec![0.0; self.config.sectors]; self.config.band_edges.len() + 1]
+    }
+
+    fn hour(time: SystemTime) -> u64 {
+        time.duration_since(UNIX_EPOCH).unwrap_or_default().as_secs() / 3600
+    }
+
+    /// Altitude band of an altitude in feet, the lowest if it is unknown
+    fn band(&self, altitude: Option<f64>) -> usize {
+        altitude.map_or(0, |altitude| {
+            self.config.band_edges.partition_point(|&edge| f64::from(edge) <= altitude)
+        })
+    }
+
+    fn sector(&self, bearing: f64) -> usize {
+        (bearing.rem_euclid(360.0) / 360.0 * self.config.sectors as f64) as usize % self.config.sectors
+    }
+
+    /// Forget the hourly buckets that fell out of the retained time at `hour`
+    fn forget_expired(&mut self, hour: u64) {
+        let first = hour.saturating_sub(self.config.retain_hours.saturating_sub(1));
+        self.data.hourly = self.data.hourly.split_off(&first);
+    }
+
+    /// Record a position at a bearing and distance from the receiver
+    pub fn record(&mut self, bearing: f64, distance_nm: f64, altitude: Option<f64>, now: SystemTime) {
+        let (band, sector) = (self.band(altitude), self.sector(bearing));
+        let range = distance_nm as f32;
+
+        let hour = Self::hour(now);
+        if !self.data.hourly.contains_key(&hour) {
+            let grid = self.empty_grid();
+            self.data.hourly.insert(hour, grid);
+            self.forget_expired(hour);
+        }
+        for grid in [&mut self.data.all_time, self.data.hourly.get_mut(&hour).unwrap()] {
+            if range > grid[band][sector] {
+                grid[band][sector] = range;
+                self.dirty = true;
+            }
+        }
+    }
+
+    /// Forget all recorded ranges
+    pub fn reset(&mut self) {
+        self.data = CoverageData {
+            sectors: self.config.sectors,
+            band_edges: self.config.band_edges.clone(),
+            all_time: self.empty_grid(),
+            hourly: BTreeMap::new(),
+        };
+        self.dirty = true;
+    }
+
+    /// The maximum ranges of the last hours, or of all time if `None`
+    fn grid(&self, hours: Option<u64>, now: SystemTime) -> Grid {
+        let Some(hours) = hours else {
+            return self.data.all_time.clone();
+        };
+        let first = Self::hour(now).saturating_sub(hours.saturating_sub(1));
+        let mut grid = self.empty_grid();
+        for hourly in self.data.hourly.range(first..).map(|(_, grid)| grid) {
+            for (band, ranges) in grid.iter_mut().zip(hourly) {
+                for (range, hourly_range) in band.iter_mut().zip(ranges) {
+                    *range = range.max(*hourly_range);
+                }
+            }
+        }
+        grid
+    }
+
+    /// Lower and upper altitude of a band
+    fn band_limits(&self, band: usize) -> (Option<i32>, Option<i32>) {
+        let edges = &self.config.band_edges;
+        let min = band.checked_sub(1).map(|i| edges[i]);
+        (min, edges.get(band).copied())
+    }
+
+    /// The coverage of the last hours, or of all time if `None`, as JSON
+    pub fn to_json(&self, hours: Option<u64>, now: SystemTime) -> Value {
+        let bands: Vec<Value> = self
+            .grid(hours, now)
+            .iter()
+            .enumerate()
+            .map(|(band, ranges)| {
+                let (min, max) = self.band_limits(band);
+                let ranges: Vec<Option<f32>> = ranges.iter().map(|&r| (r > 0.0).then_some(r)).collect();
+                json!({"min_altitude": min, "max_altitude": max, "ranges": ranges})
+            })
+            .collect();
+        json!({"sectors": self.config.sectors, "hours": hours, "bands": bands})
+    }
+
+    /// The coverage as GeoJSON, with a range outline polygon per altitude band
+    pub fn to_geojson(&self, receiver: &ReceiverLocation, hours: Option<u64>, now: SystemTime) -> Value {
+        let sector_width = 360.0 / self.config.sectors as f64;
+        let features: Vec<Value> = self
+            .grid(hours, now)
+            .iter()
+            .enumerate()
+            .filter(|(_, ranges)| ranges.iter().any(|&r| r > 0.0))
+            .map(|(band, ranges)| {
+                // Sectors without positions pull the outline back to the receiver
+                let mut ring: Vec<[f64; 2]> = ranges
+                    .iter()
+                    .enumerate()
+                    .map(|(sector, &range)| {
+                        let bearing = (sector as f64 + 0.5) * sector_width;
+                        let (lat, lon) = geo::destination(receiver.latitude, receiver.longitude, bearing, range.into());
+                        [lon, lat]
+                    })
+                    .collect();
+                ring.push(ring[0]);
+                let (min, max) = self.band_limits(band);
+                json!({
+                    "type": "Feature",
+                    "properties": {"min_altitude": min, "max_altitude": max},
+                    "geometry": {"type": "Polygon", "coordinates": [ring]},
+                })
+            })
+            .collect();
+        json!({"type": "FeatureCollection", "features": features})
+    }
+
+    /// Save the coverage to disk, replacing the previous file atomically
+    pub fn save(&mut self) -> io::Result<()> {
+        let Some(path) = &self.config.path else {
+            return Ok(());
+        };
+        let mut temp_path = path.clone().into_os_string();
+        temp_path.push(".tmp");
+        let mut file = File::create(&temp_path)?;
+        file.write_all(&serde_json::to_vec(&self.data)?)?;
+        // The data must be on disk before it replaces the previous file
+        file.sync_all()?;
+        fs::rename(&temp_path, path)?;
+        self.dirty = false;
+        self.last_save = Instant::now();
+        Ok(())
+    }
+
+    /// Save the coverage if it changed and was not saved for a while
+    pub fn save_if_due(&mut self) {
+        if !self.dirty || self.last_save.elapsed() < SAVE_INTERVAL {
+            return;
+        }
+        if let Err(e) = self.save() {
+            tracing::warn!("Could not save coverage: {}", e);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn coverage() -> Coverage {
+        Coverage::new(CoverageConfig {
+            sectors: 4,
+            band_edges: vec![10000],
+            ..Default::default()
+        })
+    }
+
+    #[test]
+    fn test_coverage_record() {
+        let mut coverage = coverage();
+        let now = SystemTime::now();
+        coverage.record(10.0, 50.0, Some(5000.0), now);
+        coverage.record(20.0, 40.0, Some(5000.0), now);
+        coverage.record(100.0, 120.0, Some(35000.0), now);
+        coverage.record(359.0, 30.0, None, now);
+
+        let json = coverage.to_json(None, now);
+        assert_eq!(json["bands"][0]["ranges"], json!([50.0, null, null, 30.0]));
+        assert_eq!(json["bands"][0]["max_altitude"], 10000);
+        assert_eq!(json["bands"][1]["ranges"], json!([null, 120.0, null, null]));
+        assert_eq!(json["bands"][1]["min_altitude"], 10000);
+
+        coverage.reset();
+        assert_eq!(coverage.to_json(None, now)["bands"][0]["ranges"], json!([null, null, null, null]));
+    }
+
+    #[test]
+    fn test_coverage_window() {
+        let mut coverage = coverage();
+        let now = SystemTime::now();
+        coverage.record(10.0, 50.0, None, now - Duration::from_secs(3 * 3600));
+        coverage.record(10.0, 20.0, None, now);
+
+        assert_eq!(coverage.to_json(Some(1), now)["bands"][0]["ranges"][0], 20.0);
+        assert_eq!(coverage.to_json(Some(4), now)["bands"][0]["ranges"][0], 50.0);
+        assert_eq!(coverage.to_json(None, now)["bands"][0]["ranges"][0], 50.0);
+    }
+
+    #[test]
+    fn test_coverage_persistence() {
+        let path = std::env::temp_dir().join(format!("airjedi-coverage-{}.json", std::process::id()));
+        let config = CoverageConfig {
+            path: Some(path.clone()),
+            sectors: 4,
+            band_edges: vec![10000],
+            ..Default::default()
+        };
+        let now = SystemTime::now();
+        let mut coverage = Coverage::new(config.clone());
+        coverage.record(190.0, 75.0, Some(12000.0), now);
+        coverage.save().unwrap();
+
+        let coverage = Coverage::new(config);
+        assert_eq!(coverage.to_json(None, now)["bands"][1]["ranges"][2], 75.0);
+        fs::remove_file(&path).unwrap();
+    }
+
+    #[test]
+    fn test_coverage_expired_hours() {
+        let path = std::env::temp_dir().join(format!("airjedi-coverage-expired-{}.json", std::process::id()));
+        let config = CoverageConfig {
+            path: Some(path.clone()),
+            sectors: 4,
+            band_edges: vec![10000],
+            retain_hours: 24,
+            ..Default::default()
+        };
+        let now = SystemTime::now();
+        let mut coverage = Coverage::new(config.clone());
+        coverage.record(10.0, 20.0, None, now - Duration::from_secs(2 * 3600));
+        coverage.record(10.0, 50.0, None, now - Duration::from_secs(30 * 3600));
+        assert_eq!(coverage.data.hourly.len(), 2);
+        coverage.save().unwrap();
+
+        // The bucket of 30 hours ago is older than the retained time when
+        // the coverage is loaded again
+        let coverage = Coverage::new(config);
+        let hour = Coverage::hour(now);
+        assert_eq!(coverage.data.hourly.keys().copied().collect::<Vec<_>>(), vec![hour - 2]);
+        assert_eq!(coverage.to_json(None, now)["bands"][0]["ranges"][0], 50.0);
+        fs::remove_file(&path).unwrap();
+    }
+
+    #[test]
+    fn test_coverage_invalid_grid() {
+        let path = std::env::temp_dir().join(format!("airjedi-coverage-invalid-{}.json", std::process::id()));
+        let config = CoverageConfig {
+            path: Some(path.clone()),
+            sectors: 4,
+            band_edges: vec![10000],
+            ..Default::default()
+        };
+        // The sectors and bands match, but an hourly grid is short a sector
+        let data = json!({
+            "sectors": 4,
+            "band_edges": [10000],
+            "all_time": [[50.0, 0.0, 0.0, 0.0], [0.0, 0.0, 0.0, 0.0]],
+            "hourly": {"1000": [[50.0, 0.0, 0.0], [0.0, 0.0, 0.0, 0.0]]},
+        });
+        fs::write(&path, data.to_string()).unwrap();
+
+        let now = SystemTime::now();
+        let mut coverage = Coverage::new(config);
+        assert_eq!(coverage.to_json(None, now)["bands"][0]["ranges"], json!([null, null, null, null]));
+        coverage.record(10.0, 20.0, None, now);
+        assert_eq!(coverage.to_json(Some(1), now)["bands"][0]["ranges"][0], 20.0);
+        fs::remove_file(&path).unwrap();
+    }
+
+    #[test]
+    fn test_coverage_geojson() {
+        let mut coverage = coverage();
+        let now = SystemTime::now();
+        coverage.record(45.0, 60.0, None, now);
+        let receiver = ReceiverLocation {
+            latitude: 52.0,
+            longitude: 4.0,
+            altitude: 0.0,
+        };
+
+        let geojson = coverage.to_geojson(&receiver, None, now);
+        let features = geojson["features"].as_array().unwrap();
+        assert_eq!(features.len(), 1);
+        let ring = features[0]["geometry"]["coordinates"][0].as_array().unwrap();
+        assert_eq!(ring.len(), 5);
+        assert_eq!(ring[0], ring[4]);
+        // The empty sectors collapse onto the receiver
+        assert!((ring[1][0].as_f64().unwrap() - 4.0).abs() < 1e-9);
+        assert!((ring[1][1].as_f64().unwrap() - 52.0).abs() < 1e-9);
+    }
+}
@@ -0,0 +1,119 @@
+//! Compact Position Reporting (CPR) decoding
+//!
+//! adsb_deku only implements the global decode of airborne positions from an
+//! even/odd frame pair. Surface positions use zones a quarter of the size, so
+//! a pair of frames still leaves four candidate positions. They are decoded
+//! locally instead, from a single frame and a reference position within
+//! 45 NM (such as the last known position or the receiver location).
+//!
+//! Airborne positions are decoded locally as well once a reference is known,
+//! so that every frame yields a position without waiting for a new pair.
+
+/// Number of latitude zones between the equator and a pole
+const NZ: f64 = 15.0;
+
+/// CPR coordinates are 17-bit fractions of a zone
+const CPR_SCALE: f64 = 131072.0;
+
+/// A single CPR encoded position, as received in a position message
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct CprFrame {
+    pub lat_cpr: u32,
+    pub lon_cpr: u32,
+    pub odd: bool,
+}
+
+/// Get the number of longitude zones at a latitude
+pub fn nl(lat: f64) -> u32 {
+    let lat = lat.abs();
+    if lat == 0.0 {
+        return 59;
+    }
+    if lat == 87.0 {
+        return 2;
+    }
+    if lat > 87.0 {
+        return 1;
+    }
+    let a = 1.0 - (std::f64::consts::PI / (2.0 * NZ)).cos();
+    let b = lat.to_radians().cos().powi(2);
+    (2.0 * std::f64::consts::PI / (1.0 - a / b).acos()).floor() as u32
+}
+
+/// Decode a CPR frame relative to a reference position
+///
+/// The reference must be within half a zone of the actual position: 180 NM
+/// for airborne and 45 NM for surface positions. Returns latitude and longitude
+/// in degrees, the longitude in [-180, 180).
+pub fn local_position(frame: &CprFrame, surface: bool, ref_lat: f64, ref_lon: f64) -> (f64, f64) {
+    let span = if surface { 90.0 } else { 360.0 };
+    let i = if frame.odd { 1.0 } else { 0.0 };
+    let lat_cpr = frame.lat_cpr as f64 / CPR_SCALE;
+    let lon_cpr = frame.lon_cpr as f64 / CPR_SCALE;
+
+    let dlat = span / (4.0 * NZ - i);
+    let j = (ref_lat / dlat).floor() + (0.5 + ref_lat.rem_euclid(dlat) / dlat - lat_cpr).floor();
+    let lat = dlat * (j + lat_cpr);
+
+    let zones = (nl(lat) as f64 - i).max(1.0);
+    let dlon = span / zones;
+    let m = (ref_lon / dlon).floor() + (0.5 + ref_lon.rem_euclid(dlon) / dlon - lon_cpr).floor();
+    // The zone east of the reference may lie across the antimeridian
+    let lon = (dlon * (m + lon_cpr) + 180.0).rem_euclid(360.0) - 180.0;
+
+    (lat, lon)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_nl() {
+        assert_eq!(nl(0.0), 59);
+        assert_eq!(nl(52.25720), 36);
+        assert_eq!(nl(-52.25720), 36);
+        assert_eq!(nl(87.0), 2);
+        assert_eq!(nl(89.0), 1);
+    }
+
+    #[test]
+    fn test_airborne_local_position() {
+        // Even airborne frame from 8D40621D58C382D690C8AC2863A7
+        let frame = CprFrame {
+            lat_cpr: 93000,
+            lon_cpr: 51372,
+            odd: false,
+        };
+        let (lat, lon) = local_position(&frame, false, 52.258, 3.918);
+        assert!((lat - 52.25720).abs() < 1e-4);
+        assert!((lon - 3.91937).abs() < 1e-4);
+    }
+
+    #[test]
+    fn test_local_position_antimeridian() {
+        // Even airborne frame at 52, -179.9, decoded from just west of the
+        // antimeridian
+        let frame = CprFrame {
+            lat_cpr: 87381,
+            lon_cpr: 1311,
+            odd: false,
+        };
+        let (lat, lon) = local_position(&frame, false, 52.0, 179.9);
+        assert!((lat - 52.0).abs() < 1e-4);
+        assert!((lon + 179.9).abs() < 1e-4);
+    }
+
+    #[test]
+    fn test_surface_local_position() {
+        // Odd surface frame from 8C4841753A9A153237AEF0F275BE, near Schiphol
+        let frame = CprFrame {
+            lat_cpr: 39195,
+            lon_cpr: 110320,
+            odd: true,
+        };
+        let (lat, lon) = local_position(&frame, true, 51.990, 4.375);
+        assert!((lat - 52.32056).abs() < 1e-4);
+        assert!((lon - 4.73574).abs() < 1e-4);
+    }
+}
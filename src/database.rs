@@ -0,0 +1,341 @@
+//! Local aircraft database
+//!
+//! The database adds the registration, ICAO type code, description, operator
+//! and year of build to the aircraft from a local dump of registrations. It
+//! reads the semicolon separated `aircraft.csv` of tar1090-db (decompressed)
+//! with the columns `icao;registration;type;flags;description;year;operator`,
+//! CSV files with a header naming the columns, and JSON objects keyed by the
+//! hex address or arrays of objects with an `icao` field:
+//! ```json
+//! {"4840d6":{"r":"PH-BXA","t":"B738","desc":"BOEING 737-800","ownOp":"KLM","year":"2000"}}
+//! ```
+//!
+//! All strings are kept in one buffer and the index only holds their offsets,
+//! so that a database of half a million aircraft stays small. Looking up an
+//! unknown address is a single hash probe.
+
+use crate::{AddressType, AircraftAddress};
+use anyhow::{Context, Result, bail};
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use std::collections::HashMap;
+use std::fs;
+use std::path::{Path, PathBuf};
+
+/// Details of an aircraft from the database
+#[derive(Serialize, Deserialize, Clone, Debug, Default, PartialEq)]
+pub struct AircraftInfo {
+    pub registration: Option<String>,
+    /// ICAO aircraft type designator, such as `B738`
+    pub type_code: Option<String>,
+    pub description: Option<String>,
+    pub operator: Option<String>,
+    /// Year of build
+    pub year: Option<u16>,
+}
+
+/// A string in the text buffer, empty if the field is unknown
+#[derive(Clone, Copy, Debug, Default)]
+struct Span {
+    start: u32,
+    len: u16,
+}
+
+#[derive(Clone, Copy, Debug)]
+struct Entry {
+    registration: Span,
+    type_code: Span,
+    description: Span,
+    operator: Span,
+    year: Option<u16>,
+}
+
+/// Columns of a CSV file
+#[derive(Clone, Copy, Debug)]
+struct Columns {
+    icao: usize,
+    registration: Option<usize>,
+    type_code: Option<usize>,
+    description: Option<usize>,
+    year: Option<usize>,
+    operator: Option<usize>,
+}
+
+/// Columns of tar1090-db's `aircraft.csv`, which has no header
+const TAR1090_COLUMNS: Columns = Columns {
+    icao: 0,
+    registration: Some(1),
+    type_code: Some(2),
+    description: Some(4),
+    year: Some(5),
+    operator: Some(6),
+};
+
+impl Columns {
+    fn from_header(header: &[&str]) -> Result<Self> {
+        let find = |names: &[&str]| {
+            header
+                .iter()
+                .position(|column| names.iter().any(|name| column.trim().eq_ignore_ascii_case(name)))
+        };
+        Ok(Self {
+            icao: find(&["icao", "icao24", "hex"]).context("No icao column in the header")?,
+            registration: find(&["registration", "reg", "r"]),
+            type_code: find(&["typecode", "type_code", "type", "t", "icaotype"]),
+            description: find(&["description", "desc", "model"]),
+            year: find(&["year", "built"]),
+            operator: find(&["operator", "ownop", "owner"]),
+        })
+    }
+}
+
+/// Parse a hex address
+fn parse_address(hex: &str) -> Option<u32> {
+    let hex = hex.trim().trim_start_matches('~');
+    Some(hex)
+        .filter(|hex| hex.len() == 6 && hex.chars().all(|c| c.is_ascii_hexdigit()))
+        .and_then(|hex| u32::from_str_radix(hex, 16).ok())
+}
+
+/// Split a CSV line, keeping separators inside double quotes
+pub(crate) fn split_csv(line: &str, separator: char) -> Vec<String> {
+    let mut fields = vec![String::new()];
+    let mut quoted = false;
+    let mut chars = line.chars().peekable();
+    while let Some(c) = chars.next() {
+        match c {
+            '"' if quoted && chars.peek() == Some(&'"') => {
+                chars.next();
+                fields.last_mut().unwrap().push('"');
+            }
+            '"' => quoted = !quoted,
+            c if c == separator && !quoted => fields.push(String::new()),
+            c => fields.last_mut().unwrap().push(c),
+        }
+    }
+    fields
+}
+
+/// The aircraft database
+#[derive(Clone, Debug, Default)]
+pub struct AircraftDatabase {
+    /// File the database was loaded from, for reloading
+    path: Option<PathBuf>,
+    index: HashMap<u32, Entry>,
+    text: String,
+}
+
+impl AircraftDatabase {
+    /// Load the database from a CSV or JSON file
+    pub fn load(path: &Path) -> Result<Self> {
+        let data = fs::read_to_string(path).with_context(|| format!("Failed to read {}", path.display()))?;
+        let mut database = Self::parse(&data).with_context(|| format!("Invalid aircraft database {}", path.display()))?;
+        database.path = Some(path.to_path_buf());
+        Ok(database)
+    }
+
+    /// Load the database again from its file, returns the number of aircraft
+    ///
+    /// The current data is kept if the file cannot be read.
+    pub fn reload(&mut self) -> Result<usize> {
+        let Some(path) = self.path.clone() else {
+            bail!("No aircraft database loaded");
+        };
+        *self = Self::load(&path)?;
+        Ok(self.len())
+    }
+
+    /// Parse the contents of a CSV or JSON file
+    pub fn parse(data: &str) -> Result<Self> {
+        let mut database = Self::default();
+        if data.trim_start().starts_with(['{', '[']) {
+            let value: Value = serde_json::from_str(data)?;
+            database.parse_json(&value)?;
+        } else {
+            database.parse_csv(data)?;
+        }
+        database.index.shrink_to_fit();
+        database.text.shrink_to_fit();
+        Ok(database)
+    }
+
+    fn parse_csv(&mut self, data: &str) -> Result<()> {
+        let mut lines = data.lines().filter(|line| !line.trim().is_empty()).peekable();
+        let Some(first) = lines.peek() else {
+            return Ok(());
+        };
+        let separator = if first.contains(';') { ';' } else { ',' };
+        let header = split_csv(first, separator);
+        let columns = match parse_address(&header[0]) {
+            Some(_) => TAR1090_COLUMNS,
+            None => {
+                let header: Vec<&str> = header.iter().map(String::as_str).collect();
+                let columns = Columns::from_header(&header)?;
+                lines.next();
+                columns
+            }
+        };
+
+        for line in lines {
+            let fields = split_csv(line, separator);
+            let field = |column: Option<usize>| column.and_then(|column| fields.get(column)).map_or("", |f| f.as_str());
+            let Some(address) = parse_address(field(Some(columns.icao))) else {
+                continue;
+            };
+            self.insert(
+                address,
+                [
+                    field(columns.registration),
+                    field(columns.type_code),
+                    field(columns.description),
+                    field(columns.operator),
+                ],
+                field(columns.year).trim().parse().ok(),
+            );
+        }
+        Ok(())
+    }
+
+    fn parse_json(&mut self, value: &Value) -> Result<()> {
+        let text = |aircraft: &Value, keys: &[&str]| {
+            keys.iter()
+                .find_map(|key| aircraft[*key].as_str().map(str::to_string))
+                .unwrap_or_default()
+        };
+        let year = |aircraft: &Value| match &aircraft["year"] {
+            Value::Number(year) => year.as_u64().and_then(|year| u16::try_from(year).ok()),
+            Value::String(year) => year.trim().parse().ok(),
+            _ => None,
+        };
+        let entries: Vec<(String, &Value)> = match value {
+            Value::Object(map) => map.iter().map(|(hex, aircraft)| (hex.clone(), aircraft)).collect(),
+            Value::Array(list) => list.iter().map(|aircraft| (text(aircraft, &["icao", "icao24", "hex"]), aircraft)).collect(),
+            _ => bail!("Expected a JSON object or array"),
+        };
+        for (hex, aircraft) in entries {
+            let Some(address) = parse_address(&hex) else {
+                continue;
+            };
+            self.insert(
+                address,
+                [
+                    text(aircraft, &["r", "registration", "reg"]).as_str(),
+                    text(aircraft, &["t", "typecode", "type_code", "type"]).as_str(),
+                    text(aircraft, &["desc", "description", "model"]).as_str(),
+                    text(aircraft, &["ownOp", "operator", "owner"]).as_str(),
+                ],
+                year(aircraft),
+            );
+        }
+        Ok(())
+    }
+
+    /// Add an aircraft with its registration, type code, description and
+    /// operator
+    fn insert(&mut self, address: u32, fields: [&str; 4], year: Option<u16>) {
+        let [registration, type_code, description, operator] = fields.map(|field| self.push(field));
+        let entry = Entry {
+            registration,
+            type_code,
+            description,
+            operator,
+            year,
+        };
+        self.index.insert(address, entry);
+    }
+
+    /// Append a string to the text buffer
+    fn push(&mut self, value: &str) -> Span {
+        let value = value.trim();
+        let (Ok(start), Ok(len)) = (u32::try_from(self.text.len()), u16::try_from(value.len())) else {
+            return Span::default();
+        };
+        self.text.push_str(value);
+        Span { start, len }
+    }
+
+    fn get(&self, span: Span) -> Option<String> {
+        let start = span.start as usize;
+        Some(&self.text[start..start + usize::from(span.len)])
+            .filter(|value| !value.is_empty())
+            .map(str::to_string)
+    }
+
+    /// Number of aircraft in the database
+    pub fn len(&self) -> usize {
+        self.index.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.index.is_empty()
+    }
+
+    /// The details of an aircraft, `None` if it is not in the database
+    pub fn lookup(&self, address: &AircraftAddress) -> Option<AircraftInfo> {
+        if address.address_type != AddressType::Icao {
+            return None;
+        }
+        let entry = self.index.get(&address.number())?;
+        Some(AircraftInfo {
+            registration: self.get(entry.registration),
+            type_code: self.get(entry.type_code),
+            description: self.get(entry.description),
+            operator: self.get(entry.operator),
+            year: entry.year,
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn address(icao: [u8; 3]) -> AircraftAddress {
+        AircraftAddress::icao(adsb_deku::ICAO(icao))
+    }
+
+    #[test]
+    fn test_tar1090_csv() {
+        let database = AircraftDatabase::parse(
+            "4840d6;PH-BXA;B738;00;BOEING 737-800;2000;KLM\n3c6444;D-AIBD;A319;00;AIRBUS A-319;;Lufthansa\nbad;X;;;;;\n",
+        )
+        .unwrap();
+        assert_eq!(database.len(), 2);
+        let info = database.lookup(&address([0x48, 0x40, 0xd6])).unwrap();
+        assert_eq!(info.registration.as_deref(), Some("PH-BXA"));
+        assert_eq!(info.type_code.as_deref(), Some("B738"));
+        assert_eq!(info.description.as_deref(), Some("BOEING 737-800"));
+        assert_eq!(info.operator.as_deref(), Some("KLM"));
+        assert_eq!(info.year, Some(2000));
+        assert_eq!(database.lookup(&address([0x3c, 0x64, 0x44])).unwrap().year, None);
+
+        assert_eq!(database.lookup(&address([0xa0, 0x00, 0x01])), None);
+        assert_eq!(database.lookup(&AircraftAddress::non_icao(adsb_deku::ICAO([0x48, 0x40, 0xd6]))), None);
+    }
+
+    #[test]
+    fn test_csv_with_header() {
+        let database = AircraftDatabase::parse(
+            "icao24,registration,model,typecode,operator\nA835AF,N628TS,\"Gulfstream G650, ER\",GLF6,\"Elon \"\"Musk\"\"\"\n",
+        )
+        .unwrap();
+        let info = database.lookup(&address([0xa8, 0x35, 0xaf])).unwrap();
+        assert_eq!(info.description.as_deref(), Some("Gulfstream G650, ER"));
+        assert_eq!(info.type_code.as_deref(), Some("GLF6"));
+        assert_eq!(info.operator.as_deref(), Some("Elon \"Musk\""));
+        assert!(AircraftDatabase::parse("registration,model\nN1,C172\n").is_err());
+    }
+
+    #[test]
+    fn test_json() {
+        let database =
+            AircraftDatabase::parse(r#"{"4840d6":{"r":"PH-BXA","t":"B738","desc":"BOEING 737-800","year":"2000"}}"#).unwrap();
+        let info = database.lookup(&address([0x48, 0x40, 0xd6])).unwrap();
+        assert_eq!(info.registration.as_deref(), Some("PH-BXA"));
+        assert_eq!(info.operator, None);
+        assert_eq!(info.year, Some(2000));
+
+        let database = AircraftDatabase::parse(r#"[{"icao":"3C6444","registration":"D-AIBD","year":1996}]"#).unwrap();
+        assert_eq!(database.lookup(&address([0x3c, 0x64, 0x44])).unwrap().year, Some(1996));
+    }
+}
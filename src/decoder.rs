@@ -1,5 +1,8 @@
+use crate::AdsbIcao;
 use crate::DemodPacket;
 use crate::metrics;
+use crate::mode_s;
+use crate::quarantine::{Quarantine, QuarantineConfig};
 use adsb_deku::deku::DekuContainerRead;
 use anyhow::bail;
 use futuresdr::macros::async_trait;
@@ -18,8 +21,12 @@ use futuresdr::tracing::debug;
 use futuresdr::tracing::info;
 use futuresdr::tracing::warn;
 use serde::Serialize;
+use std::collections::HashMap;
 use std::sync::atomic::Ordering;
-use std::time::SystemTime;
+use std::time::{Duration, SystemTime};
+
+/// How long an address stays in the known set after it was last announced
+const KNOWN_ADDRESS_LIFETIME: Duration = Duration::from_secs(60);
 
 fn bin_to_u64(s: &[u8]) -> u64 {
     s.iter().fold(0, |acc, &b| (acc << 1) + b as u64)
@@ -30,87 +37,247 @@ pub struct DecoderMetaData {
     pub preamble_index: u64,
     pub preamble_correlation: f32,
     pub crc_passed: bool,
+    /// The same frame was already received within the dedup window
+    pub duplicate: bool,
     pub timestamp: SystemTime,
 }
 
+impl DecoderMetaData {
+    /// Metadata for an update that does not come straight from a received
+    /// frame, e.g. one released by the rate limiter
+    pub fn synthetic(timestamp: SystemTime) -> Self {
+        Self {
+            preamble_index: 0,
+            preamble_correlation: 0.0,
+            crc_passed: true,
+            duplicate: false,
+            timestamp,
+        }
+    }
+}
+
 #[derive(Debug, Clone)]
 pub struct AdsbPacket {
     pub message: adsb_deku::Frame,
     pub decoder_metadata: DecoderMetaData,
     pub raw_bytes: Vec<u8>,
+    /// The aircraft address, either announced (DF11/17/18) or recovered from
+    /// the address/parity field. `None` if the frame failed validation.
+    pub address: Option<AdsbIcao>,
+}
+
+/// Configuration of the decoder block
+#[derive(Debug, Clone, Default)]
+pub struct DecoderConfig {
+    /// Forward frames that failed the CRC to the tracker
+    pub forward_failed_crc: bool,
+    /// Quarantine frames that pass the CRC but cannot be parsed
+    pub quarantine: Option<QuarantineConfig>,
+    /// Identical frames received within this window are flagged as duplicates
+    pub dedup_window: Option<Duration>,
 }
 
 pub struct Decoder {
     forward_failed_crc: bool,
+    /// Frames that passed the CRC but could not be parsed
+    quarantine: Option<Quarantine>,
     n_crc_ok: u64,
     n_crc_fail: u64,
+    /// Addresses recently announced in DF11/DF17/DF18 frames, used to validate
+    /// address/parity frames
+    known_addresses: HashMap<u32, SystemTime>,
+    /// When the known address set was last pruned
+    last_known_prune: SystemTime,
+    /// Identical frames within this window are duplicates
+    dedup_window: Option<Duration>,
+    /// Frames received within the dedup window and when they were first seen
+    recent_frames: HashMap<Vec<u8>, SystemTime>,
+    /// When the recent frames were last pruned
+    last_dedup_prune: SystemTime,
 }
 
 impl Decoder {
     #[allow(clippy::new_ret_no_self)]
     pub fn new(forward_failed_crc: bool) -> TypedBlock<Self> {
+        Self::with_config(DecoderConfig {
+            forward_failed_crc,
+            ..Default::default()
+        })
+    }
+
+    /// Create a decoder from a configuration
+    pub fn with_config(config: DecoderConfig) -> TypedBlock<Self> {
+        let quarantine = config.quarantine.and_then(|quarantine_config| {
+            let path = quarantine_config.path.clone();
+            match Quarantine::new(quarantine_config) {
+                Ok(quarantine) => Some(quarantine),
+                Err(e) => {
+                    warn!("Could not open quarantine file {}: {}", path.display(), e);
+                    None
+                }
+            }
+        });
         TypedBlock::new(
             BlockMetaBuilder::new("Decoder").build(),
             StreamIoBuilder::new().build(),
             MessageIoBuilder::new()
                 .add_input("in", Self::packet_received)
+                .add_input("ctrl_port", Self::handle_ctrl_port)
                 .add_output("out")
                 .build(),
             Self {
-                forward_failed_crc,
+                forward_failed_crc: config.forward_failed_crc,
+                quarantine,
                 n_crc_ok: 0,
                 n_crc_fail: 0,
+                known_addresses: HashMap::new(),
+                last_known_prune: SystemTime::now(),
+                dedup_window: config.dedup_window,
+                recent_frames: HashMap::new(),
+                last_dedup_prune: SystemTime::now(),
             },
         )
     }
 
-    /// Checks if the CRC is valid
-    fn check_crc(&self, bits: &[u8]) -> bool {
-        let mut bits = bits.to_vec();
-        const GENERATOR_POLY: [u8; 25] = [
-            1, 1, 1, 1, 1, 1, 1, 1, 1, 1, 1, 1, 1, 0, 1, 0, 0, 0, 0, 0, 0, 1, 0, 0, 1,
-        ];
-        for i in 0..bits.len() - (GENERATOR_POLY.len() - 1) {
-            if bits[i] == 1 {
-                for j in 0..GENERATOR_POLY.len() {
-                    bits[i + j] ^= GENERATOR_POLY[j];
+    /// Converts demodulated bits into frame bytes, trimmed to the length of
+    /// the frame's downlink format
+    fn frame_bytes(bits: &[u8]) -> Vec<u8> {
+        let bytes: Vec<u8> = (0..bits.len())
+            .step_by(8)
+            .map(|i| bin_to_u64(&bits[i..i + 8]) as u8)
+            .collect();
+        let n_bytes = mode_s::frame_bits(mode_s::downlink_format(&bytes)) / 8;
+        bytes[..n_bytes.min(bytes.len())].to_vec()
+    }
+
+    /// Validates the parity of a frame and returns the aircraft address
+    ///
+    /// DF17/DF18 need a zero CRC remainder and DF11 may only carry an II/SI
+    /// code in it. Address/parity formats are accepted when the remainder
+    /// matches an address recently announced by DF17 or by DF11 with a zero
+    /// remainder. A nonzero II/SI code is as likely to be noise, so it does
+    /// not announce the address.
+    fn validate_frame(&mut self, bytes: &[u8], now: SystemTime) -> Option<AdsbIcao> {
+        let df = mode_s::downlink_format(bytes);
+        let remainder = mode_s::crc_remainder(bytes);
+        match df {
+            17 | 18 if remainder == 0 => {
+                let address = mode_s::announced_address(bytes)?;
+                // DF18 frames may carry non-ICAO addresses, only trust ADS-B ones
+                if df == 17 || bytes[0] & 0x07 == 0 {
+                    self.known_addresses.insert(mode_s::address_to_u32(&address), now);
+                }
+                Some(address)
+            }
+            11 if mode_s::is_valid_all_call_remainder(remainder) => {
+                let address = mode_s::announced_address(bytes)?;
+                if remainder == 0 {
+                    self.known_addresses.insert(mode_s::address_to_u32(&address), now);
+                }
+                Some(address)
+            }
+            df if mode_s::has_address_parity(df) => {
+                let seen = self.known_addresses.get(&remainder)?;
+                if *seen + KNOWN_ADDRESS_LIFETIME >= now {
+                    Some(mode_s::address_from_u32(remainder))
+                } else {
+                    None
                 }
             }
+            _ => None,
         }
-        bits[(bits.len() - (GENERATOR_POLY.len() - 1))..bits.len()]
-            .iter()
-            .sum::<u8>()
-            == 0
     }
 
-    /// Decodes demodulated packet bits
+    /// Removes addresses that have not been announced for a while
+    fn prune_known_addresses(&mut self, now: SystemTime) {
+        if self.last_known_prune + KNOWN_ADDRESS_LIFETIME < now {
+            self.known_addresses
+                .retain(|_, seen| *seen + KNOWN_ADDRESS_LIFETIME >= now);
+            self.last_known_prune = now;
+        }
+    }
+
+    /// Checks whether an identical frame was received within the dedup window
+    fn is_duplicate(&mut self, bytes: &[u8], now: SystemTime) -> bool {
+        let Some(window) = self.dedup_window else {
+            return false;
+        };
+        if self.last_dedup_prune + window < now {
+            self.recent_frames.retain(|_, seen| *seen + window >= now);
+            self.last_dedup_prune = now;
+        }
+        match self.recent_frames.get(bytes) {
+            Some(seen) if *seen + window >= now => true,
+            _ => {
+                self.recent_frames.insert(bytes.to_vec(), now);
+                false
+            }
+        }
+    }
+
+    /// Decodes frame bytes
     fn decode_packet(
         &self,
         packet: &DemodPacket,
-        crc_passed: bool,
+        bytes: &[u8],
+        address: Option<AdsbIcao>,
+        duplicate: bool,
         timestamp: SystemTime,
     ) -> Result<AdsbPacket> {
         let decoder_metadata = DecoderMetaData {
             preamble_index: packet.preamble_index,
             preamble_correlation: packet.preamble_correlation,
-            crc_passed,
+            crc_passed: address.is_some(),
+            duplicate,
             timestamp,
         };
-        // Decode downlink format
-        let bytes: Vec<u8> = (0..packet.bits.len())
-            .step_by(8)
-            .map(|i| bin_to_u64(&packet.bits[i..i + 8]) as u8)
-            .collect();
-        match adsb_deku::Frame::from_bytes((&bytes, 0)) {
+        match adsb_deku::Frame::from_bytes((bytes, 0)) {
             Ok((_, message)) => {
                 let packet = AdsbPacket {
                     message,
                     decoder_metadata,
-                    raw_bytes: bytes,
+                    raw_bytes: bytes.to_vec(),
+                    address,
                 };
                 Ok(packet)
             }
-            Err(_) => bail!("adsb_deku could not parse packet"),
+            Err(e) => bail!("adsb_deku could not parse packet: {}", e),
+        }
+    }
+
+    #[message_handler]
+    async fn handle_ctrl_port(
+        &mut self,
+        io: &mut WorkIo,
+        _mio: &mut MessageIo<Self>,
+        _meta: &mut BlockMeta,
+        p: Pmt,
+    ) -> Result<Pmt> {
+        match p {
+            Pmt::String(cmd) => match cmd.as_str() {
+                "quarantine" => {
+                    // Return the most recent quarantined frames
+                    match &self.quarantine {
+                        Some(quarantine) => {
+                            let json = serde_json::to_string(quarantine.recent()).unwrap();
+                            Ok(Pmt::String(json))
+                        }
+                        None => Ok(Pmt::String("{\"quarantine\": \"disabled\"}".to_string())),
+                    }
+                }
+                _ => {
+                    warn!("Unknown control port command: {}", cmd);
+                    Ok(Pmt::String(format!("{{\"error\": \"Unknown command: {}\"}}", cmd)))
+                }
+            },
+            Pmt::Finished => {
+                io.finished = true;
+                Ok(Pmt::Ok)
+            }
+            x => {
+                warn!("Received unexpected PMT type: {:?}", x);
+                Ok(Pmt::Null)
+            }
         }
     }
 
@@ -126,7 +293,11 @@ impl Decoder {
             Pmt::Any(a) => {
                 if let Some(pkt) = a.downcast_ref::<DemodPacket>() {
                     // Validate the CRC before we start decoding
-                    let crc_passed = self.check_crc(&pkt.bits);
+                    let now = SystemTime::now();
+                    let bytes = Self::frame_bytes(&pkt.bits);
+                    let address = self.validate_frame(&bytes, now);
+                    self.prune_known_addresses(now);
+                    let crc_passed = address.is_some();
                     if crc_passed {
                         self.n_crc_ok += 1;
                         metrics().packets_crc_passed.fetch_add(1, Ordering::Relaxed);
@@ -144,16 +315,32 @@ impl Decoder {
                     }
 
                     if crc_passed || self.forward_failed_crc {
-                        match self.decode_packet(pkt, crc_passed, SystemTime::now()) {
+                        let duplicate = crc_passed && self.is_duplicate(&bytes, now);
+                        match self.decode_packet(pkt, &bytes, address, duplicate, now) {
                             Ok(decoded_packet) => {
-                                metrics().packets_decoded.fetch_add(1, Ordering::Relaxed);
+                                // Duplicates are still forwarded, flagged, for the
+                                // raw outputs that keep them
+                                if duplicate {
+                                    metrics().packets_duplicate.fetch_add(1, Ordering::Relaxed);
+                                } else {
+                                    metrics().packets_decoded.fetch_add(1, Ordering::Relaxed);
+                                }
                                 mio.output_mut(0)
                                     .post(Pmt::Any(Box::new(decoded_packet)))
                                     .await
                             }
-                            Err(_) => {
+                            Err(e) => {
                                 metrics().packets_decode_failed.fetch_add(1, Ordering::Relaxed);
-                                info!("Could not decode packet despite valid CRC")
+                                info!("Could not decode packet despite valid CRC: {}", e);
+                                let quarantined = match self.quarantine.as_mut() {
+                                    Some(quarantine) if crc_passed => {
+                                        quarantine.record(&bytes, &e.to_string())
+                                    }
+                                    _ => Ok(()),
+                                };
+                                if let Err(e) = quarantined {
+                                    warn!("Could not write to quarantine file: {}", e);
+                                }
                             }
                         }
                     }
@@ -172,3 +359,65 @@ impl Decoder {
 
 #[async_trait]
 impl Kernel for Decoder {}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn decoder(dedup_window: Option<Duration>) -> Decoder {
+        Decoder::with_config(DecoderConfig {
+            dedup_window,
+            ..Default::default()
+        })
+        .kernel
+    }
+
+    fn bytes(hex: &str) -> Vec<u8> {
+        (0..hex.len())
+            .step_by(2)
+            .map(|i| u8::from_str_radix(&hex[i..i + 2], 16).unwrap())
+            .collect()
+    }
+
+    #[test]
+    fn test_all_call_announces_address() {
+        let mut decoder = decoder(None);
+        let now = SystemTime::now();
+        let address = Some(adsb_deku::ICAO([0x48, 0x40, 0xd6]));
+        // DF4 with the address in its address/parity field
+        let surveillance = bytes("20000bb8ac0bcd");
+        assert_eq!(decoder.validate_frame(&surveillance, now), None);
+
+        // A DF11 with II code 3 is accepted, but does not announce the address
+        assert_eq!(decoder.validate_frame(&bytes("5d4840d6f8740c"), now), address);
+        assert_eq!(decoder.validate_frame(&surveillance, now), None);
+
+        assert_eq!(decoder.validate_frame(&bytes("5d4840d6f8740f"), now), address);
+        assert_eq!(decoder.validate_frame(&surveillance, now), address);
+    }
+
+    #[test]
+    fn test_duplicates_within_window() {
+        let mut decoder = decoder(Some(Duration::from_millis(5)));
+        let frame = [0x8d, 0x48, 0x40, 0xd6, 0x58, 0x99];
+        let other = [0x8d, 0x48, 0x40, 0xd6, 0x58, 0x9a];
+        let now = SystemTime::now();
+
+        assert!(!decoder.is_duplicate(&frame, now));
+        assert!(decoder.is_duplicate(&frame, now + Duration::from_millis(3)));
+        assert!(!decoder.is_duplicate(&other, now + Duration::from_millis(3)));
+
+        // The window starts at the first reception, not the last duplicate
+        assert!(!decoder.is_duplicate(&frame, now + Duration::from_millis(6)));
+        assert!(decoder.is_duplicate(&frame, now + Duration::from_millis(7)));
+    }
+
+    #[test]
+    fn test_dedup_disabled() {
+        let mut decoder = decoder(None);
+        let frame = [0x8d, 0x48, 0x40, 0xd6];
+        let now = SystemTime::now();
+        assert!(!decoder.is_duplicate(&frame, now));
+        assert!(!decoder.is_duplicate(&frame, now));
+    }
+}
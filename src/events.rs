@@ -0,0 +1,159 @@
+//! Tracker events for notable occurrences
+//!
+//! Events are raised by the tracker when something operationally interesting
+//! happens to an aircraft, as opposed to the regular state updates. All events
+//! are passed to the state output modules, and alerts are also kept in a short
+//! log that can be queried through the control port.
+//!
+//! ## JSON Format
+//! Events are serialized with the event kind flattened into the event:
+//! ```json
+//! {"address":"4840d6","time":{...},"type":"resolution_advisory","ara":12416,...}
+//! ```
+
+use crate::{
+    AircraftAddress, AircraftNavigation, AircraftState, AlertCondition, FlightEnd, FlightRoute, ResolutionAdvisory,
+};
+use serde::Serialize;
+use serde_with::DisplayFromStr;
+use serde_with::serde_as;
+use std::time::SystemTime;
+
+/// The kind of a tracker event and its details
+#[derive(Serialize, Clone, Debug)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum TrackerEventKind {
+    /// An ACAS resolution advisory was reported, or its content changed
+    ResolutionAdvisory(ResolutionAdvisory),
+    /// A special condition (emergency squawk or status, SPI) started
+    AlertStarted {
+        condition: AlertCondition,
+        start: SystemTime,
+    },
+    /// A special condition ended
+    AlertEnded {
+        condition: AlertCondition,
+        start: SystemTime,
+        end: SystemTime,
+    },
+    /// The autopilot intent (selected altitude, heading or modes) changed
+    NavigationChanged(AircraftNavigation),
+    /// A flight ended and was moved to the finished-flight log
+    FlightFinished {
+        flight: u64,
+        callsign: Option<String>,
+        end: FlightEnd,
+    },
+    /// The airline or route of a flight was found for its callsign
+    RouteChanged {
+        flight: u64,
+        route: FlightRoute,
+    },
+    /// The aircraft entered a geofence
+    GeofenceEnter {
+        fence: String,
+        state: Box<AircraftState>,
+    },
+    /// The aircraft left a geofence after `duration` seconds inside
+    GeofenceExit {
+        fence: String,
+        duration: f64,
+        state: Box<AircraftState>,
+    },
+    /// The aircraft stayed inside a geofence for longer than its dwell time
+    GeofenceDwell {
+        fence: String,
+        duration: f64,
+        state: Box<AircraftState>,
+    },
+    /// An aircraft on the watchlist appeared
+    WatchlistAppeared {
+        entry: String,
+        state: Box<AircraftState>,
+    },
+    /// The callsign, squawk, air/ground state or flight phase of an aircraft
+    /// on the watchlist changed
+    WatchlistChanged {
+        entry: String,
+        state: Box<AircraftState>,
+    },
+    /// An aircraft on the watchlist was lost or no longer matches
+    WatchlistDisappeared {
+        entry: String,
+    },
+}
+
+impl TrackerEventKind {
+    /// Whether the event is an alert, kept in the control port alert log
+    pub fn is_alert(&self) -> bool {
+        match self {
+            Self::ResolutionAdvisory(_)
+            | Self::AlertStarted { .. }
+            | Self::AlertEnded { .. }
+            | Self::GeofenceEnter { .. }
+            | Self::GeofenceExit { .. }
+            | Self::GeofenceDwell { .. }
+            | Self::WatchlistAppeared { .. }
+            | Self::WatchlistChanged { .. }
+            | Self::WatchlistDisappeared { .. } => true,
+            Self::NavigationChanged(_) | Self::FlightFinished { .. } | Self::RouteChanged { .. } => false,
+        }
+    }
+}
+
+/// An event raised by the tracker for an aircraft
+#[serde_as]
+#[derive(Serialize, Clone, Debug)]
+pub struct TrackerEvent {
+    #[serde_as(as = "DisplayFromStr")]
+    pub address: AircraftAddress,
+    pub time: SystemTime,
+    #[serde(flatten)]
+    pub kind: TrackerEventKind,
+}
+
+impl TrackerEvent {
+    /// Create an event for an aircraft at the current time
+    pub fn new(address: AircraftAddress, kind: TrackerEventKind) -> Self {
+        Self {
+            address,
+            time: SystemTime::now(),
+            kind,
+        }
+    }
+
+    /// Serialize the event as a single line of JSON
+    pub fn to_json(&self) -> String {
+        serde_json::to_string(self).unwrap_or_default()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::RaThreat;
+
+    #[test]
+    fn test_event_json() {
+        let advisory = ResolutionAdvisory {
+            ara: 0x3080,
+            rac: 0,
+            terminated: false,
+            multiple_threats: false,
+            advisories: vec!["corrective".to_string()],
+            complements: Vec::new(),
+            threat: Some(RaThreat::Address {
+                icao: adsb_deku::ICAO([0x48, 0x40, 0xD6]),
+            }),
+        };
+        let address = AircraftAddress::icao(adsb_deku::ICAO([0x40, 0x6B, 0x90]));
+        let event = TrackerEvent::new(address, TrackerEventKind::ResolutionAdvisory(advisory));
+
+        let json: serde_json::Value = serde_json::from_str(&event.to_json()).unwrap();
+        assert_eq!(json["address"], "406b90");
+        assert_eq!(json["type"], "resolution_advisory");
+        assert_eq!(json["ara"], 0x3080);
+        assert_eq!(json["threat"]["type"], "address");
+        assert_eq!(json["threat"]["icao"], "4840d6");
+    }
+}
@@ -0,0 +1,218 @@
+//! Flight segmentation and flight phases
+//!
+//! The register is keyed by address, so an airframe flying several legs
+//! would show as one long track. The tracker splits the track into flights
+//! when the callsign changes, when the aircraft is not heard for a long time,
+//! or when it takes off again after it landed. The positions of a finished
+//! flight are moved to a finished-flight record.
+//!
+//! Every position is classified into a flight phase from the air/ground
+//! state, the altitude, the vertical rate and the ground speed. Altitudes are
+//! barometric above sea level, as the elevation of the airfield is unknown.
+
+use crate::{AircraftPositionRecord, FlightRoute};
+use serde::{Deserialize, Serialize};
+use std::time::{Duration, SystemTime};
+
+/// Gap after which a flight ends if the tracker does not configure one
+pub const DEFAULT_FLIGHT_GAP: Duration = Duration::from_secs(30 * 60);
+
+/// Ground speed in knots above which an aircraft on the ground is on its
+/// takeoff or landing run
+const RUNWAY_SPEED_KT: f64 = 50.0;
+
+/// Vertical rate in ft/min below which an aircraft is level
+const LEVEL_RATE_FPM: i16 = 300;
+
+/// Altitude in feet below which a climb after takeoff is part of the takeoff
+const INITIAL_CLIMB_ALTITUDE_FT: i32 = 2000;
+
+/// Altitude in feet below which a descent is an approach
+const APPROACH_ALTITUDE_FT: i32 = 3000;
+
+/// Phase of a flight
+#[derive(Serialize, Deserialize, Clone, Copy, Debug, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum FlightPhase {
+    Taxi,
+    Takeoff,
+    Climb,
+    Cruise,
+    Descent,
+    Approach,
+    Landing,
+}
+
+impl FlightPhase {
+    /// Whether the phase is flown in the air
+    fn is_airborne(self) -> bool {
+        matches!(self, Self::Climb | Self::Cruise | Self::Descent | Self::Approach)
+    }
+
+    /// Classify a position from the state of the aircraft and the phase of
+    /// its previous position
+    pub fn classify(
+        on_ground: bool,
+        altitude: Option<i32>,
+        vertical_rate: Option<i16>,
+        ground_speed: Option<f64>,
+        previous: Option<FlightPhase>,
+    ) -> Self {
+        if on_ground {
+            let fast = ground_speed.is_some_and(|speed| speed > RUNWAY_SPEED_KT);
+            return match previous {
+                Some(phase) if fast && (phase.is_airborne() || phase == Self::Landing) => Self::Landing,
+                _ if fast => Self::Takeoff,
+                _ => Self::Taxi,
+            };
+        }
+
+        let low = |limit: i32| altitude.is_none_or(|altitude| altitude < limit);
+        let after_takeoff = matches!(previous, None | Some(Self::Taxi | Self::Takeoff));
+        match vertical_rate.unwrap_or(0) {
+            rate if rate > LEVEL_RATE_FPM && after_takeoff && low(INITIAL_CLIMB_ALTITUDE_FT) => Self::Takeoff,
+            rate if rate > LEVEL_RATE_FPM => Self::Climb,
+            rate if rate < -LEVEL_RATE_FPM && low(APPROACH_ALTITUDE_FT) => Self::Approach,
+            rate if rate < -LEVEL_RATE_FPM => Self::Descent,
+            // Level segments low down continue the approach or the takeoff
+            _ if previous == Some(Self::Approach) && low(APPROACH_ALTITUDE_FT) => Self::Approach,
+            _ if previous == Some(Self::Takeoff) && low(INITIAL_CLIMB_ALTITUDE_FT) => Self::Takeoff,
+            _ => Self::Cruise,
+        }
+    }
+}
+
+/// Why a flight ended
+#[derive(Serialize, Clone, Copy, Debug, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum FlightEnd {
+    /// The aircraft reported a different callsign
+    CallsignChange,
+    /// The aircraft was not heard for longer than the flight gap
+    Gap,
+    /// The aircraft took off again after it landed
+    Takeoff,
+    /// The aircraft was pruned or evicted from the register
+    Lost,
+}
+
+/// The current flight of an aircraft
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq)]
+pub struct Flight {
+    /// Identifier, unique while the tracker runs
+    pub id: u64,
+    pub callsign: Option<String>,
+    /// Airline and route of the callsign
+    pub route: Option<FlightRoute>,
+    pub start: SystemTime,
+    /// Time of the last position or identification of the flight
+    pub last_seen: SystemTime,
+    /// Phase of the last position
+    pub phase: Option<FlightPhase>,
+    /// Whether a takeoff was seen
+    pub took_off: bool,
+    /// Whether the aircraft was on the ground after it was airborne
+    pub landed: bool,
+    /// Highest altitude in feet
+    pub max_altitude: Option<i32>,
+    /// Whether the aircraft was airborne during the flight
+    #[serde(skip)]
+    airborne: bool,
+}
+
+impl Flight {
+    pub fn new(id: u64, callsign: Option<String>, now: SystemTime) -> Self {
+        Self {
+            id,
+            callsign,
+            route: None,
+            start: now,
+            last_seen: now,
+            phase: None,
+            took_off: false,
+            landed: false,
+            max_altitude: None,
+            airborne: false,
+        }
+    }
+
+    /// Why an update at `now` ends this flight, if it does
+    pub fn end(&self, now: SystemTime, gap: Duration, callsign: Option<&str>, on_ground: Option<bool>) -> Option<FlightEnd> {
+        if now.duration_since(self.last_seen).unwrap_or_default() > gap {
+            Some(FlightEnd::Gap)
+        } else if callsign.zip(self.callsign.as_deref()).is_some_and(|(new, old)| new != old) {
+            Some(FlightEnd::CallsignChange)
+        } else if on_ground == Some(false) && self.landed {
+            Some(FlightEnd::Takeoff)
+        } else {
+            None
+        }
+    }
+
+    /// Record a classified position of the flight
+    pub fn record(&mut self, phase: FlightPhase, on_ground: bool, altitude: Option<i32>, now: SystemTime) {
+        self.took_off |= phase == FlightPhase::Takeoff;
+        self.landed |= on_ground && self.airborne;
+        self.airborne |= !on_ground;
+        self.max_altitude = self.max_altitude.max(altitude);
+        self.phase = Some(phase);
+        self.last_seen = now;
+    }
+}
+
+/// A flight that ended, with its positions
+#[derive(Serialize, Clone, Debug)]
+pub struct FinishedFlight {
+    pub address: String,
+    #[serde(flatten)]
+    pub flight: Flight,
+    pub end: FlightEnd,
+    pub positions: Vec<AircraftPositionRecord>,
+}
+
+impl FinishedFlight {
+    /// Approximate memory used by the flight and its trail, in bytes
+    pub fn memory_size(&self) -> usize {
+        size_of::<Self>()
+            + self.address.capacity()
+            + self.flight.callsign.as_ref().map_or(0, String::capacity)
+            + self.positions.capacity() * size_of::<AircraftPositionRecord>()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_classify() {
+        use FlightPhase::*;
+        assert_eq!(FlightPhase::classify(true, None, None, Some(15.0), None), Taxi);
+        assert_eq!(FlightPhase::classify(true, None, None, Some(120.0), Some(Taxi)), Takeoff);
+        assert_eq!(FlightPhase::classify(false, Some(800), Some(2500), None, Some(Takeoff)), Takeoff);
+        assert_eq!(FlightPhase::classify(false, Some(8000), Some(2500), None, Some(Takeoff)), Climb);
+        assert_eq!(FlightPhase::classify(false, Some(36000), Some(0), None, Some(Climb)), Cruise);
+        assert_eq!(FlightPhase::classify(false, Some(20000), Some(-1500), None, Some(Cruise)), Descent);
+        assert_eq!(FlightPhase::classify(false, Some(2500), Some(-800), None, Some(Descent)), Approach);
+        assert_eq!(FlightPhase::classify(false, Some(2000), Some(0), None, Some(Approach)), Approach);
+        assert_eq!(FlightPhase::classify(true, None, None, Some(110.0), Some(Approach)), Landing);
+        assert_eq!(FlightPhase::classify(true, None, None, Some(20.0), Some(Landing)), Taxi);
+    }
+
+    #[test]
+    fn test_flight_end() {
+        let now = SystemTime::now();
+        let mut flight = Flight::new(1, Some("KLM1023".to_string()), now);
+        let gap = DEFAULT_FLIGHT_GAP;
+        assert_eq!(flight.end(now, gap, Some("KLM1023"), Some(false)), None);
+        assert_eq!(flight.end(now, gap, Some("KLM1024"), None), Some(FlightEnd::CallsignChange));
+        assert_eq!(flight.end(now + gap * 2, gap, None, None), Some(FlightEnd::Gap));
+
+        flight.record(FlightPhase::Approach, false, Some(1500), now);
+        flight.record(FlightPhase::Landing, true, None, now);
+        assert!(flight.landed);
+        assert_eq!(flight.max_altitude, Some(1500));
+        assert_eq!(flight.end(now, gap, None, Some(true)), None);
+        assert_eq!(flight.end(now, gap, None, Some(false)), Some(FlightEnd::Takeoff));
+    }
+}
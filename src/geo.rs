@@ -0,0 +1,89 @@
+//! Geodesic helpers on a spherical earth
+//!
+//! The accuracy of a sphere (about 0.5%) is plenty for plausibility checks and
+//! receiver range statistics.
+
+/// Mean earth radius in nautical miles
+const EARTH_RADIUS_NM: f64 = 3440.065;
+
+/// Feet in a nautical mile
+const FEET_PER_NM: f64 = 6076.115;
+
+/// Great circle distance between two positions in nautical miles
+pub fn distance_nm(lat1: f64, lon1: f64, lat2: f64, lon2: f64) -> f64 {
+    let (lat1, lat2) = (lat1.to_radians(), lat2.to_radians());
+    let dlat = lat2 - lat1;
+    let dlon = (lon2 - lon1).to_radians();
+    let a = (dlat / 2.0).sin().powi(2) + lat1.cos() * lat2.cos() * (dlon / 2.0).sin().powi(2);
+    2.0 * EARTH_RADIUS_NM * a.sqrt().atan2((1.0 - a).sqrt())
+}
+
+/// Initial true bearing from the first to the second position in degrees
+pub fn bearing(lat1: f64, lon1: f64, lat2: f64, lon2: f64) -> f64 {
+    let (lat1, lat2) = (lat1.to_radians(), lat2.to_radians());
+    let dlon = (lon2 - lon1).to_radians();
+    let y = dlon.sin() * lat2.cos();
+    let x = lat1.cos() * lat2.sin() - lat1.sin() * lat2.cos() * dlon.cos();
+    y.atan2(x).to_degrees().rem_euclid(360.0)
+}
+
+/// Position at a distance in nautical miles along a true bearing
+pub fn destination(lat: f64, lon: f64, bearing: f64, distance_nm: f64) -> (f64, f64) {
+    let (lat, lon, bearing) = (lat.to_radians(), lon.to_radians(), bearing.to_radians());
+    let d = distance_nm / EARTH_RADIUS_NM;
+    let lat2 = (lat.sin() * d.cos() + lat.cos() * d.sin() * bearing.cos()).asin();
+    let lon2 = lon + (bearing.sin() * d.sin() * lat.cos()).atan2(d.cos() - lat.sin() * lat2.sin());
+    (lat2.to_degrees(), (lon2.to_degrees() + 540.0).rem_euclid(360.0) - 180.0)
+}
+
+/// Elevation angle in degrees of a target at a distance and height above the
+/// observer, corrected for the curvature of the earth
+pub fn elevation_angle(distance_nm: f64, height_ft: f64) -> f64 {
+    let distance_ft = distance_nm * FEET_PER_NM;
+    let drop_ft = distance_ft * distance_ft / (2.0 * EARTH_RADIUS_NM * FEET_PER_NM);
+    (height_ft - drop_ft).atan2(distance_ft).to_degrees()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_distance_nm() {
+        assert_eq!(distance_nm(52.0, 4.0, 52.0, 4.0), 0.0);
+        // One degree of latitude is 60 NM
+        assert!((distance_nm(52.0, 4.0, 53.0, 4.0) - 60.04).abs() < 0.01);
+        // Amsterdam Schiphol to London Heathrow
+        assert!((distance_nm(52.3086, 4.7639, 51.4700, -0.4543) - 199.76).abs() < 0.01);
+    }
+
+    #[test]
+    fn test_bearing() {
+        assert!((bearing(52.0, 4.0, 53.0, 4.0) - 0.0).abs() < 1e-9);
+        assert!((bearing(0.0, 4.0, 0.0, 5.0) - 90.0).abs() < 1e-9);
+        assert!((bearing(52.0, 4.0, 51.0, 4.0) - 180.0).abs() < 1e-9);
+        assert!((bearing(52.3086, 4.7639, 51.4700, -0.4543) - 257.47).abs() < 0.01);
+    }
+
+    #[test]
+    fn test_destination() {
+        let (lat, lon) = destination(52.0, 4.0, 0.0, 60.04);
+        assert!((lat - 53.0).abs() < 1e-3);
+        assert!((lon - 4.0).abs() < 1e-9);
+        // Going back along the reverse bearing returns to the start
+        let (lat, lon) = destination(52.3086, 4.7639, 257.47, 199.76);
+        let back = bearing(lat, lon, 52.3086, 4.7639);
+        let (lat, lon) = destination(lat, lon, back, 199.76);
+        assert!((lat - 52.3086).abs() < 1e-3);
+        assert!((lon - 4.7639).abs() < 1e-3);
+    }
+
+    #[test]
+    fn test_elevation_angle() {
+        // 6076 ft up at 1 NM is 45 degrees, the curvature barely matters
+        assert!((elevation_angle(1.0, FEET_PER_NM) - 45.0).abs() < 0.01);
+        // An aircraft at 35000 ft sinks below the horizon at about 200 NM
+        assert!(elevation_angle(150.0, 35000.0) > 0.0);
+        assert!(elevation_angle(250.0, 35000.0) < 0.0);
+    }
+}
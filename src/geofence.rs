@@ -0,0 +1,448 @@
+//! Geofences
+//!
+//! A geofence is a polygon or a circle, optionally limited to an altitude band
+//! and a daily time window. The tracker checks the fences on every position
+//! of an aircraft and reports when it enters a fence, when it leaves it, and
+//! when it stayed inside for longer than the dwell time of the fence.
+//!
+//! ## GeoJSON Format
+//! Fences are read from GeoJSON features. Polygons are used as they are, a
+//! point with a `radius_nm` property is a circle. The other properties are
+//! optional, altitudes are in feet and the time window is in UTC:
+//! ```json
+//! {"type":"Feature","geometry":{"type":"Point","coordinates":[4.89,52.37]},
+//!  "properties":{"id":"helipad","radius_nm":1.5,"max_altitude":3000,"window":"06:00-22:00","dwell":120}}
+//! ```
+
+use crate::AircraftAddress;
+use crate::geo;
+use anyhow::{Context, Result, anyhow, bail};
+use serde_json::{Value, json};
+use std::collections::HashMap;
+use std::fs;
+use std::path::Path;
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+const MINUTES_PER_DAY: u32 = 24 * 60;
+
+/// Area of a geofence
+#[derive(Clone, Debug, PartialEq)]
+pub enum GeofenceShape {
+    /// Rings of (longitude, latitude) points in degrees, the first is the
+    /// outline and the others are holes
+    Polygon(Vec<Vec<(f64, f64)>>),
+    /// Circle around a point
+    Circle {
+        latitude: f64,
+        longitude: f64,
+        radius_nm: f64,
+    },
+}
+
+impl GeofenceShape {
+    fn contains(&self, latitude: f64, longitude: f64) -> bool {
+        match self {
+            Self::Polygon(rings) => {
+                let mut rings = rings.iter().map(|ring| ring_contains(ring, latitude, longitude));
+                rings.next().unwrap_or(false) && !rings.any(|inside| inside)
+            }
+            Self::Circle {
+                latitude: center_lat,
+                longitude: center_lon,
+                radius_nm,
+            } => geo::distance_nm(*center_lat, *center_lon, latitude, longitude) <= *radius_nm,
+        }
+    }
+}
+
+/// Whether a point is inside a ring, by counting the edges a ray to the east
+/// crosses
+fn ring_contains(ring: &[(f64, f64)], latitude: f64, longitude: f64) -> bool {
+    let mut inside = false;
+    let edges = ring.iter().zip(ring.iter().cycle().skip(1));
+    for (&(lon1, lat1), &(lon2, lat2)) in edges {
+        if (lat1 > latitude) != (lat2 > latitude)
+            && longitude < lon1 + (latitude - lat1) / (lat2 - lat1) * (lon2 - lon1)
+        {
+            inside = !inside;
+        }
+    }
+    inside
+}
+
+/// Daily time window in minutes after midnight UTC, it wraps around midnight
+/// if it ends before it starts
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct TimeWindow {
+    pub start: u32,
+    pub end: u32,
+}
+
+impl TimeWindow {
+    /// Parse a window written as `HH:MM-HH:MM`
+    pub fn parse(window: &str) -> Result<Self> {
+        let minutes = |time: &str| -> Result<u32> {
+            let (hours, minutes) = time.trim().split_once(':').ok_or_else(|| anyhow!("Invalid time: {}", time))?;
+            let (hours, minutes): (u32, u32) = (hours.parse()?, minutes.parse()?);
+            if hours > 23 || minutes > 59 {
+                bail!("Invalid time: {}", time);
+            }
+            Ok(hours * 60 + minutes)
+        };
+        let (start, end) = window.split_once('-').ok_or_else(|| anyhow!("Invalid time window: {}", window))?;
+        Ok(Self {
+            start: minutes(start)?,
+            end: minutes(end)?,
+        })
+    }
+
+    pub fn contains(&self, time: SystemTime) -> bool {
+        let seconds = time.duration_since(UNIX_EPOCH).unwrap_or_default().as_secs();
+        let minute = (seconds / 60 % u64::from(MINUTES_PER_DAY)) as u32;
+        if self.start <= self.end {
+            (self.start..self.end).contains(&minute)
+        } else {
+            minute >= self.start || minute < self.end
+        }
+    }
+}
+
+impl std::fmt::Display for TimeWindow {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(
+            f,
+            "{:02}:{:02}-{:02}:{:02}",
+            self.start / 60,
+            self.start % 60,
+            self.end / 60,
+            self.end % 60
+        )
+    }
+}
+
+/// A user-defined area to monitor
+#[derive(Clone, Debug, PartialEq)]
+pub struct Geofence {
+    /// Identifier, unique among the fences
+    pub id: String,
+    pub name: Option<String>,
+    pub shape: GeofenceShape,
+    /// Lowest altitude in feet inside the fence
+    pub min_altitude: Option<i32>,
+    /// Highest altitude in feet inside the fence
+    pub max_altitude: Option<i32>,
+    /// Time of day the fence is active, always if `None`
+    pub window: Option<TimeWindow>,
+    /// Report aircraft that stay inside for longer than this
+    pub dwell: Option<Duration>,
+}
+
+impl Geofence {
+    /// Whether a position is inside the fence at the given time
+    ///
+    /// Positions without an altitude, such as surface positions, are below
+    /// the upper limit of every altitude band.
+    pub fn contains(&self, latitude: f64, longitude: f64, altitude: Option<i32>, time: SystemTime) -> bool {
+        let above_min = self.min_altitude.is_none_or(|min| altitude.is_some_and(|altitude| altitude >= min));
+        let below_max = self.max_altitude.is_none_or(|max| altitude.is_none_or(|altitude| altitude <= max));
+        above_min
+            && below_max
+            && self.window.is_none_or(|window| window.contains(time))
+            && self.shape.contains(latitude, longitude)
+    }
+
+    /// Read the fences of a GeoJSON file
+    pub fn load(path: &Path) -> Result<Vec<Self>> {
+        let data = fs::read_to_string(path).with_context(|| format!("Failed to read {}", path.display()))?;
+        let value: Value = serde_json::from_str(&data).with_context(|| format!("Invalid JSON in {}", path.display()))?;
+        Self::parse(&value)
+    }
+
+    /// Parse the fences of a GeoJSON feature or feature collection
+    pub fn parse(value: &Value) -> Result<Vec<Self>> {
+        match value["type"].as_str() {
+            Some("FeatureCollection") => value["features"]
+                .as_array()
+                .ok_or_else(|| anyhow!("Feature collection without features"))?
+                .iter()
+                .map(Self::parse_feature)
+                .collect(),
+            Some("Feature") => Ok(vec![Self::parse_feature(value)?]),
+            _ => bail!("Expected a GeoJSON Feature or FeatureCollection"),
+        }
+    }
+
+    fn parse_feature(feature: &Value) -> Result<Self> {
+        let properties = &feature["properties"];
+        let id = properties["id"]
+            .as_str()
+            .or(feature["id"].as_str())
+            .ok_or_else(|| anyhow!("Geofence without an id"))?
+            .to_string();
+        let geometry = &feature["geometry"];
+        let point = |value: &Value| -> Result<(f64, f64)> {
+            match value.as_array().map(Vec::as_slice) {
+                Some([lon, lat, ..]) => lon.as_f64().zip(lat.as_f64()).ok_or_else(|| anyhow!("Invalid coordinates")),
+                _ => bail!("Invalid coordinates in geofence {}", id),
+            }
+        };
+        let shape = match geometry["type"].as_str() {
+            Some("Polygon") => {
+                let rings = geometry["coordinates"]
+                    .as_array()
+                    .ok_or_else(|| anyhow!("Polygon without coordinates in geofence {}", id))?
+                    .iter()
+                    .map(|ring| ring.as_array().into_iter().flatten().map(&point).collect::<Result<Vec<_>>>())
+                    .collect::<Result<Vec<_>>>()?;
+                if rings.first().is_none_or(|outline| outline.len() < 3) {
+                    bail!("Polygon with less than three points in geofence {}", id);
+                }
+                GeofenceShape::Polygon(rings)
+            }
+            Some("Point") => {
+                let (longitude, latitude) = point(&geometry["coordinates"])?;
+                let radius_nm = properties["radius_nm"]
+                    .as_f64()
+                    .filter(|radius| *radius > 0.0)
+                    .ok_or_else(|| anyhow!("Point without a positive radius_nm in geofence {}", id))?;
+                GeofenceShape::Circle {
+                    latitude,
+                    longitude,
+                    radius_nm,
+                }
+            }
+            _ => bail!("Unsupported geometry in geofence {}, expected a Polygon or Point", id),
+        };
+        let altitude = |key: &str| properties[key].as_i64().map(|altitude| altitude as i32);
+        Ok(Self {
+            name: properties["name"].as_str().map(str::to_string),
+            shape,
+            min_altitude: altitude("min_altitude"),
+            max_altitude: altitude("max_altitude"),
+            window: properties["window"].as_str().map(TimeWindow::parse).transpose()?,
+            dwell: properties["dwell"].as_u64().map(Duration::from_secs),
+            id,
+        })
+    }
+
+    /// The fence as a GeoJSON feature
+    pub fn to_geojson(&self) -> Value {
+        let (geometry, radius_nm) = match &self.shape {
+            GeofenceShape::Polygon(rings) => {
+                let coordinates: Vec<Vec<[f64; 2]>> = rings
+                    .iter()
+                    .map(|ring| ring.iter().map(|&(lon, lat)| [lon, lat]).collect())
+                    .collect();
+                (json!({"type": "Polygon", "coordinates": coordinates}), None)
+            }
+            GeofenceShape::Circle {
+                latitude,
+                longitude,
+                radius_nm,
+            } => (json!({"type": "Point", "coordinates": [longitude, latitude]}), Some(radius_nm)),
+        };
+        json!({
+            "type": "Feature",
+            "geometry": geometry,
+            "properties": {
+                "id": self.id,
+                "name": self.name,
+                "radius_nm": radius_nm,
+                "min_altitude": self.min_altitude,
+                "max_altitude": self.max_altitude,
+                "window": self.window.map(|window| window.to_string()),
+                "dwell": self.dwell.map(|dwell| dwell.as_secs()),
+            },
+        })
+    }
+}
+
+/// What happened between an aircraft and a fence
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum GeofenceTransition {
+    Enter,
+    Exit,
+    Dwell,
+}
+
+/// A transition of an aircraft at a fence
+#[derive(Clone, Debug, PartialEq)]
+pub struct GeofenceChange {
+    pub fence: String,
+    pub transition: GeofenceTransition,
+    /// Time the aircraft has been inside the fence
+    pub duration: Duration,
+}
+
+/// An aircraft inside a fence
+#[derive(Clone, Debug)]
+struct Presence {
+    entered: SystemTime,
+    /// Whether the dwell was reported
+    dwelled: bool,
+}
+
+/// The fences and the aircraft inside them
+#[derive(Clone, Debug, Default)]
+pub struct Geofences {
+    fences: Vec<Geofence>,
+    inside: HashMap<(AircraftAddress, String), Presence>,
+}
+
+impl Geofences {
+    pub fn new(fences: Vec<Geofence>) -> Self {
+        let mut geofences = Self::default();
+        for fence in fences {
+            geofences.add(fence);
+        }
+        geofences
+    }
+
+    pub fn fences(&self) -> &[Geofence] {
+        &self.fences
+    }
+
+    /// Add a fence, replacing the fence with the same id
+    pub fn add(&mut self, fence: Geofence) {
+        self.remove(&fence.id);
+        self.fences.push(fence);
+    }
+
+    /// Remove a fence, returns whether it existed
+    pub fn remove(&mut self, id: &str) -> bool {
+        let count = self.fences.len();
+        self.fences.retain(|fence| fence.id != id);
+        self.inside.retain(|(_, fence), _| fence != id);
+        self.fences.len() != count
+    }
+
+    /// Forget an aircraft that is no longer tracked, without reporting exits
+    pub fn forget(&mut self, address: &AircraftAddress) {
+        self.inside.retain(|(inside, _), _| inside != address);
+    }
+
+    /// Check a new position of an aircraft against all fences
+    pub fn update(
+        &mut self,
+        address: AircraftAddress,
+        latitude: f64,
+        longitude: f64,
+        altitude: Option<i32>,
+        now: SystemTime,
+    ) -> Vec<GeofenceChange> {
+        let mut changes = Vec::new();
+        for fence in &self.fences {
+            let key = (address, fence.id.clone());
+            let inside = fence.contains(latitude, longitude, altitude, now);
+            let change = |transition, entered: SystemTime| GeofenceChange {
+                fence: fence.id.clone(),
+                transition,
+                duration: now.duration_since(entered).unwrap_or_default(),
+            };
+            match self.inside.get_mut(&key) {
+                None if inside => {
+                    self.inside.insert(key, Presence { entered: now, dwelled: false });
+                    changes.push(change(GeofenceTransition::Enter, now));
+                }
+                Some(presence) if !inside => {
+                    changes.push(change(GeofenceTransition::Exit, presence.entered));
+                    self.inside.remove(&key);
+                }
+                Some(presence) => {
+                    let dwell = change(GeofenceTransition::Dwell, presence.entered);
+                    if !presence.dwelled && fence.dwell.is_some_and(|limit| dwell.duration >= limit) {
+                        presence.dwelled = true;
+                        changes.push(dwell);
+                    }
+                }
+                None => {}
+            }
+        }
+        changes
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn helipad() -> Value {
+        json!({
+            "type": "Feature",
+            "geometry": {"type": "Point", "coordinates": [4.89, 52.37]},
+            "properties": {"id": "helipad", "radius_nm": 1.5, "max_altitude": 3000, "dwell": 120},
+        })
+    }
+
+    #[test]
+    fn test_parse_geojson() {
+        let square = json!({
+            "type": "Feature",
+            "id": "square",
+            "geometry": {"type": "Polygon", "coordinates": [[[4.0, 52.0], [5.0, 52.0], [5.0, 53.0], [4.0, 53.0], [4.0, 52.0]]]},
+            "properties": {"min_altitude": 1000, "window": "22:00-06:00"},
+        });
+        let collection = json!({"type": "FeatureCollection", "features": [helipad(), square]});
+        let fences = Geofence::parse(&collection).unwrap();
+        assert_eq!(fences.len(), 2);
+        assert!(matches!(fences[0].shape, GeofenceShape::Circle { radius_nm, .. } if radius_nm == 1.5));
+        assert_eq!(fences[0].dwell, Some(Duration::from_secs(120)));
+        assert_eq!(fences[1].id, "square");
+        assert_eq!(fences[1].window, Some(TimeWindow { start: 1320, end: 360 }));
+
+        // Fences survive the round trip through GeoJSON
+        let again = Geofence::parse(&fences[1].to_geojson()).unwrap();
+        assert_eq!(again[0], fences[1]);
+
+        let missing_radius = json!({"type": "Feature", "geometry": {"type": "Point", "coordinates": [4.0, 52.0]}, "properties": {"id": "x"}});
+        assert!(Geofence::parse(&missing_radius).is_err());
+    }
+
+    #[test]
+    fn test_contains() {
+        let fences = Geofence::parse(&helipad()).unwrap();
+        let fence = &fences[0];
+        let now = SystemTime::now();
+        assert!(fence.contains(52.38, 4.90, Some(1500), now));
+        assert!(fence.contains(52.38, 4.90, None, now));
+        assert!(!fence.contains(52.38, 4.90, Some(5000), now));
+        assert!(!fence.contains(52.50, 4.90, Some(1500), now));
+
+        let ring = vec![(0.0, 0.0), (2.0, 0.0), (2.0, 2.0), (0.0, 2.0)];
+        let hole = vec![(0.5, 0.5), (1.5, 0.5), (1.5, 1.5), (0.5, 1.5)];
+        let shape = GeofenceShape::Polygon(vec![ring, hole]);
+        assert!(shape.contains(0.25, 0.25));
+        assert!(!shape.contains(1.0, 1.0));
+        assert!(!shape.contains(3.0, 1.0));
+
+        let night = TimeWindow::parse("22:00-06:00").unwrap();
+        let day = UNIX_EPOCH + Duration::from_secs(12 * 3600);
+        assert!(!night.contains(day));
+        assert!(night.contains(day + Duration::from_secs(12 * 3600)));
+        assert!(TimeWindow::parse("25:00-06:00").is_err());
+    }
+
+    #[test]
+    fn test_transitions() {
+        let mut geofences = Geofences::new(Geofence::parse(&helipad()).unwrap());
+        let address = AircraftAddress::icao(adsb_deku::ICAO([0x48, 0x40, 0xd6]));
+        let now = SystemTime::now();
+        let transitions = |changes: Vec<GeofenceChange>| changes.iter().map(|c| c.transition).collect::<Vec<_>>();
+
+        let changes = geofences.update(address, 52.38, 4.90, Some(1500), now);
+        assert_eq!(transitions(changes), [GeofenceTransition::Enter]);
+        assert!(geofences.update(address, 52.38, 4.90, Some(1500), now + Duration::from_secs(60)).is_empty());
+        let later = now + Duration::from_secs(150);
+        let changes = geofences.update(address, 52.38, 4.90, Some(1500), later);
+        assert_eq!(transitions(changes), [GeofenceTransition::Dwell]);
+        // The dwell is only reported once
+        assert!(geofences.update(address, 52.38, 4.90, Some(1500), later).is_empty());
+
+        let changes = geofences.update(address, 52.38, 4.90, Some(4000), later + Duration::from_secs(30));
+        assert_eq!(changes[0].transition, GeofenceTransition::Exit);
+        assert_eq!(changes[0].duration, Duration::from_secs(180));
+
+        assert!(geofences.remove("helipad"));
+        assert!(geofences.update(address, 52.38, 4.90, Some(1500), later).is_empty());
+    }
+}
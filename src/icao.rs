@@ -0,0 +1,305 @@
+//! ICAO 24-bit address allocation
+//!
+//! ICAO allocates blocks of 24-bit addresses to the states of registry
+//! (ICAO Annex 10, Volume III, Chapter 9). The block an address falls in
+//! gives the country the aircraft is registered in. Some states reserve
+//! parts of their block for military aircraft, the known ranges are flagged.
+//! The tables are built in and need no data files.
+
+use crate::{AddressType, AircraftAddress};
+
+/// A block of addresses, first and last address inclusive
+struct Block {
+    first: u32,
+    last: u32,
+    country: &'static str,
+}
+
+const fn block(first: u32, last: u32, country: &'static str) -> Block {
+    Block { first, last, country }
+}
+
+/// Allocation blocks of the states of registry, sorted by address
+const COUNTRIES: &[Block] = &[
+    block(0x004000, 0x0043ff, "Zimbabwe"),
+    block(0x006000, 0x006fff, "Mozambique"),
+    block(0x008000, 0x00ffff, "South Africa"),
+    block(0x010000, 0x017fff, "Egypt"),
+    block(0x018000, 0x01ffff, "Libya"),
+    block(0x020000, 0x027fff, "Morocco"),
+    block(0x028000, 0x02ffff, "Tunisia"),
+    block(0x030000, 0x0303ff, "Botswana"),
+    block(0x032000, 0x032fff, "Burundi"),
+    block(0x034000, 0x034fff, "Cameroon"),
+    block(0x035000, 0x0353ff, "Comoros"),
+    block(0x036000, 0x036fff, "Congo"),
+    block(0x038000, 0x038fff, "Cote d'Ivoire"),
+    block(0x03e000, 0x03efff, "Gabon"),
+    block(0x040000, 0x040fff, "Ethiopia"),
+    block(0x042000, 0x042fff, "Equatorial Guinea"),
+    block(0x044000, 0x044fff, "Ghana"),
+    block(0x046000, 0x046fff, "Guinea"),
+    block(0x048000, 0x0483ff, "Guinea-Bissau"),
+    block(0x04a000, 0x04a3ff, "Lesotho"),
+    block(0x04c000, 0x04cfff, "Kenya"),
+    block(0x050000, 0x050fff, "Liberia"),
+    block(0x054000, 0x054fff, "Madagascar"),
+    block(0x058000, 0x058fff, "Malawi"),
+    block(0x05a000, 0x05a3ff, "Maldives"),
+    block(0x05c000, 0x05cfff, "Mali"),
+    block(0x05e000, 0x05e3ff, "Mauritania"),
+    block(0x060000, 0x0603ff, "Mauritius"),
+    block(0x062000, 0x062fff, "Niger"),
+    block(0x064000, 0x064fff, "Nigeria"),
+    block(0x068000, 0x068fff, "Uganda"),
+    block(0x06a000, 0x06a3ff, "Qatar"),
+    block(0x06c000, 0x06cfff, "Central African Republic"),
+    block(0x06e000, 0x06efff, "Rwanda"),
+    block(0x070000, 0x070fff, "Senegal"),
+    block(0x074000, 0x0743ff, "Seychelles"),
+    block(0x076000, 0x0763ff, "Sierra Leone"),
+    block(0x078000, 0x078fff, "Somalia"),
+    block(0x07a000, 0x07a3ff, "Eswatini"),
+    block(0x07c000, 0x07cfff, "Sudan"),
+    block(0x080000, 0x080fff, "Tanzania"),
+    block(0x084000, 0x084fff, "Chad"),
+    block(0x088000, 0x088fff, "Togo"),
+    block(0x08a000, 0x08afff, "Zambia"),
+    block(0x08c000, 0x08cfff, "DR Congo"),
+    block(0x090000, 0x090fff, "Angola"),
+    block(0x094000, 0x0943ff, "Benin"),
+    block(0x096000, 0x0963ff, "Cape Verde"),
+    block(0x098000, 0x0983ff, "Djibouti"),
+    block(0x09a000, 0x09afff, "Gambia"),
+    block(0x09c000, 0x09cfff, "Burkina Faso"),
+    block(0x09e000, 0x09e3ff, "Sao Tome and Principe"),
+    block(0x0a0000, 0x0a7fff, "Algeria"),
+    block(0x0a8000, 0x0a8fff, "Bahamas"),
+    block(0x0aa000, 0x0aa3ff, "Barbados"),
+    block(0x0ab000, 0x0ab3ff, "Belize"),
+    block(0x0ac000, 0x0acfff, "Colombia"),
+    block(0x0ae000, 0x0aefff, "Costa Rica"),
+    block(0x0b0000, 0x0b0fff, "Cuba"),
+    block(0x0b2000, 0x0b2fff, "El Salvador"),
+    block(0x0b4000, 0x0b4fff, "Guatemala"),
+    block(0x0b6000, 0x0b6fff, "Guyana"),
+    block(0x0b8000, 0x0b8fff, "Haiti"),
+    block(0x0ba000, 0x0bafff, "Honduras"),
+    block(0x0bc000, 0x0bc3ff, "Saint Vincent and the Grenadines"),
+    block(0x0be000, 0x0befff, "Jamaica"),
+    block(0x0c0000, 0x0c0fff, "Nicaragua"),
+    block(0x0c2000, 0x0c2fff, "Panama"),
+    block(0x0c4000, 0x0c4fff, "Dominican Republic"),
+    block(0x0c6000, 0x0c6fff, "Trinidad and Tobago"),
+    block(0x0c8000, 0x0c8fff, "Suriname"),
+    block(0x0ca000, 0x0ca3ff, "Antigua and Barbuda"),
+    block(0x0cc000, 0x0cc3ff, "Grenada"),
+    block(0x0d0000, 0x0d7fff, "Mexico"),
+    block(0x0d8000, 0x0dffff, "Venezuela"),
+    block(0x100000, 0x1fffff, "Russia"),
+    block(0x201000, 0x2013ff, "Namibia"),
+    block(0x202000, 0x2023ff, "Eritrea"),
+    block(0x300000, 0x33ffff, "Italy"),
+    block(0x340000, 0x37ffff, "Spain"),
+    block(0x380000, 0x3bffff, "France"),
+    block(0x3c0000, 0x3fffff, "Germany"),
+    block(0x400000, 0x43ffff, "United Kingdom"),
+    block(0x440000, 0x447fff, "Austria"),
+    block(0x448000, 0x44ffff, "Belgium"),
+    block(0x450000, 0x457fff, "Bulgaria"),
+    block(0x458000, 0x45ffff, "Denmark"),
+    block(0x460000, 0x467fff, "Finland"),
+    block(0x468000, 0x46ffff, "Greece"),
+    block(0x470000, 0x477fff, "Hungary"),
+    block(0x478000, 0x47ffff, "Norway"),
+    block(0x480000, 0x487fff, "Netherlands"),
+    block(0x488000, 0x48ffff, "Poland"),
+    block(0x490000, 0x497fff, "Portugal"),
+    block(0x498000, 0x49ffff, "Czechia"),
+    block(0x4a0000, 0x4a7fff, "Romania"),
+    block(0x4a8000, 0x4affff, "Sweden"),
+    block(0x4b0000, 0x4b7fff, "Switzerland"),
+    block(0x4b8000, 0x4bffff, "Turkey"),
+    block(0x4c0000, 0x4c7fff, "Serbia"),
+    block(0x4c8000, 0x4c83ff, "Cyprus"),
+    block(0x4ca000, 0x4cafff, "Ireland"),
+    block(0x4cc000, 0x4ccfff, "Iceland"),
+    block(0x4d0000, 0x4d03ff, "Luxembourg"),
+    block(0x4d2000, 0x4d23ff, "Malta"),
+    block(0x4d4000, 0x4d43ff, "Monaco"),
+    block(0x500000, 0x5003ff, "San Marino"),
+    block(0x501000, 0x5013ff, "Albania"),
+    block(0x501c00, 0x501fff, "Croatia"),
+    block(0x502c00, 0x502fff, "Latvia"),
+    block(0x503c00, 0x503fff, "Lithuania"),
+    block(0x504c00, 0x504fff, "Moldova"),
+    block(0x505c00, 0x505fff, "Slovakia"),
+    block(0x506c00, 0x506fff, "Slovenia"),
+    block(0x507c00, 0x507fff, "Uzbekistan"),
+    block(0x508000, 0x50ffff, "Ukraine"),
+    block(0x510000, 0x5103ff, "Belarus"),
+    block(0x511000, 0x5113ff, "Estonia"),
+    block(0x512000, 0x5123ff, "North Macedonia"),
+    block(0x513000, 0x5133ff, "Bosnia and Herzegovina"),
+    block(0x514000, 0x5143ff, "Georgia"),
+    block(0x515000, 0x5153ff, "Tajikistan"),
+    block(0x516000, 0x5163ff, "Montenegro"),
+    block(0x600000, 0x6003ff, "Armenia"),
+    block(0x600800, 0x600bff, "Azerbaijan"),
+    block(0x601000, 0x6013ff, "Kyrgyzstan"),
+    block(0x601800, 0x601bff, "Turkmenistan"),
+    block(0x680000, 0x6803ff, "Bhutan"),
+    block(0x681000, 0x6813ff, "Micronesia"),
+    block(0x682000, 0x6823ff, "Mongolia"),
+    block(0x683000, 0x6833ff, "Kazakhstan"),
+    block(0x684000, 0x6843ff, "Palau"),
+    block(0x700000, 0x700fff, "Afghanistan"),
+    block(0x702000, 0x702fff, "Bangladesh"),
+    block(0x704000, 0x704fff, "Myanmar"),
+    block(0x706000, 0x706fff, "Kuwait"),
+    block(0x708000, 0x708fff, "Laos"),
+    block(0x70a000, 0x70afff, "Nepal"),
+    block(0x70c000, 0x70c3ff, "Oman"),
+    block(0x70e000, 0x70efff, "Cambodia"),
+    block(0x710000, 0x717fff, "Saudi Arabia"),
+    block(0x718000, 0x71ffff, "South Korea"),
+    block(0x720000, 0x727fff, "North Korea"),
+    block(0x728000, 0x72ffff, "Iraq"),
+    block(0x730000, 0x737fff, "Iran"),
+    block(0x738000, 0x73ffff, "Israel"),
+    block(0x740000, 0x747fff, "Jordan"),
+    block(0x748000, 0x74ffff, "Lebanon"),
+    block(0x750000, 0x757fff, "Malaysia"),
+    block(0x758000, 0x75ffff, "Philippines"),
+    block(0x760000, 0x767fff, "Pakistan"),
+    block(0x768000, 0x76ffff, "Singapore"),
+    block(0x770000, 0x777fff, "Sri Lanka"),
+    block(0x778000, 0x77ffff, "Syria"),
+    block(0x780000, 0x7bffff, "China"),
+    block(0x7c0000, 0x7fffff, "Australia"),
+    block(0x800000, 0x83ffff, "India"),
+    block(0x840000, 0x87ffff, "Japan"),
+    block(0x880000, 0x887fff, "Thailand"),
+    block(0x888000, 0x88ffff, "Vietnam"),
+    block(0x890000, 0x890fff, "Yemen"),
+    block(0x894000, 0x894fff, "Bahrain"),
+    block(0x895000, 0x8953ff, "Brunei"),
+    block(0x896000, 0x896fff, "United Arab Emirates"),
+    block(0x897000, 0x8973ff, "Solomon Islands"),
+    block(0x898000, 0x898fff, "Papua New Guinea"),
+    block(0x899000, 0x8993ff, "Taiwan"),
+    block(0x8a0000, 0x8a7fff, "Indonesia"),
+    block(0x900000, 0x9003ff, "Marshall Islands"),
+    block(0x901000, 0x9013ff, "Cook Islands"),
+    block(0x902000, 0x9023ff, "Samoa"),
+    block(0xa00000, 0xafffff, "United States"),
+    block(0xc00000, 0xc3ffff, "Canada"),
+    block(0xc80000, 0xc87fff, "New Zealand"),
+    block(0xc88000, 0xc88fff, "Fiji"),
+    block(0xc8a000, 0xc8a3ff, "Nauru"),
+    block(0xc8c000, 0xc8c3ff, "Saint Lucia"),
+    block(0xc8d000, 0xc8d3ff, "Tonga"),
+    block(0xc8e000, 0xc8e3ff, "Kiribati"),
+    block(0xc90000, 0xc903ff, "Vanuatu"),
+    block(0xe00000, 0xe3ffff, "Argentina"),
+    block(0xe40000, 0xe7ffff, "Brazil"),
+    block(0xe80000, 0xe80fff, "Chile"),
+    block(0xe84000, 0xe84fff, "Ecuador"),
+    block(0xe88000, 0xe88fff, "Paraguay"),
+    block(0xe8c000, 0xe8cfff, "Peru"),
+    block(0xe90000, 0xe90fff, "Uruguay"),
+    block(0xe94000, 0xe94fff, "Bolivia"),
+];
+
+/// Ranges known to be used by military aircraft, as flagged by readsb and
+/// tar1090
+const MILITARY: &[(u32, u32)] = &[
+    (0x010070, 0x01008f), // Egypt
+    (0x0a4000, 0x0a4fff), // Algeria
+    (0x33ff00, 0x33ffff), // Italy
+    (0x350000, 0x37ffff), // Spain
+    (0x3a8000, 0x3bffff), // France
+    (0x3e8000, 0x3ebfff), // Germany
+    (0x3f4000, 0x3fbfff), // Germany
+    (0x400000, 0x40003f), // United Kingdom
+    (0x43c000, 0x43cfff), // United Kingdom
+    (0x444000, 0x446fff), // Austria
+    (0x44f000, 0x44ffff), // Belgium
+    (0x457000, 0x457fff), // Bulgaria
+    (0x45f400, 0x45f4ff), // Denmark
+    (0x468000, 0x4683ff), // Greece
+    (0x473c00, 0x473c0f), // Hungary
+    (0x478100, 0x4781ff), // Norway
+    (0x480000, 0x480fff), // Netherlands
+    (0x48d800, 0x48d87f), // Poland
+    (0x497c00, 0x497cff), // Portugal
+    (0x498420, 0x49842f), // Czechia
+    (0x4b7000, 0x4b7fff), // Switzerland
+    (0x4b8200, 0x4b82ff), // Turkey
+    (0x506f00, 0x506fff), // Slovenia
+    (0x70c070, 0x70c07f), // Oman
+    (0x710258, 0x71028f), // Saudi Arabia
+    (0x710380, 0x71039f), // Saudi Arabia
+    (0x738a00, 0x738aff), // Israel
+    (0x7cf800, 0x7cfaff), // Australia
+    (0x800200, 0x8002ff), // India
+    (0xadf7c8, 0xafffff), // United States
+    (0xc20000, 0xc3ffff), // Canada
+    (0xe40000, 0xe41fff), // Brazil
+    (0xe80600, 0xe806ff), // Chile
+];
+
+/// Country of registration of an ICAO address
+///
+/// Non-ICAO addresses are not allocated and have no country.
+pub fn country(address: &AircraftAddress) -> Option<&'static str> {
+    if address.address_type != AddressType::Icao {
+        return None;
+    }
+    let number = address.number();
+    let index = COUNTRIES.partition_point(|block| block.last < number);
+    COUNTRIES
+        .get(index)
+        .filter(|block| block.first <= number)
+        .map(|block| block.country)
+}
+
+/// Whether an ICAO address is in a known military range
+pub fn is_military(address: &AircraftAddress) -> bool {
+    let number = address.number();
+    address.address_type == AddressType::Icao
+        && MILITARY.iter().any(|(first, last)| (*first..=*last).contains(&number))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn address(icao: [u8; 3]) -> AircraftAddress {
+        AircraftAddress::icao(adsb_deku::ICAO(icao))
+    }
+
+    #[test]
+    fn test_tables_sorted() {
+        assert!(COUNTRIES.windows(2).all(|pair| pair[0].last < pair[1].first));
+        assert!(COUNTRIES.iter().all(|block| block.first <= block.last));
+        assert!(MILITARY.iter().all(|(first, last)| first <= last));
+    }
+
+    #[test]
+    fn test_country() {
+        assert_eq!(country(&address([0x48, 0x40, 0xd6])), Some("Netherlands"));
+        assert_eq!(country(&address([0x3c, 0x64, 0x44])), Some("Germany"));
+        assert_eq!(country(&address([0xa0, 0x00, 0x01])), Some("United States"));
+        assert_eq!(country(&address([0x00, 0x40, 0x00])), Some("Zimbabwe"));
+        // Gaps between the blocks are not allocated
+        assert_eq!(country(&address([0x00, 0x50, 0x00])), None);
+        assert_eq!(country(&address([0xff, 0xff, 0xff])), None);
+        assert_eq!(country(&AircraftAddress::non_icao(adsb_deku::ICAO([0x48, 0x40, 0xd6]))), None);
+
+        assert!(is_military(&address([0xae, 0x12, 0x34])));
+        assert!(is_military(&address([0x43, 0xc1, 0x23])));
+        assert!(!is_military(&address([0xa0, 0x00, 0x01])));
+        assert!(!is_military(&AircraftAddress::non_icao(adsb_deku::ICAO([0xae, 0x12, 0x34]))));
+    }
+}
@@ -0,0 +1,368 @@
+//! Kalman filtering of aircraft tracks
+//!
+//! Each aircraft can carry a constant velocity Kalman filter that combines
+//! the positions and velocities it reports. The horizontal motion is filtered
+//! along the east and north axes of a plane tangent to the earth near the
+//! aircraft, the vertical motion along its own axis. Between messages the
+//! filter dead-reckons, so the position of an aircraft can be predicted at
+//! any time, together with its uncertainty.
+
+use serde::{Deserialize, Serialize};
+use std::time::{Duration, SystemTime};
+
+/// The filter starts over after this long without a position
+const MAX_GAP: Duration = Duration::from_secs(120);
+
+/// Distance in NM from the plane origin after which the plane is moved
+const RECENTER_DISTANCE_NM: f64 = 30.0;
+
+/// Initial speed uncertainty in knots, before a velocity was received
+const INITIAL_SPEED_NOISE_KT: f64 = 600.0;
+
+/// Initial vertical rate uncertainty in ft/min
+const INITIAL_VERTICAL_RATE_NOISE_FPM: f64 = 6000.0;
+
+/// Measurement and process noise of the track filter
+#[derive(Debug, Clone)]
+pub struct KalmanConfig {
+    /// Position accuracy in NM, if the aircraft reports no NACp
+    pub position_noise_nm: f64,
+    /// Ground speed accuracy in knots
+    pub speed_noise_kt: f64,
+    /// Altitude accuracy in feet
+    pub altitude_noise_ft: f64,
+    /// Vertical rate accuracy in ft/min
+    pub vertical_rate_noise_fpm: f64,
+    /// Expected horizontal acceleration in knots per second
+    pub acceleration_kt_s: f64,
+    /// Expected vertical acceleration in ft/min per second
+    pub vertical_acceleration_fpm_s: f64,
+}
+
+impl Default for KalmanConfig {
+    fn default() -> Self {
+        Self {
+            position_noise_nm: 0.05,
+            speed_noise_kt: 4.0,
+            altitude_noise_ft: 50.0,
+            vertical_rate_noise_fpm: 100.0,
+            acceleration_kt_s: 2.0,
+            vertical_acceleration_fpm_s: 300.0,
+        }
+    }
+}
+
+/// Position accuracy in NM for a NACp, half of its 95% bound
+pub fn nac_p_noise_nm(nac_p: u8) -> Option<f64> {
+    let bound_nm = match nac_p {
+        1 => 10.0,
+        2 => 4.0,
+        3 => 2.0,
+        4 => 1.0,
+        5 => 0.5,
+        6 => 0.3,
+        7 => 0.1,
+        8 => 0.05,
+        9 => 30.0 / 1852.0,
+        10 => 10.0 / 1852.0,
+        11 => 3.0 / 1852.0,
+        _ => return None,
+    };
+    Some(bound_nm / 2.0)
+}
+
+/// Value and rate along one axis, with their covariance
+#[derive(Clone, Debug)]
+struct Axis {
+    x: [f64; 2],
+    p: [[f64; 2]; 2],
+}
+
+impl Axis {
+    fn new(value: f64, variance: f64, rate_variance: f64) -> Self {
+        Self {
+            x: [value, 0.0],
+            p: [[variance, 0.0], [0.0, rate_variance]],
+        }
+    }
+
+    /// Move the state `dt` seconds ahead under white noise acceleration of
+    /// spectral density `q`
+    fn predict(&mut self, dt: f64, q: f64) {
+        let p = self.p;
+        self.x[0] += self.x[1] * dt;
+        self.p[0][0] = p[0][0] + dt * (p[0][1] + p[1][0]) + dt * dt * p[1][1] + q * dt.powi(3) / 3.0;
+        self.p[0][1] = p[0][1] + dt * p[1][1] + q * dt * dt / 2.0;
+        self.p[1][0] = self.p[0][1];
+        self.p[1][1] = p[1][1] + q * dt;
+    }
+
+    /// Update the value (`index` 0) or the rate (`index` 1) with a
+    /// measurement of variance `r`
+    fn update(&mut self, index: usize, z: f64, r: f64) {
+        let p = self.p;
+        let s = p[index][index] + r;
+        let k = [p[0][index] / s, p[1][index] / s];
+        let y = z - self.x[index];
+        self.x[0] += k[0] * y;
+        self.x[1] += k[1] * y;
+        for (row, gain) in k.iter().enumerate() {
+            self.p[row][0] -= gain * p[index][0];
+            self.p[row][1] -= gain * p[index][1];
+        }
+    }
+}
+
+/// Covariance of a track estimate, per axis as `[[value, value-rate],
+/// [rate-value, rate]]`
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq)]
+pub struct TrackCovariance {
+    /// East position in NM and velocity in NM/s
+    pub east: [[f64; 2]; 2],
+    /// North position in NM and velocity in NM/s
+    pub north: [[f64; 2]; 2],
+    /// Altitude in feet and vertical rate in ft/s
+    pub vertical: Option<[[f64; 2]; 2]>,
+}
+
+/// The filtered or predicted state of an aircraft
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq)]
+pub struct TrackEstimate {
+    pub time: SystemTime,
+    pub latitude: f64,
+    pub longitude: f64,
+    /// Altitude in feet
+    pub altitude: Option<f64>,
+    /// Ground speed in knots
+    pub ground_speed: f64,
+    /// Ground track in degrees
+    pub track: f64,
+    /// Vertical rate in ft/min
+    pub vertical_rate: Option<f64>,
+    /// Standard deviation of the position in NM
+    pub position_error_nm: f64,
+    pub covariance: TrackCovariance,
+}
+
+/// A constant velocity Kalman filter of the track of an aircraft
+#[derive(Clone, Debug)]
+pub struct TrackFilter {
+    /// Latitude and longitude of the origin of the tangent plane
+    origin: (f64, f64),
+    /// Time of the state
+    time: SystemTime,
+    east: Axis,
+    north: Axis,
+    vertical: Option<Axis>,
+    config: KalmanConfig,
+}
+
+impl TrackFilter {
+    /// Start a filter at a position, with an altitude in feet if known
+    pub fn new(
+        latitude: f64,
+        longitude: f64,
+        altitude: Option<f64>,
+        position_noise_nm: f64,
+        time: SystemTime,
+        config: KalmanConfig,
+    ) -> Self {
+        let (variance, rate_variance) = (position_noise_nm.powi(2), (INITIAL_SPEED_NOISE_KT / 3600.0).powi(2));
+        let vertical = altitude.map(|altitude| {
+            let rate_variance = (INITIAL_VERTICAL_RATE_NOISE_FPM / 60.0).powi(2);
+            Axis::new(altitude, config.altitude_noise_ft.powi(2), rate_variance)
+        });
+        Self {
+            origin: (latitude, longitude),
+            time,
+            east: Axis::new(0.0, variance, rate_variance),
+            north: Axis::new(0.0, variance, rate_variance),
+            vertical,
+            config,
+        }
+    }
+
+    /// Whether the filter is too old to be continued at `time`
+    pub fn is_expired(&self, time: SystemTime) -> bool {
+        time.duration_since(self.time).unwrap_or_default() > MAX_GAP
+    }
+
+    /// Project a position onto the tangent plane, in NM east and north
+    fn project(&self, latitude: f64, longitude: f64) -> (f64, f64) {
+        let (lat0, lon0) = self.origin;
+        let dlon = (longitude - lon0 + 540.0).rem_euclid(360.0) - 180.0;
+        (dlon * 60.0 * lat0.to_radians().cos(), (latitude - lat0) * 60.0)
+    }
+
+    /// The position of a point on the tangent plane
+    fn unproject(&self, east: f64, north: f64) -> (f64, f64) {
+        let (lat0, lon0) = self.origin;
+        let longitude = lon0 + east / (60.0 * lat0.to_radians().cos());
+        (lat0 + north / 60.0, (longitude + 540.0).rem_euclid(360.0) - 180.0)
+    }
+
+    /// Move the origin of the plane to the current position, so that the
+    /// projection stays accurate
+    fn recenter(&mut self) {
+        if self.east.x[0].hypot(self.north.x[0]) < RECENTER_DISTANCE_NM {
+            return;
+        }
+        self.origin = self.unproject(self.east.x[0], self.north.x[0]);
+        self.east.x[0] = 0.0;
+        self.north.x[0] = 0.0;
+    }
+
+    /// Predict the state forward to `time`, earlier times leave it unchanged
+    fn advance(&mut self, time: SystemTime) {
+        let Ok(dt) = time.duration_since(self.time) else {
+            return;
+        };
+        let dt = dt.as_secs_f64();
+        let q = (self.config.acceleration_kt_s / 3600.0).powi(2);
+        self.east.predict(dt, q);
+        self.north.predict(dt, q);
+        if let Some(vertical) = self.vertical.as_mut() {
+            vertical.predict(dt, (self.config.vertical_acceleration_fpm_s / 60.0).powi(2));
+        }
+        self.time = time;
+    }
+
+    /// Update the filter with a position, and an altitude in feet if known
+    pub fn update_position(
+        &mut self,
+        latitude: f64,
+        longitude: f64,
+        altitude: Option<f64>,
+        position_noise_nm: f64,
+        time: SystemTime,
+    ) {
+        self.advance(time);
+        let (east, north) = self.project(latitude, longitude);
+        let r = position_noise_nm.powi(2);
+        self.east.update(0, east, r);
+        self.north.update(0, north, r);
+        self.recenter();
+
+        if let Some(altitude) = altitude {
+            let r = self.config.altitude_noise_ft.powi(2);
+            match self.vertical.as_mut() {
+                Some(vertical) => vertical.update(0, altitude, r),
+                None => {
+                    let rate_variance = (INITIAL_VERTICAL_RATE_NOISE_FPM / 60.0).powi(2);
+                    self.vertical = Some(Axis::new(altitude, r, rate_variance));
+                }
+            }
+        }
+    }
+
+    /// Update the filter with a ground speed in knots and track in degrees,
+    /// and a vertical rate in ft/min if known
+    pub fn update_velocity(
+        &mut self,
+        ground_speed: f64,
+        track: f64,
+        vertical_rate: Option<f64>,
+        time: SystemTime,
+    ) {
+        self.advance(time);
+        let speed = ground_speed / 3600.0;
+        let r = (self.config.speed_noise_kt / 3600.0).powi(2);
+        let track = track.to_radians();
+        self.east.update(1, speed * track.sin(), r);
+        self.north.update(1, speed * track.cos(), r);
+
+        let r = (self.config.vertical_rate_noise_fpm / 60.0).powi(2);
+        if let Some((vertical, rate)) = self.vertical.as_mut().zip(vertical_rate) {
+            vertical.update(1, rate / 60.0, r);
+        }
+    }
+
+    /// The current estimate, as of the last update
+    pub fn estimate(&self) -> TrackEstimate {
+        let (latitude, longitude) = self.unproject(self.east.x[0], self.north.x[0]);
+        let (ve, vn) = (self.east.x[1], self.north.x[1]);
+        TrackEstimate {
+            time: self.time,
+            latitude,
+            longitude,
+            altitude: self.vertical.as_ref().map(|v| v.x[0]),
+            ground_speed: ve.hypot(vn) * 3600.0,
+            track: ve.atan2(vn).to_degrees().rem_euclid(360.0),
+            vertical_rate: self.vertical.as_ref().map(|v| v.x[1] * 60.0),
+            position_error_nm: (self.east.p[0][0] + self.north.p[0][0]).sqrt(),
+            covariance: TrackCovariance {
+                east: self.east.p,
+                north: self.north.p,
+                vertical: self.vertical.as_ref().map(|v| v.p),
+            },
+        }
+    }
+
+    /// The estimate dead-reckoned to `time`
+    pub fn predict(&self, time: SystemTime) -> TrackEstimate {
+        let mut filter = self.clone();
+        filter.advance(time);
+        filter.estimate()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_axis_update() {
+        let mut axis = Axis::new(0.0, 1.0, 1.0);
+        axis.update(0, 2.0, 1.0);
+        // Equal variances meet halfway and halve the variance
+        assert!((axis.x[0] - 1.0).abs() < 1e-12);
+        assert!((axis.p[0][0] - 0.5).abs() < 1e-12);
+
+        // Prediction moves along the rate and grows the variance
+        axis.x[1] = 0.5;
+        axis.predict(2.0, 0.0);
+        assert!((axis.x[0] - 2.0).abs() < 1e-12);
+        assert!(axis.p[0][0] > 0.5);
+    }
+
+    #[test]
+    fn test_track_filter() {
+        let config = KalmanConfig::default();
+        let start = SystemTime::now();
+        // Due east at 360 kt (0.1 NM/s) along the equator, with noisy fixes
+        let truth = |t: f64| (0.0, 0.1 * t / 60.0);
+        let noise = [0.03, -0.04, 0.02, -0.03, 0.04, -0.02];
+        let (lat, lon) = truth(0.0);
+        let mut filter = TrackFilter::new(lat, lon, Some(30000.0), config.position_noise_nm, start, config.clone());
+        let mut raw_error = 0.0;
+        for i in 1..=30 {
+            let t = i as f64;
+            let time = start + Duration::from_secs(i);
+            let (lat, lon) = truth(t);
+            let offset = noise[i as usize % noise.len()] / 60.0;
+            filter.update_velocity(360.0, 90.0, Some(0.0), time);
+            filter.update_position(lat + offset, lon, Some(30000.0), config.position_noise_nm, time);
+            raw_error = offset.abs() * 60.0;
+        }
+
+        let estimate = filter.estimate();
+        let (lat, lon) = truth(30.0);
+        let error = crate::geo::distance_nm(lat, lon, estimate.latitude, estimate.longitude);
+        assert!(error < raw_error);
+        assert!((estimate.ground_speed - 360.0).abs() < 1.0);
+        assert!((estimate.track - 90.0).abs() < 0.5);
+        assert!((estimate.altitude.unwrap() - 30000.0).abs() < 1.0);
+
+        // Dead reckoning 60 seconds ahead covers 6 NM, with a larger error
+        let predicted = filter.predict(start + Duration::from_secs(90));
+        let (lat, lon) = truth(90.0);
+        assert!(crate::geo::distance_nm(lat, lon, predicted.latitude, predicted.longitude) < 0.1);
+        assert!(predicted.position_error_nm > estimate.position_error_nm);
+    }
+
+    #[test]
+    fn test_nac_p_noise() {
+        assert_eq!(nac_p_noise_nm(0), None);
+        assert_eq!(nac_p_noise_nm(8), Some(0.025));
+    }
+}
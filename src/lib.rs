@@ -1,8 +1,10 @@
 //! An ADS-B receiver
-use serde::Serialize;
+use serde::{Deserialize, Serialize};
 use serde_with::DisplayFromStr;
 use serde_with::serde_as;
 use std::collections::HashMap;
+use std::fmt;
+use std::str::FromStr;
 use std::time::SystemTime;
 
 /// Demodulator sample rate
@@ -21,12 +23,63 @@ mod demodulator;
 pub use demodulator::DemodPacket;
 pub use demodulator::Demodulator;
 
+mod mode_s;
+
+mod cpr;
+
+mod geo;
+
+mod icao;
+
+mod database;
+pub use database::{AircraftDatabase, AircraftInfo};
+
+mod routes;
+pub use routes::{FlightRoute, RouteDatabase};
+
+mod retention;
+pub use retention::RetentionConfig;
+
+mod coverage;
+pub use coverage::CoverageConfig;
+
+mod snapshot;
+pub use snapshot::SnapshotConfig;
+
+mod geofence;
+pub use geofence::{Geofence, GeofenceChange, GeofenceShape, GeofenceTransition, Geofences, TimeWindow};
+
+mod kalman;
+pub use kalman::{KalmanConfig, TrackCovariance, TrackEstimate, TrackFilter};
+
+mod flight;
+pub use flight::{FinishedFlight, Flight, FlightEnd, FlightPhase};
+
+mod state;
+pub use state::{AircraftState, FieldUpdate, FieldUpdates, StateField, StatePosition, StateSource};
+
 mod decoder;
 pub use decoder::AdsbPacket;
 pub use decoder::Decoder;
+pub use decoder::DecoderConfig;
+
+mod quarantine;
+pub use quarantine::{QuarantineConfig, QuarantineEntry};
+
+mod alerts;
+pub use alerts::{Alert, AlertChange, AlertCondition, AlertKind, AlertManager};
+
+mod watchlist;
+pub use watchlist::{WatchEntry, WatchPattern, WatchedState, Watchlist};
+
+mod query;
+pub use query::AircraftQuery;
 
 mod tracker;
-pub use tracker::Tracker;
+pub use tracker::{IntegrityPolicy, Tracker, TrackerConfig};
+
+mod events;
+pub use events::{TrackerEvent, TrackerEventKind};
 
 mod beast_output;
 pub use beast_output::{BeastBroadcaster, BeastMessage, BeastServer, BeastOutput};
@@ -43,8 +96,14 @@ pub use sbs1_output::{Sbs1Broadcaster, Sbs1Message, Sbs1Server, Sbs1Output};
 mod websocket_output;
 pub use websocket_output::{WebSocketBroadcaster, WebSocketMessage, WebSocketServer, WebSocketOutput};
 
+mod alert_output;
+pub use alert_output::{AlertBroadcaster, AlertMessage, AlertOutput, AlertServer};
+
 mod output_module;
-pub use output_module::{OutputModule, OutputModuleBuilder, OutputModuleConfig, OutputModuleManager, OutputModuleRegistry};
+pub use output_module::{
+    LineBroadcaster, LineMessage, LineServer, OutputModule, OutputModuleBuilder, OutputModuleConfig,
+    OutputModuleManager, OutputModuleRegistry,
+};
 
 mod rate_limiter;
 pub use rate_limiter::{
@@ -69,42 +128,219 @@ mod rate_limiting_integration_tests;
 type AdsbIcao = adsb_deku::ICAO;
 type AdsbIdentification = adsb_deku::adsb::Identification;
 type AdsbPosition = adsb_deku::Altitude;
-type AdsbVelocity = adsb_deku::adsb::AirborneVelocity;
+
+/// Distinguishes real ICAO 24-bit addresses from anonymous or non-ICAO ones.
+#[derive(Serialize, Deserialize, Clone, Copy, Debug, PartialEq, Eq, Hash)]
+#[serde(rename_all = "snake_case")]
+pub enum AddressType {
+    Icao,
+    NonIcao,
+}
+
+/// Represents where the data for an aircraft was received from.
+#[derive(Serialize, Deserialize, Clone, Copy, Debug, PartialEq, Eq)]
+#[serde(rename_all = "lowercase")]
+pub enum AircraftSource {
+    /// Mode S replies only (no extended squitter received yet)
+    ModeS,
+    /// ADS-B extended squitter (DF17, or DF18 from non-transponder devices)
+    Adsb,
+    /// TIS-B rebroadcast of a ground surveillance target (DF18)
+    Tisb,
+    /// ADS-R rebroadcast of a UAT target (DF18)
+    Adsr,
+}
+
+/// Identifies an aircraft in the register.
+///
+/// Non-ICAO addresses are kept in their own address space so that they cannot
+/// collide with a real aircraft using the same 24-bit value. They are
+/// displayed with a `~` prefix, as dump1090 does.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub struct AircraftAddress {
+    pub icao: AdsbIcao,
+    pub address_type: AddressType,
+}
+
+impl AircraftAddress {
+    /// Create an address for a real ICAO 24-bit address
+    pub fn icao(icao: AdsbIcao) -> Self {
+        Self {
+            icao,
+            address_type: AddressType::Icao,
+        }
+    }
+
+    /// Create an address for an anonymous or non-ICAO address
+    pub fn non_icao(icao: AdsbIcao) -> Self {
+        Self {
+            icao,
+            address_type: AddressType::NonIcao,
+        }
+    }
+
+    /// The 24-bit address as a number
+    pub fn number(&self) -> u32 {
+        u32::from_be_bytes([0, self.icao.0[0], self.icao.0[1], self.icao.0[2]])
+    }
+
+    /// Format the address as an upper case hex ident for SBS-1 style outputs
+    pub fn hex_ident(&self) -> String {
+        let prefix = match self.address_type {
+            AddressType::Icao => "",
+            AddressType::NonIcao => "~",
+        };
+        format!("{}{:02X}{:02X}{:02X}", prefix, self.icao.0[0], self.icao.0[1], self.icao.0[2])
+    }
+}
+
+impl fmt::Display for AircraftAddress {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self.address_type {
+            AddressType::Icao => write!(f, "{}", self.icao),
+            AddressType::NonIcao => write!(f, "~{}", self.icao),
+        }
+    }
+}
+
+impl FromStr for AircraftAddress {
+    type Err = anyhow::Error;
+
+    /// Parse an address as it is displayed, six hex digits with a `~` prefix
+    /// for non-ICAO addresses
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let (hex, address_type) = match s.strip_prefix('~') {
+            Some(hex) => (hex, AddressType::NonIcao),
+            None => (s, AddressType::Icao),
+        };
+        if hex.len() != 6 || !hex.chars().all(|c| c.is_ascii_hexdigit()) {
+            anyhow::bail!("Invalid aircraft address: {}", s);
+        }
+        let [_, high, middle, low] = u32::from_str_radix(hex, 16)?.to_be_bytes();
+        Ok(Self {
+            icao: AdsbIcao([high, middle, low]),
+            address_type,
+        })
+    }
+}
 
 /// Represents the position of an aircraft.
-#[derive(Serialize, Clone, Debug)]
+#[derive(Serialize, Deserialize, Clone, Debug)]
 pub struct AircraftPosition {
     pub latitude: f64,
     pub longitude: f64,
     pub altitude: Option<u16>,
     pub type_code: u8,
+    /// Ground speed in knots (surface positions only)
+    pub ground_speed: Option<f64>,
+    /// Ground track in degrees (surface positions only)
+    pub track: Option<f64>,
+    /// Accuracy and integrity of the position
+    pub quality: PositionQuality,
+}
+
+/// Represents the accuracy and integrity of a position.
+#[derive(Serialize, Deserialize, Clone, Debug, Default, PartialEq)]
+pub struct PositionQuality {
+    /// Navigation integrity category, derived from the type code and the
+    /// NIC supplements of the aircraft's ADS-B version
+    pub nic: u8,
+    /// Navigation accuracy category for position
+    pub nac_p: Option<u8>,
+    /// Navigation accuracy category for velocity
+    pub nac_v: Option<u8>,
+    /// Source integrity level
+    pub sil: Option<u8>,
+    /// System design assurance
+    pub sda: Option<u8>,
+    /// ADS-B version of the aircraft, if an operational status was received
+    pub version: Option<u8>,
+    /// Whether the NIC is below the configured minimum
+    pub low_integrity: bool,
+}
+
+/// Represents the operational status reported by an aircraft (TC 31).
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq)]
+pub struct OperationalStatus {
+    /// ADS-B version number (0 = DO-260, 1 = DO-260A, 2 = DO-260B)
+    pub version: u8,
+    pub surface: bool,
+    pub nic_supplement_a: bool,
+    /// NIC supplement C (surface, version 2 only)
+    pub nic_supplement_c: Option<bool>,
+    pub nac_p: Option<u8>,
+    /// NACv (surface, version 2 only; airborne NACv comes from velocity messages)
+    pub nac_v: Option<u8>,
+    /// Geometric vertical accuracy (airborne, version 2 only)
+    pub gva: Option<u8>,
+    pub sil: Option<u8>,
+    /// SIL supplement, set if the SIL is per sample instead of per hour
+    pub sil_supplement: Option<bool>,
+    pub sda: Option<u8>,
+    /// Barometric altitude cross-checked (airborne only)
+    pub nic_baro: Option<bool>,
+}
+
+/// Represents the location of the receiver.
+#[derive(Serialize, Deserialize, Clone, Copy, Debug, PartialEq)]
+pub struct ReceiverLocation {
+    pub latitude: f64,
+    pub longitude: f64,
+    /// Antenna altitude in feet above mean sea level
+    pub altitude: f64,
+}
+
+/// Represents where an aircraft is as seen from the receiver.
+#[derive(Serialize, Deserialize, Clone, Copy, Debug, PartialEq)]
+pub struct ReceiverRange {
+    /// Great circle distance in nautical miles
+    pub distance_nm: f64,
+    /// True bearing from the receiver in degrees
+    pub bearing: f64,
+    /// Elevation angle above the horizon in degrees, if the altitude is known
+    pub elevation: Option<f64>,
 }
 
 /// Represents the source of the vertical rate.
-#[derive(Serialize, Clone, Debug)]
+#[derive(Serialize, Deserialize, Clone, Copy, Debug, PartialEq, Eq)]
 pub enum AircraftVerticalRateSource {
     BarometricPressureAltitude,
     GeometricAltitude,
 }
 
 /// Represents the velocity of an aircraft.
-#[derive(Serialize, Clone, Debug)]
+#[derive(Serialize, Deserialize, Clone, Debug)]
 pub struct AircraftVelocity {
-    pub heading: f64,
-    pub ground_speed: f64,
-    pub vertical_rate: i16,
+    /// Velocity message subtype: 1-2 for ground speed, 3-4 for airspeed
+    pub subtype: u8,
+    /// Ground track in degrees (subtypes 1-2)
+    pub heading: Option<f64>,
+    /// Ground speed in knots (subtypes 1-2)
+    pub ground_speed: Option<f64>,
+    /// Magnetic heading in degrees (subtypes 3-4)
+    pub magnetic_heading: Option<f64>,
+    /// Indicated airspeed in knots (subtypes 3-4)
+    pub indicated_airspeed: Option<u16>,
+    /// True airspeed in knots (subtypes 3-4)
+    pub true_airspeed: Option<u16>,
+    /// Vertical rate in ft/min, positive when climbing
+    pub vertical_rate: Option<i16>,
     pub vertical_rate_source: AircraftVerticalRateSource,
+    /// Geometric minus barometric altitude in feet
+    pub geo_minus_baro: Option<i16>,
 }
 
 /// Represents a received position of an aircraft.
-#[derive(Serialize, Clone, Debug)]
+#[derive(Serialize, Deserialize, Clone, Debug)]
 pub struct AircraftPositionRecord {
     pub position: AircraftPosition,
     pub time: SystemTime,
+    /// Flight phase at the position
+    pub phase: Option<FlightPhase>,
 }
 
 /// Represents a received velocity of an aircraft.
-#[derive(Serialize, Clone, Debug)]
+#[derive(Serialize, Deserialize, Clone, Debug)]
 pub struct AircraftVelocityRecord {
     pub velocity: AircraftVelocity,
     pub time: SystemTime,
@@ -117,16 +353,198 @@ pub struct CprFrameRecord {
     pub time: SystemTime,
 }
 
+/// Represents the source of the selected altitude.
+#[derive(Serialize, Deserialize, Clone, Copy, Debug, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum SelectedAltitudeSource {
+    /// Mode control panel / flight control unit
+    McpFcu,
+    /// Flight management system
+    Fms,
+}
+
+/// Represents the autopilot intent reported by an aircraft (TC 29).
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq)]
+pub struct AircraftNavigation {
+    /// Selected altitude in feet
+    pub selected_altitude: Option<u32>,
+    pub selected_altitude_source: Option<SelectedAltitudeSource>,
+    /// Barometric pressure setting (QNH) in hPa
+    pub qnh: Option<f64>,
+    /// Selected heading in degrees
+    pub selected_heading: Option<f64>,
+    /// Autopilot engaged, if the MCP/FCU mode bits are valid
+    pub autopilot: Option<bool>,
+    pub vnav: Option<bool>,
+    pub altitude_hold: Option<bool>,
+    pub approach: Option<bool>,
+    pub lnav: Option<bool>,
+    pub tcas_operational: bool,
+}
+
+/// Represents the emergency/priority status reported by an aircraft (TC 28).
+#[derive(Serialize, Deserialize, Clone, Copy, Debug, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum EmergencyState {
+    None,
+    General,
+    Lifeguard,
+    MinimumFuel,
+    NoCommunications,
+    UnlawfulInterference,
+    DownedAircraft,
+    Reserved,
+}
+
+impl EmergencyState {
+    /// Get the emergency state from its 3-bit code
+    pub fn from_code(code: u8) -> Self {
+        match code {
+            0 => Self::None,
+            1 => Self::General,
+            2 => Self::Lifeguard,
+            3 => Self::MinimumFuel,
+            4 => Self::NoCommunications,
+            5 => Self::UnlawfulInterference,
+            6 => Self::DownedAircraft,
+            _ => Self::Reserved,
+        }
+    }
+
+    /// Whether the state declares an emergency
+    pub fn is_emergency(&self) -> bool {
+        !matches!(self, Self::None | Self::Reserved)
+    }
+}
+
+/// Whether a squawk is one of the emergency codes 7500, 7600 or 7700
+pub fn is_emergency_squawk(squawk: u16) -> bool {
+    matches!(squawk, 7500 | 7600 | 7700)
+}
+
+/// Identity of the threat aircraft in a resolution advisory.
+#[serde_as]
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum RaThreat {
+    /// Mode S address of a transponder equipped threat
+    Address {
+        #[serde_as(as = "DisplayFromStr")]
+        icao: AdsbIcao,
+    },
+    /// Altitude, range and bearing of a threat without a Mode S address
+    Position {
+        altitude: Option<i32>,
+        range_nm: Option<f64>,
+        /// Start of the 6 degree bearing sector, relative to own heading
+        bearing: Option<u16>,
+    },
+}
+
+/// Represents an ACAS/TCAS resolution advisory report (BDS 3,0).
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq)]
+pub struct ResolutionAdvisory {
+    /// Active resolution advisory (ARA) bits
+    pub ara: u16,
+    /// Resolution advisory complement (RAC) bits
+    pub rac: u8,
+    /// Resolution advisory terminated (RAT)
+    pub terminated: bool,
+    /// Multiple threat encounter (MTE)
+    pub multiple_threats: bool,
+    /// Decoded senses of the active resolution advisory
+    pub advisories: Vec<String>,
+    /// Decoded complements received from other ACAS aircraft
+    pub complements: Vec<String>,
+    pub threat: Option<RaThreat>,
+}
+
+/// Represents a received resolution advisory of an aircraft.
+#[derive(Serialize, Deserialize, Clone, Debug)]
+pub struct ResolutionAdvisoryRecord {
+    pub advisory: ResolutionAdvisory,
+    pub time: SystemTime,
+}
+
 /// Represents a summary of the received information about an aircraft.
 #[serde_as]
-#[derive(Serialize, Clone, Debug)]
+#[derive(Serialize, Deserialize, Clone, Debug)]
 pub struct AircraftRecord {
     #[serde_as(as = "DisplayFromStr")]
     pub icao: AdsbIcao,
+    pub address_type: AddressType,
+    /// Country of registration from the ICAO address allocation
+    #[serde(skip_deserializing)]
+    pub country: Option<&'static str>,
+    /// Whether the address is in a known military range
+    pub military: bool,
+    /// Registration, type and operator from the aircraft database
+    #[serde(flatten)]
+    pub info: AircraftInfo,
+    pub source: AircraftSource,
     pub callsign: Option<String>,
     pub emitter_category: Option<u8>,
+    /// Transponder capability (CA) from the last all-call reply
+    pub capability: Option<u8>,
+    /// Whether the aircraft is on the ground, if known
+    pub on_ground: Option<bool>,
+    /// Mode A code, written with its octal digits as decimal digits (7700)
+    pub squawk: Option<u16>,
+    /// Emergency/priority status from the last aircraft status message
+    pub emergency: Option<EmergencyState>,
+    /// Special position identification (IDENT) from the last surveillance reply
+    pub spi: bool,
+    /// Autopilot intent from the last target state and status message
+    pub navigation: Option<AircraftNavigation>,
+    /// Version and quality indicators from the last operational status message
+    pub operational_status: Option<OperationalStatus>,
+    /// NIC supplement B from the last airborne position message
+    #[serde(skip)]
+    pub nic_supplement_b: bool,
+    /// NACv from the last airborne velocity message
+    #[serde(skip)]
+    pub velocity_nac: Option<u8>,
+    /// Geometric (GNSS) altitude in feet, reported directly or estimated from
+    /// the barometric altitude and the difference in the velocity message
+    pub geometric_altitude: Option<i32>,
     pub positions: Vec<AircraftPositionRecord>,
+    /// Time of the last position of the trail that is final after
+    /// simplification, the positions after it are simplified on the next pass
+    #[serde(skip)]
+    pub simplified_until: Option<SystemTime>,
     pub velocities: Vec<AircraftVelocityRecord>,
+    /// The latest ACAS resolution advisory
+    pub resolution_advisory: Option<ResolutionAdvisoryRecord>,
+    /// Previous ACAS resolution advisories, oldest first
+    pub resolution_advisory_history: Vec<ResolutionAdvisoryRecord>,
+    /// Distance, bearing and elevation from the receiver at the last position
+    pub receiver_range: Option<ReceiverRange>,
+    /// The current flight, its positions are the flight's track
+    pub flight: Option<Flight>,
+    /// Kalman filtered position and velocity as of the last update
+    pub track_estimate: Option<TrackEstimate>,
+    /// Name of the watchlist entry the aircraft matches
+    pub watchlist: Option<String>,
+    /// Watched fields as of the last watchlist check
+    #[serde(skip)]
+    pub watched: Option<WatchedState>,
+    /// Kalman filter of the track, if enabled
+    #[serde(skip)]
+    pub track_filter: Option<TrackFilter>,
+    /// Consecutive position fixes rejected as implausible
+    #[serde(skip)]
+    pub rejected_positions: u32,
+    /// Whether the position was re-initialised after implausible fixes, the
+    /// last position is then no reference for the next one
+    #[serde(skip)]
+    pub position_reset: bool,
+    /// Whether the positions are decoded relative to the receiver location
+    /// and not yet confirmed by a global decode
+    #[serde(skip)]
+    pub receiver_relative: bool,
+    /// When the fields without a history were last updated
+    #[serde(skip)]
+    pub updates: FieldUpdates,
     #[serde(skip)]
     pub last_cpr_even: Option<CprFrameRecord>,
     #[serde(skip)]
@@ -134,10 +552,74 @@ pub struct AircraftRecord {
     pub last_seen: SystemTime,
 }
 
+impl AircraftRecord {
+    /// Create an empty record for an aircraft first seen at `now`
+    pub fn new(address: &AircraftAddress, now: SystemTime) -> Self {
+        Self {
+            icao: address.icao,
+            address_type: address.address_type,
+            country: icao::country(address),
+            military: icao::is_military(address),
+            info: AircraftInfo::default(),
+            source: AircraftSource::ModeS,
+            callsign: None,
+            emitter_category: None,
+            capability: None,
+            on_ground: None,
+            squawk: None,
+            emergency: None,
+            spi: false,
+            navigation: None,
+            operational_status: None,
+            nic_supplement_b: false,
+            velocity_nac: None,
+            geometric_altitude: None,
+            receiver_range: None,
+            flight: None,
+            track_estimate: None,
+            track_filter: None,
+            watchlist: None,
+            watched: None,
+            rejected_positions: 0,
+            position_reset: false,
+            receiver_relative: false,
+            positions: Vec::new(),
+            simplified_until: None,
+            velocities: Vec::new(),
+            resolution_advisory: None,
+            resolution_advisory_history: Vec::new(),
+            updates: FieldUpdates::default(),
+            last_cpr_even: None,
+            last_cpr_odd: None,
+            last_seen: now,
+        }
+    }
+
+    /// Whether the aircraft has declared an emergency or squawks an emergency code
+    pub fn has_emergency(&self) -> bool {
+        self.emergency.is_some_and(|state| state.is_emergency())
+            || self.squawk.is_some_and(is_emergency_squawk)
+    }
+
+    /// The Kalman filtered state dead-reckoned to `time`, if the track is filtered
+    pub fn predict(&self, time: SystemTime) -> Option<TrackEstimate> {
+        self.track_filter.as_ref().map(|filter| filter.predict(time))
+    }
+
+    /// Approximate memory used by the record and its history, in bytes
+    pub fn memory_size(&self) -> usize {
+        size_of::<Self>()
+            + self.callsign.as_ref().map_or(0, String::capacity)
+            + self.positions.capacity() * size_of::<AircraftPositionRecord>()
+            + self.velocities.capacity() * size_of::<AircraftVelocityRecord>()
+            + self.resolution_advisory_history.capacity() * size_of::<ResolutionAdvisoryRecord>()
+    }
+}
+
 /// Represents a collection of received aircrafts.
 #[serde_as]
-#[derive(Serialize, Clone, Debug)]
+#[derive(Serialize, Deserialize, Clone, Debug)]
 pub struct AircraftRegister {
     #[serde_as(as = "HashMap<DisplayFromStr, _>")]
-    register: HashMap<AdsbIcao, AircraftRecord>,
+    register: HashMap<AircraftAddress, AircraftRecord>,
 }
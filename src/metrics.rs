@@ -17,16 +17,31 @@ pub struct GlobalMetrics {
     pub packets_crc_failed: AtomicU64,
     pub packets_decoded: AtomicU64,
     pub packets_decode_failed: AtomicU64,
+    pub packets_duplicate: AtomicU64,
 
     // Message types (by ADS-B ME field)
     pub msg_identification: AtomicU64,
     pub msg_position: AtomicU64,
     pub msg_velocity: AtomicU64,
+    pub msg_status: AtomicU64,
+    pub msg_all_call: AtomicU64,
+    pub msg_acas: AtomicU64,
     pub msg_other: AtomicU64,
 
     // Tracker
     pub aircraft_tracked: AtomicU64,
     pub updates_processed: AtomicU64,
+    pub positions_rejected: AtomicU64,
+    pub aircraft_evicted: AtomicU64,
+
+    // Register memory
+    pub register_positions: AtomicU64,
+    pub register_velocities: AtomicU64,
+    pub register_bytes: AtomicU64,
+
+    // Receiver range, f64 values stored as bits
+    pub max_range_nm: AtomicU64,
+    pub avg_range_nm: AtomicU64,
 
     // Output modules
     pub output_beast: AtomicU64,
@@ -44,12 +59,23 @@ impl GlobalMetrics {
             packets_crc_failed: AtomicU64::new(0),
             packets_decoded: AtomicU64::new(0),
             packets_decode_failed: AtomicU64::new(0),
+            packets_duplicate: AtomicU64::new(0),
             msg_identification: AtomicU64::new(0),
             msg_position: AtomicU64::new(0),
             msg_velocity: AtomicU64::new(0),
+            msg_status: AtomicU64::new(0),
+            msg_all_call: AtomicU64::new(0),
+            msg_acas: AtomicU64::new(0),
             msg_other: AtomicU64::new(0),
             aircraft_tracked: AtomicU64::new(0),
             updates_processed: AtomicU64::new(0),
+            positions_rejected: AtomicU64::new(0),
+            aircraft_evicted: AtomicU64::new(0),
+            register_positions: AtomicU64::new(0),
+            register_velocities: AtomicU64::new(0),
+            register_bytes: AtomicU64::new(0),
+            max_range_nm: AtomicU64::new(0),
+            avg_range_nm: AtomicU64::new(0),
             output_beast: AtomicU64::new(0),
             output_raw: AtomicU64::new(0),
             output_sbs1: AtomicU64::new(0),
@@ -65,12 +91,23 @@ impl GlobalMetrics {
             packets_crc_failed: self.packets_crc_failed.load(Ordering::Relaxed),
             packets_decoded: self.packets_decoded.load(Ordering::Relaxed),
             packets_decode_failed: self.packets_decode_failed.load(Ordering::Relaxed),
+            packets_duplicate: self.packets_duplicate.load(Ordering::Relaxed),
             msg_identification: self.msg_identification.load(Ordering::Relaxed),
             msg_position: self.msg_position.load(Ordering::Relaxed),
             msg_velocity: self.msg_velocity.load(Ordering::Relaxed),
+            msg_status: self.msg_status.load(Ordering::Relaxed),
+            msg_all_call: self.msg_all_call.load(Ordering::Relaxed),
+            msg_acas: self.msg_acas.load(Ordering::Relaxed),
             msg_other: self.msg_other.load(Ordering::Relaxed),
             aircraft_tracked: self.aircraft_tracked.load(Ordering::Relaxed),
             updates_processed: self.updates_processed.load(Ordering::Relaxed),
+            positions_rejected: self.positions_rejected.load(Ordering::Relaxed),
+            aircraft_evicted: self.aircraft_evicted.load(Ordering::Relaxed),
+            register_positions: self.register_positions.load(Ordering::Relaxed),
+            register_velocities: self.register_velocities.load(Ordering::Relaxed),
+            register_bytes: self.register_bytes.load(Ordering::Relaxed),
+            max_range_nm: f64::from_bits(self.max_range_nm.load(Ordering::Relaxed)),
+            avg_range_nm: f64::from_bits(self.avg_range_nm.load(Ordering::Relaxed)),
             output_beast: self.output_beast.load(Ordering::Relaxed),
             output_raw: self.output_raw.load(Ordering::Relaxed),
             output_sbs1: self.output_sbs1.load(Ordering::Relaxed),
@@ -104,12 +141,25 @@ pub struct MetricsSnapshot {
     pub packets_crc_failed: u64,
     pub packets_decoded: u64,
     pub packets_decode_failed: u64,
+    pub packets_duplicate: u64,
     pub msg_identification: u64,
     pub msg_position: u64,
     pub msg_velocity: u64,
+    pub msg_status: u64,
+    pub msg_all_call: u64,
+    pub msg_acas: u64,
     pub msg_other: u64,
     pub aircraft_tracked: u64,
     pub updates_processed: u64,
+    pub positions_rejected: u64,
+    pub aircraft_evicted: u64,
+    pub register_positions: u64,
+    pub register_velocities: u64,
+    pub register_bytes: u64,
+    /// Longest distance of a position from the receiver in NM
+    pub max_range_nm: f64,
+    /// Average distance of positions from the receiver in NM
+    pub avg_range_nm: f64,
     pub output_beast: u64,
     pub output_raw: u64,
     pub output_sbs1: u64,
@@ -177,21 +227,34 @@ impl MetricsSnapshot {
     pub fn format_detailed(&self) -> String {
         format!(
             "Metrics Summary:\n\
-             ├─ Decoder: {} packets ({:.1}% CRC OK), {} decoded ({:.1}% success)\n\
-             ├─ Messages: {} ID, {} Pos, {} Vel, {} Other\n\
-             ├─ Aircraft: {} tracked, {} updates processed\n\
+             ├─ Decoder: {} packets ({:.1}% CRC OK), {} decoded ({:.1}% success), {} duplicates\n\
+             ├─ Messages: {} ID, {} Pos, {} Vel, {} Status, {} All-call, {} ACAS, {} Other\n\
+             ├─ Aircraft: {} tracked, {} updates processed, {} positions rejected, {} evicted\n\
+             ├─ Register: {} positions, {} velocities, {} KiB\n\
+             ├─ Range: {:.1} NM max, {:.1} NM average\n\
              ├─ Outputs: {} BEAST, {} Raw, {} SBS-1, {} WebSocket\n\
              └─ Performance: {:.0} msg/s over {:.0}s uptime",
             self.total_packets(),
             self.crc_pass_rate(),
             self.packets_decoded,
             self.decode_success_rate(),
+            self.packets_duplicate,
             self.msg_identification,
             self.msg_position,
             self.msg_velocity,
+            self.msg_status,
+            self.msg_all_call,
+            self.msg_acas,
             self.msg_other,
             self.aircraft_tracked,
             self.updates_processed,
+            self.positions_rejected,
+            self.aircraft_evicted,
+            self.register_positions,
+            self.register_velocities,
+            self.register_bytes / 1024,
+            self.max_range_nm,
+            self.avg_range_nm,
             self.output_beast,
             self.output_raw,
             self.output_sbs1,
@@ -234,12 +297,23 @@ mod tests {
             packets_crc_failed: 50,
             packets_decoded: 980,
             packets_decode_failed: 20,
+            packets_duplicate: 0,
             msg_identification: 100,
             msg_position: 600,
             msg_velocity: 280,
+            msg_status: 0,
+            msg_all_call: 0,
+            msg_acas: 0,
             msg_other: 0,
             aircraft_tracked: 45,
             updates_processed: 980,
+            positions_rejected: 0,
+            aircraft_evicted: 0,
+            register_positions: 0,
+            register_velocities: 0,
+            register_bytes: 0,
+            max_range_nm: 0.0,
+            avg_range_nm: 0.0,
             output_beast: 0,
             output_raw: 0,
             output_sbs1: 0,
@@ -0,0 +1,914 @@
+//! Mode S frame helpers shared by the decoder and tracker
+//!
+//! These cover the parts of the Mode S downlink formats that are needed
+//! before (or independently of) parsing with adsb_deku: frame length,
+//! the CRC-24 remainder used for parity and address/parity validation,
+//! and raw field access for the short formats and the ACAS reports carried
+//! in DF16/DF20/DF21.
+
+use crate::cpr::CprFrame;
+use crate::{
+    AddressType, AdsbIcao, AircraftNavigation, AircraftSource, AircraftVelocity,
+    AircraftVerticalRateSource, EmergencyState, OperationalStatus, RaThreat, ResolutionAdvisory,
+    SelectedAltitudeSource,
+};
+
+/// Length of a short Mode S frame (DF0, DF4, DF5, DF11) in bits
+pub const SHORT_FRAME_BITS: usize = 56;
+/// Length of a long Mode S frame (DF16 and above) in bits
+pub const LONG_FRAME_BITS: usize = 112;
+
+/// Mode S CRC-24 generator polynomial, including the implicit x^24 term
+const CRC_GENERATOR: u32 = 0x1FFF409;
+
+/// Mask of the II/SI code bits that may be XORed into a DF11 parity field
+const DF11_IID_MASK: u32 = 0x7F;
+
+/// BDS register (and DF16 VDS) of an ACAS active resolution advisory report
+const BDS_RESOLUTION_ADVISORY: u64 = 0x30;
+
+/// Get the downlink format of a frame from its first byte
+///
+/// DF24 and above only use the first two bits, so they are all reported as 24.
+pub fn downlink_format(bytes: &[u8]) -> u8 {
+    let df = bytes.first().copied().unwrap_or(0) >> 3;
+    df.min(24)
+}
+
+/// Get the frame length in bits for a downlink format
+pub fn frame_bits(df: u8) -> usize {
+    if df < 16 {
+        SHORT_FRAME_BITS
+    } else {
+        LONG_FRAME_BITS
+    }
+}
+
+/// Compute the CRC-24 checksum over the given bytes
+fn crc24(data: &[u8]) -> u32 {
+    let mut crc: u32 = 0;
+    for &byte in data {
+        crc ^= (byte as u32) << 16;
+        for _ in 0..8 {
+            crc <<= 1;
+            if crc & 0x1000000 != 0 {
+                crc ^= CRC_GENERATOR;
+            }
+        }
+    }
+    crc & 0xFFFFFF
+}
+
+/// Compute the CRC remainder of a complete frame
+///
+/// This is the checksum of the data bits XORed with the 24-bit parity field.
+/// It is zero for a valid DF17/DF18 frame, the II/SI code for DF11 and the
+/// aircraft address for address/parity formats (DF0, DF4, DF5, DF16, DF20, DF21).
+pub fn crc_remainder(bytes: &[u8]) -> u32 {
+    if bytes.len() < 4 {
+        return u32::MAX;
+    }
+    let (data, parity) = bytes.split_at(bytes.len() - 3);
+    crc24(data) ^ u32::from_be_bytes([0, parity[0], parity[1], parity[2]])
+}
+
+/// Check whether a DF11 remainder is valid (zero apart from the II/SI code)
+pub fn is_valid_all_call_remainder(remainder: u32) -> bool {
+    remainder & !DF11_IID_MASK == 0
+}
+
+/// Whether the downlink format carries the address in its address/parity field
+pub fn has_address_parity(df: u8) -> bool {
+    matches!(df, 0 | 4 | 5 | 16 | 20 | 21)
+}
+
+/// Get the announced address of a DF11, DF17 or DF18 frame
+pub fn announced_address(bytes: &[u8]) -> Option<AdsbIcao> {
+    match downlink_format(bytes) {
+        11 | 17 | 18 if bytes.len() >= 4 => Some(adsb_deku::ICAO([bytes[1], bytes[2], bytes[3]])),
+        _ => None,
+    }
+}
+
+/// Convert a 24-bit value (such as a CRC remainder) into an address
+pub fn address_from_u32(value: u32) -> AdsbIcao {
+    adsb_deku::ICAO([(value >> 16) as u8, (value >> 8) as u8, value as u8])
+}
+
+/// Convert an address into its 24-bit value
+pub fn address_to_u32(icao: &AdsbIcao) -> u32 {
+    u32::from_be_bytes([0, icao.0[0], icao.0[1], icao.0[2]])
+}
+
+/// Get the CA (capability) field of a DF11 or DF17 frame
+pub fn capability(bytes: &[u8]) -> u8 {
+    bytes.first().copied().unwrap_or(0) & 0x07
+}
+
+/// Get the CF (control field) of a DF18 frame
+pub fn control_field(bytes: &[u8]) -> u8 {
+    bytes.first().copied().unwrap_or(0) & 0x07
+}
+
+/// Get the type code of the ME field of a DF17/DF18 frame
+pub fn type_code(bytes: &[u8]) -> u8 {
+    bytes.get(4).copied().unwrap_or(0) >> 3
+}
+
+/// Get the IMF flag of a TIS-B/ADS-R message
+///
+/// The flag is set when the announced address is not an ICAO address (for
+/// example a TIS-B track file number). Only position and velocity messages
+/// carry it.
+pub fn imf_flag(bytes: &[u8]) -> bool {
+    match type_code(bytes) {
+        9..=18 | 20..=22 => bytes.get(4).is_some_and(|b| b & 0x01 != 0),
+        19 => bytes.get(5).is_some_and(|b| b & 0x80 != 0),
+        _ => false,
+    }
+}
+
+/// Classify the address and source of a DF18 frame from its control field
+///
+/// Returns `None` for control fields whose ME field does not follow the
+/// ADS-B message layout (coarse TIS-B, TIS-B management and reserved).
+pub fn extended_squitter_kind(bytes: &[u8]) -> Option<(AddressType, AircraftSource)> {
+    let imf_address = if imf_flag(bytes) {
+        AddressType::NonIcao
+    } else {
+        AddressType::Icao
+    };
+    match control_field(bytes) {
+        0 => Some((AddressType::Icao, AircraftSource::Adsb)),
+        1 => Some((AddressType::NonIcao, AircraftSource::Adsb)),
+        2 => Some((imf_address, AircraftSource::Tisb)),
+        5 => Some((AddressType::NonIcao, AircraftSource::Tisb)),
+        6 => Some((imf_address, AircraftSource::Adsr)),
+        _ => None,
+    }
+}
+
+/// Convert a 13-bit identity (ID) field into the Gillham-ordered hex code
+/// used by `mode_a_to_mode_c`, with the squawk digits in the four nibbles
+fn id13_to_gillham(id13: u16) -> u16 {
+    // Field bits from MSB: C1 A1 C2 A2 C4 A4 X B1 D1 B2 D2 B4 D4
+    const BITS: [(u16, u16); 12] = [
+        (0x1000, 0x0010), // C1
+        (0x0800, 0x1000), // A1
+        (0x0400, 0x0020), // C2
+        (0x0200, 0x2000), // A2
+        (0x0100, 0x0040), // C4
+        (0x0080, 0x4000), // A4
+        (0x0020, 0x0100), // B1
+        (0x0010, 0x0001), // D1
+        (0x0008, 0x0200), // B2
+        (0x0004, 0x0002), // D2
+        (0x0002, 0x0400), // B4
+        (0x0001, 0x0004), // D4
+    ];
+    BITS.iter()
+        .filter(|(field, _)| id13 & field != 0)
+        .fold(0, |acc, (_, gillham)| acc | gillham)
+}
+
+/// Decode a 13-bit identity (ID) field into a squawk
+///
+/// The four octal digits of the Mode A code are returned as decimal digits,
+/// so 7700 is returned as `7700`.
+pub fn squawk_from_id13(id13: u16) -> u16 {
+    let gillham = id13_to_gillham(id13);
+    ((gillham >> 12) & 0x7) * 1000
+        + ((gillham >> 8) & 0x7) * 100
+        + ((gillham >> 4) & 0x7) * 10
+        + (gillham & 0x7)
+}
+
+/// Convert a Gillham coded Mode A value into a Mode C altitude in 100 ft units
+fn mode_a_to_mode_c(mode_a: u16) -> Option<i32> {
+    if mode_a & 0x8889 != 0 || mode_a & 0x00F0 == 0 {
+        return None;
+    }
+
+    let mut one_hundreds: i32 = 0;
+    if mode_a & 0x0010 != 0 {
+        one_hundreds ^= 0x007; // C1
+    }
+    if mode_a & 0x0020 != 0 {
+        one_hundreds ^= 0x003; // C2
+    }
+    if mode_a & 0x0040 != 0 {
+        one_hundreds ^= 0x001; // C4
+    }
+    // Swap 5 and 7 so that only 1 to 5 are valid
+    if one_hundreds & 5 == 5 {
+        one_hundreds ^= 2;
+    }
+    if one_hundreds > 5 {
+        return None;
+    }
+
+    let mut five_hundreds: i32 = 0;
+    for (bit, mask) in [
+        (0x0002, 0x0FF), // D2
+        (0x0004, 0x07F), // D4
+        (0x1000, 0x03F), // A1
+        (0x2000, 0x01F), // A2
+        (0x4000, 0x00F), // A4
+        (0x0100, 0x007), // B1
+        (0x0200, 0x003), // B2
+        (0x0400, 0x001), // B4
+    ] {
+        if mode_a & bit != 0 {
+            five_hundreds ^= mask;
+        }
+    }
+    // The hundreds count runs backwards in odd five hundred bands
+    if five_hundreds & 1 != 0 {
+        one_hundreds = 6 - one_hundreds;
+    }
+
+    let altitude = five_hundreds * 5 + one_hundreds - 13;
+    (altitude >= -12).then_some(altitude)
+}
+
+/// Decode a 13-bit altitude code (AC) field into feet
+///
+/// Supports both 25 ft (Q bit set) and Gillham coded 100 ft altitudes.
+/// Metric altitudes (M bit set) are not decoded.
+pub fn ac13_altitude(ac13: u16) -> Option<i32> {
+    if ac13 == 0 || ac13 & 0x0040 != 0 {
+        return None;
+    }
+    if ac13 & 0x0010 != 0 {
+        let n = ((ac13 & 0x1F80) >> 2) | ((ac13 & 0x0020) >> 1) | (ac13 & 0x000F);
+        Some(n as i32 * 25 - 1000)
+    } else {
+        mode_a_to_mode_c(id13_to_gillham(ac13)).map(|alt| alt * 100)
+    }
+}
+
+/// Get the 56-bit ME, MB or MV field of a long frame
+fn long_field(bytes: &[u8]) -> Option<u64> {
+    let field = bytes.get(4..11)?;
+    let mut field_bytes = [0u8; 8];
+    field_bytes[1..].copy_from_slice(field);
+    Some(u64::from_be_bytes(field_bytes))
+}
+
+/// Extract `len` bits starting at 1-based bit `start` of a 56-bit ME/MB/MV field
+fn mb_field(mb: u64, start: u32, len: u32) -> u64 {
+    (mb >> (56 - (start + len - 1))) & ((1 << len) - 1)
+}
+
+/// Decode an ACAS resolution advisory report from a DF16, DF20 or DF21 frame
+///
+/// DF16 carries it in the MV field with VDS 3,0 and Comm-B replies in the MB
+/// field as BDS 3,0. Returns `None` for any other content.
+pub fn resolution_advisory(bytes: &[u8]) -> Option<ResolutionAdvisory> {
+    if !matches!(downlink_format(bytes), 16 | 20 | 21) {
+        return None;
+    }
+    let mb = long_field(bytes)?;
+    if mb_field(mb, 1, 8) != BDS_RESOLUTION_ADVISORY {
+        return None;
+    }
+
+    let ara = mb_field(mb, 9, 14) as u16;
+    let rac = mb_field(mb, 23, 4) as u8;
+    let terminated = mb_field(mb, 27, 1) != 0;
+    let multiple_threats = mb_field(mb, 28, 1) != 0;
+    let threat = match mb_field(mb, 29, 2) {
+        0 => None,
+        1 => Some(RaThreat::Address {
+            icao: address_from_u32(mb_field(mb, 31, 24) as u32),
+        }),
+        2 => {
+            let range = mb_field(mb, 44, 7);
+            let bearing = mb_field(mb, 51, 6);
+            Some(RaThreat::Position {
+                altitude: ac13_altitude(mb_field(mb, 31, 13) as u16),
+                range_nm: match range {
+                    0 => None,
+                    1 => Some(0.05),
+                    n => Some((n - 1) as f64 / 10.0),
+                },
+                bearing: match bearing {
+                    1..=60 => Some((bearing as u16 - 1) * 6),
+                    _ => None,
+                },
+            })
+        }
+        _ => return None,
+    };
+
+    // An empty register is not a report
+    if ara == 0 && rac == 0 && !terminated && threat.is_none() {
+        return None;
+    }
+
+    Some(ResolutionAdvisory {
+        ara,
+        rac,
+        terminated,
+        multiple_threats,
+        advisories: decode_ara(ara, multiple_threats),
+        complements: decode_rac(rac),
+        threat,
+    })
+}
+
+/// Describe the senses set in an active resolution advisory (ARA) field
+fn decode_ara(ara: u16, multiple_threats: bool) -> Vec<String> {
+    // ARA bit 41 is the MSB of the 14-bit field
+    let bit = |n: u16| ara & (1 << (54 - n)) != 0;
+    let mut advisories = Vec::new();
+    if bit(41) {
+        advisories.push(if bit(42) { "corrective" } else { "preventive" });
+        advisories.push(if bit(43) { "downward sense" } else { "upward sense" });
+        if bit(44) {
+            advisories.push("increased rate");
+        }
+        if bit(45) {
+            advisories.push("sense reversal");
+        }
+        if bit(46) {
+            advisories.push("altitude crossing");
+        }
+        advisories.push(if bit(47) { "positive" } else { "vertical speed limit" });
+    } else if multiple_threats {
+        for (n, name) in [
+            (42, "upward correction"),
+            (43, "positive climb"),
+            (44, "downward correction"),
+            (45, "positive descend"),
+            (46, "altitude crossing"),
+            (47, "sense reversal"),
+        ] {
+            if bit(n) {
+                advisories.push(name);
+            }
+        }
+    }
+    advisories.into_iter().map(String::from).collect()
+}
+
+/// Describe the RA complements (RAC) received from other ACAS aircraft
+fn decode_rac(rac: u8) -> Vec<String> {
+    [
+        (0x8, "do not pass below"),
+        (0x4, "do not pass above"),
+        (0x2, "do not turn left"),
+        (0x1, "do not turn right"),
+    ]
+    .iter()
+    .filter(|(mask, _)| rac & mask != 0)
+    .map(|(_, name)| name.to_string())
+    .collect()
+}
+
+/// Decoded fields of a surface position message (TC 5-8)
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct SurfacePositionReport {
+    pub type_code: u8,
+    /// Ground speed in knots, from the movement field
+    pub ground_speed: Option<f64>,
+    /// Ground track in degrees, if the track status bit is set
+    pub track: Option<f64>,
+    pub frame: CprFrame,
+}
+
+/// Decode the movement field of a surface position message into knots
+fn surface_movement(movement: u64) -> Option<f64> {
+    // Start of each quantization band and its speed in knots
+    const BANDS: [(u64, f64); 7] = [
+        (2, 0.125),
+        (9, 1.0),
+        (13, 2.0),
+        (39, 15.0),
+        (94, 70.0),
+        (109, 100.0),
+        (124, 175.0),
+    ];
+    match movement {
+        0 | 125.. => None,
+        1 => Some(0.0),
+        124 => Some(175.0),
+        _ => {
+            let band = BANDS.iter().position(|(start, _)| *start > movement)?;
+            let (start, speed) = BANDS[band - 1];
+            let (next_start, next_speed) = BANDS[band];
+            let step = (next_speed - speed) / (next_start - start) as f64;
+            Some(speed + (movement - start) as f64 * step)
+        }
+    }
+}
+
+/// Decode a surface position message (TC 5-8) from a DF17/DF18 frame
+pub fn surface_position(bytes: &[u8]) -> Option<SurfacePositionReport> {
+    let me = long_field(bytes)?;
+    let type_code = mb_field(me, 1, 5) as u8;
+    if !(5..=8).contains(&type_code) {
+        return None;
+    }
+    let track_valid = mb_field(me, 13, 1) != 0;
+    Some(SurfacePositionReport {
+        type_code,
+        ground_speed: surface_movement(mb_field(me, 6, 7)),
+        track: track_valid.then(|| mb_field(me, 14, 7) as f64 * 360.0 / 128.0),
+        frame: CprFrame {
+            lat_cpr: mb_field(me, 23, 17) as u32,
+            lon_cpr: mb_field(me, 40, 17) as u32,
+            odd: mb_field(me, 22, 1) != 0,
+        },
+    })
+}
+
+/// Decode an airborne velocity message (TC 19) from a DF17/DF18 frame
+///
+/// Unlike adsb_deku, this also decodes the airspeed subtypes (3 and 4) and
+/// the difference between the geometric and barometric altitude.
+pub fn airborne_velocity(bytes: &[u8]) -> Option<AircraftVelocity> {
+    let me = long_field(bytes)?;
+    if mb_field(me, 1, 5) != 19 {
+        return None;
+    }
+    let subtype = mb_field(me, 6, 3) as u8;
+    // Supersonic subtypes use units of 4 knots
+    let scale = if subtype == 2 || subtype == 4 { 4 } else { 1 };
+    // Speed fields are offset by one, zero means not available
+    let speed = |start| match mb_field(me, start, 10) {
+        0 => None,
+        value => Some((value - 1) * scale),
+    };
+
+    let mut velocity = AircraftVelocity {
+        subtype,
+        heading: None,
+        ground_speed: None,
+        magnetic_heading: None,
+        indicated_airspeed: None,
+        true_airspeed: None,
+        vertical_rate: match mb_field(me, 38, 9) {
+            0 => None,
+            value if mb_field(me, 37, 1) != 0 => Some(-(((value - 1) * 64) as i16)),
+            value => Some(((value - 1) * 64) as i16),
+        },
+        vertical_rate_source: if mb_field(me, 36, 1) != 0 {
+            AircraftVerticalRateSource::BarometricPressureAltitude
+        } else {
+            AircraftVerticalRateSource::GeometricAltitude
+        },
+        geo_minus_baro: match mb_field(me, 50, 7) {
+            0 => None,
+            value if mb_field(me, 49, 1) != 0 => Some(-(((value - 1) * 25) as i16)),
+            value => Some(((value - 1) * 25) as i16),
+        },
+    };
+    match subtype {
+        1 | 2 => {
+            if let (Some(east_west), Some(north_south)) = (speed(15), speed(26)) {
+                // Direction bits are set for westward and southward
+                let sign = |bit| if mb_field(me, bit, 1) != 0 { -1.0 } else { 1.0 };
+                let v_ew = east_west as f64 * sign(14);
+                let v_ns = north_south as f64 * sign(25);
+                velocity.ground_speed = Some(v_ew.hypot(v_ns));
+                velocity.heading = Some(v_ew.atan2(v_ns).to_degrees().rem_euclid(360.0));
+            }
+        }
+        3 | 4 => {
+            if mb_field(me, 14, 1) != 0 {
+                velocity.magnetic_heading = Some(mb_field(me, 15, 10) as f64 * 360.0 / 1024.0);
+            }
+            let airspeed = speed(26).map(|speed| speed as u16);
+            if mb_field(me, 25, 1) != 0 {
+                velocity.true_airspeed = airspeed;
+            } else {
+                velocity.indicated_airspeed = airspeed;
+            }
+        }
+        _ => return None,
+    }
+    Some(velocity)
+}
+
+/// Decoded fields of an emergency/priority status message (TC 28 subtype 1)
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct AircraftStatusReport {
+    pub emergency: EmergencyState,
+    pub squawk: u16,
+}
+
+/// Decode an emergency/priority status message from a DF17/DF18 frame
+///
+/// Subtype 2 (ACAS RA broadcast) is not handled here.
+pub fn aircraft_status(bytes: &[u8]) -> Option<AircraftStatusReport> {
+    let me = long_field(bytes)?;
+    if mb_field(me, 1, 5) != 28 || mb_field(me, 6, 3) != 1 {
+        return None;
+    }
+    Some(AircraftStatusReport {
+        emergency: EmergencyState::from_code(mb_field(me, 9, 3) as u8),
+        squawk: squawk_from_id13(mb_field(me, 12, 13) as u16),
+    })
+}
+
+/// Flight status and identity or altitude of a surveillance reply.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct SurveillanceReply {
+    /// Flight status (FS) field
+    pub flight_status: u8,
+    /// Squawk of an identity reply (DF5, DF21)
+    pub squawk: Option<u16>,
+    /// Altitude in feet of an altitude reply (DF4, DF20)
+    pub altitude: Option<i32>,
+}
+
+impl SurveillanceReply {
+    /// Whether the reply reports the aircraft on the ground, if it does
+    pub fn on_ground(&self) -> Option<bool> {
+        match self.flight_status {
+            0 | 2 => Some(false),
+            1 | 3 => Some(true),
+            _ => None,
+        }
+    }
+
+    /// Whether the special position identification (IDENT) is set
+    pub fn spi(&self) -> bool {
+        matches!(self.flight_status, 4 | 5)
+    }
+}
+
+/// Decode the flight status and the identity or altitude code of a DF4,
+/// DF5, DF20 or DF21 frame
+pub fn surveillance_reply(bytes: &[u8]) -> Option<SurveillanceReply> {
+    let df = downlink_format(bytes);
+    if !matches!(df, 4 | 5 | 20 | 21) {
+        return None;
+    }
+    let field = (u16::from(*bytes.get(2)? & 0x1F) << 8) | u16::from(*bytes.get(3)?);
+    let identity = matches!(df, 5 | 21);
+    Some(SurveillanceReply {
+        flight_status: bytes[0] & 0x07,
+        squawk: identity.then(|| squawk_from_id13(field)),
+        altitude: if identity { None } else { ac13_altitude(field) },
+    })
+}
+
+/// Decode a target state and status message (TC 29 subtype 1) from a DF17/DF18 frame
+///
+/// Subtype 0 (ADS-B version 1) uses a different layout and is not decoded.
+pub fn target_state(bytes: &[u8]) -> Option<AircraftNavigation> {
+    let me = long_field(bytes)?;
+    if mb_field(me, 1, 5) != 29 || mb_field(me, 6, 2) != 1 {
+        return None;
+    }
+
+    let altitude = mb_field(me, 10, 11);
+    let qnh = mb_field(me, 21, 9);
+    let heading_valid = mb_field(me, 30, 1) != 0;
+    let modes_valid = mb_field(me, 47, 1) != 0;
+    let mode = |bit: u32| modes_valid.then(|| mb_field(me, bit, 1) != 0);
+
+    Some(AircraftNavigation {
+        selected_altitude: (altitude != 0).then(|| (altitude as u32 - 1) * 32),
+        selected_altitude_source: (altitude != 0).then(|| {
+            if mb_field(me, 9, 1) == 0 {
+                SelectedAltitudeSource::McpFcu
+            } else {
+                SelectedAltitudeSource::Fms
+            }
+        }),
+        qnh: (qnh != 0).then(|| 800.0 + (qnh - 1) as f64 * 0.8),
+        // The sign bit and the 8 heading bits form a 9-bit angle
+        selected_heading: heading_valid.then(|| mb_field(me, 31, 9) as f64 * 180.0 / 256.0),
+        autopilot: mode(48),
+        vnav: mode(49),
+        altitude_hold: mode(50),
+        approach: mode(52),
+        lnav: mode(54),
+        tcas_operational: mb_field(me, 53, 1) != 0,
+    })
+}
+
+/// Decode an aircraft operational status message (TC 31) from a DF17/DF18 frame
+pub fn operational_status(bytes: &[u8]) -> Option<OperationalStatus> {
+    let me = long_field(bytes)?;
+    let subtype = mb_field(me, 6, 3);
+    if mb_field(me, 1, 5) != 31 || subtype > 1 {
+        return None;
+    }
+    let surface = subtype == 1;
+    let version = mb_field(me, 41, 3) as u8;
+    // Version 0 messages carry none of the quality indicators
+    let v1 = version >= 1;
+    let v2 = version >= 2;
+    let bit = |n: u32| mb_field(me, n, 1) != 0;
+
+    Some(OperationalStatus {
+        version,
+        surface,
+        nic_supplement_a: v1 && bit(44),
+        nic_supplement_c: (surface && v2).then(|| bit(20)),
+        nac_p: v1.then(|| mb_field(me, 45, 4) as u8),
+        nac_v: (surface && v2).then(|| mb_field(me, 17, 3) as u8),
+        gva: (!surface && v2).then(|| mb_field(me, 49, 2) as u8),
+        sil: v1.then(|| mb_field(me, 51, 2) as u8),
+        sil_supplement: v2.then(|| bit(55)),
+        sda: v2.then(|| mb_field(me, 31, 2) as u8),
+        nic_baro: (!surface && v1).then(|| bit(53)),
+    })
+}
+
+/// Get NIC supplement B from an airborne position message (TC 9-18)
+pub fn nic_supplement_b(bytes: &[u8]) -> Option<bool> {
+    let me = long_field(bytes)?;
+    (9..=18).contains(&mb_field(me, 1, 5)).then(|| mb_field(me, 8, 1) != 0)
+}
+
+/// Get the NACv of an airborne velocity message (TC 19)
+pub fn velocity_nac(bytes: &[u8]) -> Option<u8> {
+    let me = long_field(bytes)?;
+    (mb_field(me, 1, 5) == 19).then(|| mb_field(me, 11, 3) as u8)
+}
+
+/// Derive the navigation integrity category of a position message
+///
+/// The type code alone is ambiguous for some containment radii, which are
+/// told apart by the NIC supplements. Version 1 has no supplement B and uses
+/// supplement A in its place; version 0 has neither and gets the lower value.
+pub fn nic(type_code: u8, version: u8, supplement_a: bool, supplement_b: bool, supplement_c: bool) -> u8 {
+    let a = version >= 1 && supplement_a;
+    let b = if version >= 2 { supplement_b } else { a };
+    let c = version >= 2 && supplement_c;
+    match type_code {
+        5 => 11,
+        6 => 10,
+        7 => if a { 9 } else { 8 },
+        8 => match (a, c) {
+            (true, true) => 7,
+            (true, false) | (false, true) => 6,
+            (false, false) => 0,
+        },
+        9 | 20 => 11,
+        10 | 21 => 10,
+        11 => if a && b { 9 } else { 8 },
+        12 => 7,
+        13 => 6,
+        14 => 5,
+        15 => 4,
+        16 => if a && b { 3 } else { 2 },
+        17 => 1,
+        _ => 0,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn hex(s: &str) -> Vec<u8> {
+        (0..s.len())
+            .step_by(2)
+            .map(|i| u8::from_str_radix(&s[i..i + 2], 16).unwrap())
+            .collect()
+    }
+
+    #[test]
+    fn test_extended_squitter_remainder_is_zero() {
+        let frame = hex("8D406B902015A678D4D220AA4BDA");
+        assert_eq!(downlink_format(&frame), 17);
+        assert_eq!(frame_bits(17), LONG_FRAME_BITS);
+        assert_eq!(crc_remainder(&frame), 0);
+        assert_eq!(announced_address(&frame), Some(adsb_deku::ICAO([0x40, 0x6B, 0x90])));
+    }
+
+    #[test]
+    fn test_all_call_remainder_carries_iid() {
+        let frame = hex("5D484FDEA248F5");
+        assert_eq!(downlink_format(&frame), 11);
+        assert_eq!(frame_bits(11), SHORT_FRAME_BITS);
+        assert_eq!(capability(&frame), 5);
+        assert!(is_valid_all_call_remainder(crc_remainder(&frame)));
+        assert_eq!(announced_address(&frame), Some(adsb_deku::ICAO([0x48, 0x4F, 0xDE])));
+    }
+
+    #[test]
+    fn test_address_parity_remainder_is_address() {
+        let frame = hex("20001838CA3804");
+        assert!(has_address_parity(downlink_format(&frame)));
+        let address = address_from_u32(crc_remainder(&frame));
+        assert_eq!(address_to_u32(&address), crc_remainder(&frame));
+    }
+
+    #[test]
+    fn test_extended_squitter_kind() {
+        // DF18 CF=0 airborne position: ADS-B from a non-transponder device
+        let mut frame = hex("9040621D58C382D690C8AC2863A7");
+        assert_eq!(downlink_format(&frame), 18);
+        assert_eq!(
+            extended_squitter_kind(&frame),
+            Some((AddressType::Icao, AircraftSource::Adsb))
+        );
+
+        // CF=2 airborne position with IMF set: TIS-B track file number
+        frame[0] = (18 << 3) | 2;
+        assert_eq!(type_code(&frame), 11);
+        frame[4] |= 0x01;
+        assert_eq!(
+            extended_squitter_kind(&frame),
+            Some((AddressType::NonIcao, AircraftSource::Tisb))
+        );
+
+        // CF=6 is ADS-R, CF=4 (TIS-B management) is not decoded
+        frame[0] = (18 << 3) | 6;
+        assert_eq!(extended_squitter_kind(&frame).map(|k| k.1), Some(AircraftSource::Adsr));
+        frame[0] = (18 << 3) | 4;
+        assert_eq!(extended_squitter_kind(&frame), None);
+    }
+
+    #[test]
+    fn test_ac13_altitude() {
+        // 25 ft encoding from the DF4 reply above
+        assert_eq!(ac13_altitude(0x1838), Some(38000));
+        // Gillham encoding: C2 B1 B2 is 1000 ft
+        assert_eq!(ac13_altitude(0x0428), Some(1000));
+        // No altitude and metric altitudes are not decoded
+        assert_eq!(ac13_altitude(0), None);
+        assert_eq!(ac13_altitude(0x1878), None);
+    }
+
+    #[test]
+    fn test_resolution_advisory_with_address() {
+        // DF16, single threat corrective climb against 4840D6
+        let frame = hex("8000000030C20005210358000000");
+        let ra = resolution_advisory(&frame).unwrap();
+        assert_eq!(ra.ara, 0x3080);
+        assert_eq!(ra.rac, 0);
+        assert!(!ra.terminated);
+        assert!(!ra.multiple_threats);
+        assert_eq!(ra.advisories, vec!["corrective", "upward sense", "positive"]);
+        assert_eq!(
+            ra.threat,
+            Some(RaThreat::Address {
+                icao: adsb_deku::ICAO([0x48, 0x40, 0xD6])
+            })
+        );
+    }
+
+    #[test]
+    fn test_resolution_advisory_with_position() {
+        // DF20 BDS 3,0 terminated RA with a non Mode S threat
+        let frame = hex("A00000003000022B070690000000");
+        let ra = resolution_advisory(&frame).unwrap();
+        assert!(ra.terminated);
+        assert!(ra.advisories.is_empty());
+        assert_eq!(ra.complements, vec!["do not pass below"]);
+        assert_eq!(
+            ra.threat,
+            Some(RaThreat::Position {
+                altitude: Some(38000),
+                range_nm: Some(2.5),
+                bearing: Some(90),
+            })
+        );
+
+        // Other Comm-B registers and formats are ignored
+        let mut other = frame.clone();
+        other[4] = 0x20;
+        assert_eq!(resolution_advisory(&other), None);
+        assert_eq!(resolution_advisory(&hex("8D406B902015A678D4D220AA4BDA")), None);
+    }
+
+    #[test]
+    fn test_surface_position() {
+        let frame = hex("8C4841753A9A153237AEF0F275BE");
+        let report = surface_position(&frame).unwrap();
+        assert_eq!(report.type_code, 7);
+        assert_eq!(report.ground_speed, Some(17.0));
+        assert_eq!(report.track, Some(92.8125));
+        assert_eq!(
+            report.frame,
+            CprFrame {
+                lat_cpr: 39195,
+                lon_cpr: 110320,
+                odd: true,
+            }
+        );
+
+        // Airborne positions are not surface positions
+        assert_eq!(surface_position(&hex("8D40621D58C382D690C8AC2863A7")), None);
+    }
+
+    #[test]
+    fn test_airborne_velocity_ground_speed() {
+        let velocity = airborne_velocity(&hex("8D485020994409940838175B284F")).unwrap();
+        assert_eq!(velocity.subtype, 1);
+        assert!((velocity.ground_speed.unwrap() - 159.20).abs() < 0.01);
+        assert!((velocity.heading.unwrap() - 182.88).abs() < 0.01);
+        assert_eq!(velocity.vertical_rate, Some(-832));
+        assert_eq!(velocity.vertical_rate_source, AircraftVerticalRateSource::GeometricAltitude);
+        assert_eq!(velocity.geo_minus_baro, Some(550));
+        assert_eq!(velocity.true_airspeed, None);
+    }
+
+    #[test]
+    fn test_airborne_velocity_airspeed() {
+        let velocity = airborne_velocity(&hex("8DA05F219B06B6AF189400CBC33F")).unwrap();
+        assert_eq!(velocity.subtype, 3);
+        assert_eq!(velocity.magnetic_heading, Some(243.984375));
+        assert_eq!(velocity.true_airspeed, Some(375));
+        assert_eq!(velocity.indicated_airspeed, None);
+        assert_eq!(velocity.ground_speed, None);
+        assert_eq!(velocity.vertical_rate, Some(-2304));
+        assert_eq!(
+            velocity.vertical_rate_source,
+            AircraftVerticalRateSource::BarometricPressureAltitude
+        );
+        assert_eq!(velocity.geo_minus_baro, None);
+    }
+
+    #[test]
+    fn test_surveillance_reply() {
+        // DF5 with flight status 4 (alert and SPI), squawking 7700
+        let reply = surveillance_reply(&hex("2C000AAA000000")).unwrap();
+        assert_eq!(reply.squawk, Some(7700));
+        assert_eq!(reply.altitude, None);
+        assert!(reply.spi());
+        assert_eq!(reply.on_ground(), None);
+
+        // DF4 on the ground at 38000 ft
+        let reply = surveillance_reply(&hex("21001838000000")).unwrap();
+        assert_eq!(reply.altitude, Some(38000));
+        assert_eq!(reply.squawk, None);
+        assert!(!reply.spi());
+        assert_eq!(reply.on_ground(), Some(true));
+
+        assert_eq!(surveillance_reply(&hex("5D4840D6202CC3")), None);
+    }
+
+    #[test]
+    fn test_squawk_from_id13() {
+        assert_eq!(squawk_from_id13(0x0AAA), 7700);
+        assert_eq!(squawk_from_id13(0x0808), 1200);
+        assert_eq!(squawk_from_id13(0), 0);
+    }
+
+    #[test]
+    fn test_aircraft_status() {
+        // TC 28 subtype 1, general emergency squawking 7700
+        let frame = hex("8D4840D6E12AAA00000000000000");
+        let report = aircraft_status(&frame).unwrap();
+        assert_eq!(report.emergency, EmergencyState::General);
+        assert_eq!(report.squawk, 7700);
+
+        assert_eq!(aircraft_status(&hex("8C4841753A9A153237AEF0F275BE")), None);
+    }
+
+    #[test]
+    fn test_target_state() {
+        let frame = hex("8DA05629EA21485CBF3F8CADAEEB");
+        let navigation = target_state(&frame).unwrap();
+        assert_eq!(navigation.selected_altitude, Some(16992));
+        assert_eq!(navigation.selected_altitude_source, Some(SelectedAltitudeSource::McpFcu));
+        assert!((navigation.qnh.unwrap() - 1012.8).abs() < 1e-6);
+        assert!((navigation.selected_heading.unwrap() - 66.8).abs() < 0.01);
+        assert_eq!(navigation.autopilot, Some(true));
+        assert_eq!(navigation.vnav, Some(true));
+        assert_eq!(navigation.altitude_hold, Some(false));
+        assert_eq!(navigation.approach, Some(false));
+        assert_eq!(navigation.lnav, Some(true));
+        assert!(navigation.tcas_operational);
+    }
+
+    #[test]
+    fn test_operational_status() {
+        // Airborne, version 2, NIC-A set, NACp 10, GVA 2, SIL 3, NICbaro, SDA 2
+        let frame = hex("8D4840D6F8000002005AB8000000");
+        let status = operational_status(&frame).unwrap();
+        assert_eq!(status.version, 2);
+        assert!(!status.surface);
+        assert!(status.nic_supplement_a);
+        assert_eq!(status.nic_supplement_c, None);
+        assert_eq!(status.nac_p, Some(10));
+        assert_eq!(status.gva, Some(2));
+        assert_eq!(status.sil, Some(3));
+        assert_eq!(status.sil_supplement, Some(false));
+        assert_eq!(status.sda, Some(2));
+        assert_eq!(status.nic_baro, Some(true));
+    }
+
+    #[test]
+    fn test_nic() {
+        assert_eq!(nic(9, 2, false, false, false), 11);
+        // TC 11 needs both supplements in version 2 for the smaller radius
+        assert_eq!(nic(11, 2, true, true, false), 9);
+        assert_eq!(nic(11, 2, true, false, false), 8);
+        // Version 1 has no supplement B
+        assert_eq!(nic(11, 1, true, false, false), 9);
+        // Version 0 has no supplements
+        assert_eq!(nic(16, 0, true, true, false), 2);
+        assert_eq!(nic(8, 2, true, false, false), 6);
+        assert_eq!(nic(18, 2, false, false, false), 0);
+    }
+}
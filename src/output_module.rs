@@ -5,10 +5,14 @@
 //! without requiring code changes when adding new modules.
 
 use crate::decoder::DecoderMetaData;
-use crate::{AdsbIcao, AircraftRecord};
+use crate::{AircraftAddress, AircraftRecord, TrackerEvent};
 use anyhow::Result;
 use async_trait::async_trait;
 use std::collections::HashMap;
+use tokio::io::AsyncWriteExt;
+use tokio::net::{TcpListener, TcpStream};
+use tokio::sync::broadcast;
+use tracing::{debug, error, info, warn};
 
 /// Configuration for an output module
 #[derive(Debug, Clone)]
@@ -105,6 +109,11 @@ pub trait OutputModuleBase: Send + Sync {
 pub trait RawOutputModule: OutputModuleBase {
     /// Broadcast a raw ADS-B packet to all connected clients
     fn broadcast_raw_packet(&self, data: &[u8], metadata: &DecoderMetaData) -> Result<()>;
+
+    /// Whether duplicate frames should be relayed as well (for MLAT consumers)
+    fn keeps_duplicates(&self) -> bool {
+        false
+    }
 }
 
 /// Trait for output modules that require decoded aircraft state
@@ -114,7 +123,107 @@ pub trait RawOutputModule: OutputModuleBase {
 #[async_trait]
 pub trait StateOutputModule: OutputModuleBase {
     /// Broadcast an aircraft state update to all connected clients
-    fn broadcast_aircraft_update(&self, icao: &AdsbIcao, record: &AircraftRecord) -> Result<()>;
+    fn broadcast_aircraft_update(&self, address: &AircraftAddress, record: &AircraftRecord) -> Result<()>;
+
+    /// Broadcast that an all-call reply (DF11) was received from an aircraft
+    fn broadcast_all_call(&self, _address: &AircraftAddress, _record: &AircraftRecord) -> Result<()> {
+        Ok(())
+    }
+
+    /// Broadcast a tracker event (such as an ACAS resolution advisory)
+    fn broadcast_event(&self, _event: &TrackerEvent) -> Result<()> {
+        Ok(())
+    }
+}
+
+/// A message streamed to TCP clients as a line of text
+pub trait LineMessage: Clone + Send + 'static {
+    /// Name of the format in log messages
+    const FORMAT: &'static str;
+
+    /// Encode the message as it is written to clients, with the line ending
+    fn encode(&self) -> String;
+}
+
+/// TCP server streaming line-oriented messages to all clients
+pub struct LineServer<M> {
+    listener: TcpListener,
+    receiver: broadcast::Receiver<M>,
+}
+
+impl<M: LineMessage> LineServer<M> {
+    /// Create a new server listening on the specified port
+    pub async fn new(port: u16, receiver: broadcast::Receiver<M>) -> Result<Self> {
+        let addr = format!("0.0.0.0:{}", port);
+        let listener = TcpListener::bind(&addr).await?;
+        info!("{} server listening on {}", M::FORMAT, addr);
+
+        Ok(Self { listener, receiver })
+    }
+
+    /// Run the server, accepting connections and streaming messages
+    pub async fn run(self) -> Result<()> {
+        loop {
+            match self.listener.accept().await {
+                Ok((stream, addr)) => {
+                    info!("{} client connected from {}", M::FORMAT, addr);
+                    let mut receiver = self.receiver.resubscribe();
+
+                    tokio::spawn(async move {
+                        if let Err(e) = Self::handle_client(stream, &mut receiver).await {
+                            debug!("{} client {} disconnected: {}", M::FORMAT, addr, e);
+                        }
+                    });
+                }
+                Err(e) => {
+                    error!("Failed to accept {} connection: {}", M::FORMAT, e);
+                }
+            }
+        }
+    }
+
+    /// Handle a single client connection
+    async fn handle_client(mut stream: TcpStream, receiver: &mut broadcast::Receiver<M>) -> Result<()> {
+        loop {
+            match receiver.recv().await {
+                Ok(message) => stream.write_all(message.encode().as_bytes()).await?,
+                Err(broadcast::error::RecvError::Lagged(skipped)) => {
+                    warn!("{} client lagged, skipped {} messages", M::FORMAT, skipped);
+                }
+                Err(broadcast::error::RecvError::Closed) => {
+                    debug!("{} message channel closed", M::FORMAT);
+                    return Ok(());
+                }
+            }
+        }
+    }
+}
+
+/// Broadcaster of line-oriented messages to the clients of a `LineServer`
+pub struct LineBroadcaster<M> {
+    sender: broadcast::Sender<M>,
+}
+
+impl<M: LineMessage> LineBroadcaster<M> {
+    /// Create a new broadcaster with the specified channel capacity
+    pub fn new(capacity: usize) -> (Self, broadcast::Receiver<M>) {
+        let (sender, receiver) = broadcast::channel(capacity);
+        (Self { sender }, receiver)
+    }
+
+    /// Broadcast a message
+    pub fn broadcast_message(&self, message: M) -> Result<()> {
+        // No receivers, which is fine
+        if let Ok(receiver_count) = self.sender.send(message) {
+            debug!("Broadcasted {} message to {} clients", M::FORMAT, receiver_count);
+        }
+        Ok(())
+    }
+
+    /// Get the number of active clients
+    pub fn client_count(&self) -> usize {
+        self.sender.receiver_count()
+    }
 }
 
 /// Unified wrapper enum for managing both raw and state-based output modules
@@ -285,8 +394,13 @@ impl OutputModuleManager {
     }
 
     /// Broadcast raw ADS-B packet bytes to all raw output modules
+    ///
+    /// Duplicate frames only go to the modules that keep them.
     pub fn broadcast_raw(&self, data: &[u8], metadata: &DecoderMetaData) {
         for module in &self.raw_modules {
+            if metadata.duplicate && !module.keeps_duplicates() {
+                continue;
+            }
             if let Err(e) = module.broadcast_raw_packet(data, metadata) {
                 tracing::warn!("Failed to broadcast raw packet to module '{}': {}", module.name(), e);
             }
@@ -294,20 +408,40 @@ impl OutputModuleManager {
     }
 
     /// Broadcast aircraft state update to all state-based output modules
-    pub fn broadcast_state(&self, icao: &AdsbIcao, record: &AircraftRecord) {
+    pub fn broadcast_state(&self, address: &AircraftAddress, record: &AircraftRecord) {
         for module in &self.state_modules {
-            if let Err(e) = module.broadcast_aircraft_update(icao, record) {
+            if let Err(e) = module.broadcast_aircraft_update(address, record) {
                 tracing::warn!("Failed to broadcast state update to module '{}': {}", module.name(), e);
             }
         }
     }
 
+    /// Broadcast an all-call reply to all state-based output modules
+    pub fn broadcast_all_call(&self, address: &AircraftAddress, record: &AircraftRecord) {
+        for module in &self.state_modules {
+            if let Err(e) = module.broadcast_all_call(address, record) {
+                tracing::warn!("Failed to broadcast all-call reply to module '{}': {}", module.name(), e);
+            }
+        }
+    }
+
+    /// Broadcast a tracker event to all state-based output modules
+    pub fn broadcast_event(&self, event: &TrackerEvent) {
+        for module in &self.state_modules {
+            if let Err(e) = module.broadcast_event(event) {
+                tracing::warn!("Failed to broadcast event to module '{}': {}", module.name(), e);
+            }
+        }
+    }
+
     /// Broadcast a packet to all active modules (legacy method for backward compatibility)
     pub fn broadcast_to_all(&self, data: &[u8], metadata: &DecoderMetaData) {
-        // Broadcast to legacy modules
-        for module in &self.modules {
-            if let Err(e) = module.broadcast_packet(data, metadata) {
-                tracing::warn!("Failed to broadcast to module '{}': {}", module.name(), e);
+        // Broadcast to legacy modules, which never get duplicates
+        if !metadata.duplicate {
+            for module in &self.modules {
+                if let Err(e) = module.broadcast_packet(data, metadata) {
+                    tracing::warn!("Failed to broadcast to module '{}': {}", module.name(), e);
+                }
             }
         }
 
@@ -390,4 +524,78 @@ impl Default for OutputModuleManager {
     fn default() -> Self {
         Self::new()
     }
-}
\ No newline at end of file
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::sync::Arc;
+    use std::sync::atomic::{AtomicUsize, Ordering};
+    use std::time::SystemTime;
+
+    /// A raw output module that counts the frames it is given
+    struct CountingModule {
+        keeps_duplicates: bool,
+        frames: Arc<AtomicUsize>,
+    }
+
+    impl OutputModuleBase for CountingModule {
+        fn name(&self) -> &str {
+            "counting"
+        }
+
+        fn description(&self) -> &str {
+            "Counts frames"
+        }
+
+        fn port(&self) -> u16 {
+            0
+        }
+
+        fn client_count(&self) -> usize {
+            0
+        }
+
+        fn is_running(&self) -> bool {
+            true
+        }
+
+        fn stop(&mut self) -> Result<()> {
+            Ok(())
+        }
+    }
+
+    impl RawOutputModule for CountingModule {
+        fn broadcast_raw_packet(&self, _data: &[u8], _metadata: &DecoderMetaData) -> Result<()> {
+            self.frames.fetch_add(1, Ordering::Relaxed);
+            Ok(())
+        }
+
+        fn keeps_duplicates(&self) -> bool {
+            self.keeps_duplicates
+        }
+    }
+
+    #[test]
+    fn test_duplicates_reach_only_modules_that_keep_them() {
+        let mut manager = OutputModuleManager::new();
+        let (kept, dropped) = (Arc::new(AtomicUsize::new(0)), Arc::new(AtomicUsize::new(0)));
+        manager.add_raw_module(Box::new(CountingModule {
+            keeps_duplicates: true,
+            frames: kept.clone(),
+        }));
+        manager.add_raw_module(Box::new(CountingModule {
+            keeps_duplicates: false,
+            frames: dropped.clone(),
+        }));
+
+        let mut metadata = DecoderMetaData::synthetic(SystemTime::now());
+        let frame = [0x8d, 0x48, 0x40, 0xd6];
+        manager.broadcast_to_all(&frame, &metadata);
+        metadata.duplicate = true;
+        manager.broadcast_to_all(&frame, &metadata);
+
+        assert_eq!(kept.load(Ordering::Relaxed), 2);
+        assert_eq!(dropped.load(Ordering::Relaxed), 1);
+    }
+}
@@ -0,0 +1,174 @@
+//! Quarantine for frames that pass the CRC but cannot be parsed
+//!
+//! Such frames point at gaps or bugs in adsb_deku. Instead of only logging
+//! them, the decoder appends them to a JSON lines file so they can be reported
+//! upstream. The file is rotated once it exceeds its size limit, keeping a
+//! bounded number of older files (`quarantine.jsonl.1`, `quarantine.jsonl.2`, ...).
+//!
+//! ## JSON Format
+//! ```json
+//! {"hex":"8d4840d6...","df":17,"timestamp":{...},"error":"..."}
+//! ```
+
+use serde::Serialize;
+use std::collections::VecDeque;
+use std::fs::{self, File, OpenOptions};
+use std::io::{self, Write};
+use std::path::PathBuf;
+use std::time::SystemTime;
+
+use crate::mode_s;
+
+/// Number of entries kept in memory for the control port
+const MAX_RECENT: usize = 100;
+
+/// Where and how much to quarantine
+#[derive(Debug, Clone)]
+pub struct QuarantineConfig {
+    /// Path of the current quarantine file
+    pub path: PathBuf,
+    /// Size in bytes after which the file is rotated
+    pub max_bytes: u64,
+    /// Number of rotated files to keep
+    pub max_files: usize,
+}
+
+impl Default for QuarantineConfig {
+    fn default() -> Self {
+        Self {
+            path: PathBuf::from("quarantine.jsonl"),
+            max_bytes: 10 * 1024 * 1024,
+            max_files: 5,
+        }
+    }
+}
+
+/// A frame that passed the CRC but could not be parsed
+#[derive(Serialize, Clone, Debug)]
+pub struct QuarantineEntry {
+    /// The frame bytes as lowercase hex
+    pub hex: String,
+    /// Downlink format of the frame
+    pub df: u8,
+    pub timestamp: SystemTime,
+    /// The parse error reported by adsb_deku
+    pub error: String,
+}
+
+/// A rotating JSON lines file of quarantined frames
+pub struct Quarantine {
+    config: QuarantineConfig,
+    file: File,
+    /// Bytes written to the current file
+    size: u64,
+    /// Most recent entries, oldest first
+    recent: VecDeque<QuarantineEntry>,
+}
+
+impl Quarantine {
+    /// Open the quarantine file, appending to it if it already exists
+    pub fn new(config: QuarantineConfig) -> io::Result<Self> {
+        let file = Self::open(&config)?;
+        let size = file.metadata()?.len();
+        Ok(Self {
+            config,
+            file,
+            size,
+            recent: VecDeque::new(),
+        })
+    }
+
+    fn open(config: &QuarantineConfig) -> io::Result<File> {
+        OpenOptions::new().create(true).append(true).open(&config.path)
+    }
+
+    /// Path of the n-th rotated file
+    fn rotated_path(&self, n: usize) -> PathBuf {
+        let mut path = self.config.path.clone().into_os_string();
+        path.push(format!(".{}", n));
+        path.into()
+    }
+
+    /// Shift the rotated files by one and start a new current file
+    fn rotate(&mut self) -> io::Result<()> {
+        if self.config.max_files == 0 {
+            fs::remove_file(&self.config.path)?;
+        } else {
+            for n in (1..self.config.max_files).rev() {
+                let from = self.rotated_path(n);
+                if from.exists() {
+                    fs::rename(&from, self.rotated_path(n + 1))?;
+                }
+            }
+            fs::rename(&self.config.path, self.rotated_path(1))?;
+        }
+        self.file = Self::open(&self.config)?;
+        self.size = 0;
+        Ok(())
+    }
+
+    /// Quarantine a frame together with its parse error
+    pub fn record(&mut self, bytes: &[u8], error: &str) -> io::Result<()> {
+        let entry = QuarantineEntry {
+            hex: bytes.iter().map(|b| format!("{:02x}", b)).collect(),
+            df: mode_s::downlink_format(bytes),
+            timestamp: SystemTime::now(),
+            error: error.to_string(),
+        };
+
+        if self.recent.len() == MAX_RECENT {
+            self.recent.pop_front();
+        }
+        self.recent.push_back(entry.clone());
+
+        let mut line = serde_json::to_string(&entry)?;
+        line.push('\n');
+        if self.size > 0 && self.size + line.len() as u64 > self.config.max_bytes {
+            self.rotate()?;
+        }
+        self.file.write_all(line.as_bytes())?;
+        self.size += line.len() as u64;
+        Ok(())
+    }
+
+    /// The most recent quarantined frames, oldest first
+    pub fn recent(&self) -> &VecDeque<QuarantineEntry> {
+        &self.recent
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_quarantine_rotation() {
+        let dir = std::env::temp_dir().join(format!("airjedi-quarantine-{}", std::process::id()));
+        fs::create_dir_all(&dir).unwrap();
+        let config = QuarantineConfig {
+            path: dir.join("quarantine.jsonl"),
+            max_bytes: 200,
+            max_files: 2,
+        };
+        let mut quarantine = Quarantine::new(config.clone()).unwrap();
+        let frame = [0x8D, 0x48, 0x40, 0xD6, 0x20, 0x2C, 0xC3, 0x71, 0xC3, 0x2C, 0xE0, 0x57, 0x60, 0x98];
+        for _ in 0..10 {
+            quarantine.record(&frame, "test error").unwrap();
+        }
+
+        assert_eq!(quarantine.recent().len(), 10);
+        assert_eq!(quarantine.recent()[0].df, 17);
+        assert_eq!(quarantine.recent()[0].hex, "8d4840d6202cc371c32ce0576098");
+
+        // Every file stays within its limit and only two rotated files are kept
+        for path in [config.path.clone(), dir.join("quarantine.jsonl.1"), dir.join("quarantine.jsonl.2")] {
+            let content = fs::read_to_string(&path).unwrap();
+            assert!(content.len() <= 200);
+            let entry: serde_json::Value = serde_json::from_str(content.lines().next().unwrap()).unwrap();
+            assert_eq!(entry["error"], "test error");
+        }
+        assert!(!dir.join("quarantine.jsonl.3").exists());
+
+        fs::remove_dir_all(&dir).unwrap();
+    }
+}
@@ -0,0 +1,124 @@
+//! Filtered aircraft queries
+//!
+//! The control port `query` command returns the aircraft matching a set of
+//! filters, written like a URL query string:
+//! ```text
+//! query country=United Kingdom&military=true&min_altitude=10000
+//! ```
+//! Filters that are not given match every aircraft. The callsign filter
+//! takes `*` and `?` wildcards.
+
+use crate::AircraftRecord;
+use crate::watchlist::glob_matches;
+use anyhow::{Result, anyhow, bail};
+
+/// Filters of an aircraft query
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct AircraftQuery {
+    /// Country of registration, case insensitive
+    pub country: Option<String>,
+    pub military: Option<bool>,
+    /// Callsign pattern
+    pub callsign: Option<String>,
+    pub on_ground: Option<bool>,
+    /// Lowest altitude in feet
+    pub min_altitude: Option<i32>,
+    /// Highest altitude in feet
+    pub max_altitude: Option<i32>,
+    /// Only aircraft on the watchlist
+    pub watchlist: Option<bool>,
+}
+
+impl AircraftQuery {
+    /// Parse filters written as `key=value` pairs separated by `&`
+    pub fn parse(query: &str) -> Result<Self> {
+        let mut filters = Self::default();
+        for filter in query.split('&').map(str::trim).filter(|filter| !filter.is_empty()) {
+            let (key, value) = filter
+                .split_once('=')
+                .ok_or_else(|| anyhow!("Expected key=value: {}", filter))?;
+            let value = value.trim();
+            let flag = || value.parse::<bool>().map_err(|_| anyhow!("Invalid {}: {}", key, value));
+            let altitude = || value.parse::<i32>().map_err(|_| anyhow!("Invalid {}: {}", key, value));
+            match key.trim() {
+                "country" => filters.country = Some(value.to_string()),
+                "military" => filters.military = Some(flag()?),
+                "callsign" => filters.callsign = Some(value.to_uppercase()),
+                "on_ground" => filters.on_ground = Some(flag()?),
+                "min_altitude" => filters.min_altitude = Some(altitude()?),
+                "max_altitude" => filters.max_altitude = Some(altitude()?),
+                "watchlist" => filters.watchlist = Some(flag()?),
+                _ => bail!("Unknown filter: {}", key),
+            }
+        }
+        Ok(filters)
+    }
+
+    /// Whether an aircraft matches all filters
+    ///
+    /// Aircraft without a known altitude do not match altitude filters.
+    pub fn matches(&self, rec: &AircraftRecord) -> bool {
+        let altitude = rec
+            .positions
+            .last()
+            .and_then(|p| p.position.altitude.map(i32::from))
+            .or(rec.geometric_altitude);
+        let in_band = |limit: Option<i32>, inside: fn(i32, i32) -> bool| {
+            limit.is_none_or(|limit| altitude.is_some_and(|altitude| inside(altitude, limit)))
+        };
+        let country = self.country.as_deref().is_none_or(|country| {
+            rec.country.is_some_and(|registered| registered.eq_ignore_ascii_case(country))
+        });
+        let callsign = self.callsign.as_deref().is_none_or(|pattern| {
+            rec.callsign
+                .as_deref()
+                .is_some_and(|callsign| glob_matches(pattern.as_bytes(), callsign.trim().as_bytes()))
+        });
+        country
+            && callsign
+            && self.military.is_none_or(|military| rec.military == military)
+            && self.on_ground.is_none_or(|on_ground| rec.on_ground == Some(on_ground))
+            && in_band(self.min_altitude, |altitude, min| altitude >= min)
+            && in_band(self.max_altitude, |altitude, max| altitude <= max)
+            && self.watchlist.is_none_or(|watched| rec.watchlist.is_some() == watched)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::AircraftAddress;
+    use std::time::SystemTime;
+
+    #[test]
+    fn test_parse_query() {
+        let query = AircraftQuery::parse("country=United Kingdom&military=true&callsign=rch*&min_altitude=10000").unwrap();
+        assert_eq!(query.country.as_deref(), Some("United Kingdom"));
+        assert_eq!(query.military, Some(true));
+        assert_eq!(query.callsign.as_deref(), Some("RCH*"));
+        assert_eq!(query.min_altitude, Some(10000));
+        assert_eq!(AircraftQuery::parse("").unwrap(), AircraftQuery::default());
+
+        assert!(AircraftQuery::parse("military=yes").is_err());
+        assert!(AircraftQuery::parse("registration=PH-BXA").is_err());
+        assert!(AircraftQuery::parse("military").is_err());
+    }
+
+    #[test]
+    fn test_query_matches() {
+        let address = AircraftAddress::icao(adsb_deku::ICAO([0x43, 0xc1, 0x23]));
+        let mut rec = AircraftRecord::new(&address, SystemTime::now());
+        rec.callsign = Some("RRR7201 ".to_string());
+        rec.geometric_altitude = Some(24000);
+
+        let matches = |query: &str| AircraftQuery::parse(query).unwrap().matches(&rec);
+        assert!(matches(""));
+        assert!(matches("country=united kingdom&military=true"));
+        assert!(matches("callsign=RRR*&min_altitude=20000&max_altitude=30000"));
+        assert!(!matches("country=Netherlands"));
+        assert!(!matches("military=false"));
+        assert!(!matches("max_altitude=10000"));
+        assert!(!matches("on_ground=false"));
+        assert!(!matches("watchlist=true"));
+    }
+}
@@ -33,6 +33,9 @@ pub enum UpdateType {
     Velocity,
     Identification,
     Metadata,
+    /// All-call replies, limited at the metadata interval but separately
+    /// from the other metadata so that they are not starved by it
+    AllCall,
 }
 
 impl UpdateType {
@@ -42,7 +45,7 @@ impl UpdateType {
             UpdateType::Position => config.position_interval,
             UpdateType::Velocity => config.velocity_interval,
             UpdateType::Identification => config.identification_interval,
-            UpdateType::Metadata => config.metadata_interval,
+            UpdateType::Metadata | UpdateType::AllCall => config.metadata_interval,
         }
     }
 }
@@ -290,6 +293,11 @@ mod tests {
 
         // But identification should still be allowed (immediate)
         assert!(tracker.is_update_allowed(UpdateType::Identification, &config));
+
+        // All-call replies are limited separately from the other metadata
+        tracker.record_update(UpdateType::Metadata);
+        assert!(!tracker.is_update_allowed(UpdateType::Metadata, &config));
+        assert!(tracker.is_update_allowed(UpdateType::AllCall, &config));
     }
 
     #[test]
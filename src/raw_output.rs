@@ -142,12 +142,15 @@ pub struct RawOutput {
     port: u16,
     broadcaster: RawBroadcaster,
     is_running: bool,
+    /// Also relay duplicate frames (`keep_duplicates` extra option), for MLAT
+    keep_duplicates: bool,
 }
 
 impl RawOutput {
     /// Create a new Raw output module
     pub async fn new(config: crate::output_module::OutputModuleConfig) -> Result<Self> {
         let (broadcaster, receiver) = RawBroadcaster::new(config.buffer_capacity);
+        let keep_duplicates = config.extra.get("keep_duplicates").is_some_and(|v| v == "true");
         
         // Start the server
         let server = RawServer::new(config.port, receiver).await?;
@@ -162,6 +165,7 @@ impl RawOutput {
             port: config.port,
             broadcaster,
             is_running: true,
+            keep_duplicates,
         })
     }
 }
@@ -200,6 +204,10 @@ impl crate::output_module::RawOutputModule for RawOutput {
     fn broadcast_raw_packet(&self, data: &[u8], metadata: &DecoderMetaData) -> Result<()> {
         self.broadcaster.broadcast_packet(data, metadata)
     }
+
+    fn keeps_duplicates(&self) -> bool {
+        self.keep_duplicates
+    }
 }
 
 // Keep legacy trait implementation for backward compatibility during migration
@@ -248,6 +256,7 @@ mod tests {
             preamble_index: 12345,
             preamble_correlation: 15.5,
             crc_passed: true,
+            duplicate: false,
             timestamp: SystemTime::now(),
         };
         
@@ -265,6 +274,7 @@ mod tests {
             preamble_index: 0,
             preamble_correlation: 25.0,
             crc_passed: true,
+            duplicate: false,
             timestamp: SystemTime::now(),
         };
         
@@ -282,6 +292,7 @@ mod tests {
             preamble_index: 999999,
             preamble_correlation: 0.0,
             crc_passed: false,
+            duplicate: false,
             timestamp: SystemTime::now(),
         };
         
@@ -0,0 +1,275 @@
+//! Retention of the position and velocity history of aircraft
+//!
+//! Without limits, the history of an aircraft grows for as long as it is
+//! tracked. The retention policy bounds it by number of points and by age,
+//! and can thin the position trail with the Douglas-Peucker algorithm, which
+//! drops points that deviate less than a tolerance from the simplified line.
+//! A trail is simplified once: each pass only simplifies the positions after
+//! the last final point, and keeps the positions after the last turn until
+//! the trail goes on, so the error stays within the tolerance.
+
+use crate::{AircraftPositionRecord, AircraftRecord};
+use std::time::{Duration, SystemTime};
+
+/// Limits on the history kept per aircraft
+#[derive(Debug, Clone, Default)]
+pub struct RetentionConfig {
+    /// Maximum number of positions and velocities kept
+    pub max_points: Option<usize>,
+    /// Maximum age of positions and velocities
+    pub max_age: Option<Duration>,
+    /// Simplify the position trail, keeping points that deviate more than
+    /// this many nautical miles
+    pub simplify_tolerance_nm: Option<f64>,
+}
+
+impl RetentionConfig {
+    /// Whether any limit is configured
+    pub fn is_enabled(&self) -> bool {
+        self.max_points.is_some() || self.max_age.is_some() || self.simplify_tolerance_nm.is_some()
+    }
+
+    /// Apply the limits to the history of an aircraft
+    pub fn apply(&self, rec: &mut AircraftRecord, now: SystemTime) {
+        if let Some(max_age) = self.max_age {
+            let expired = |time: SystemTime| time + max_age < now;
+            let n = rec.positions.partition_point(|p| expired(p.time));
+            rec.positions.drain(..n);
+            let n = rec.velocities.partition_point(|v| expired(v.time));
+            rec.velocities.drain(..n);
+        }
+        if let Some(tolerance) = self.simplify_tolerance_nm {
+            simplify_trail(rec, tolerance);
+        }
+        if let Some(max_points) = self.max_points {
+            let n = rec.positions.len().saturating_sub(max_points);
+            rec.positions.drain(..n);
+            let n = rec.velocities.len().saturating_sub(max_points);
+            rec.velocities.drain(..n);
+        }
+    }
+}
+
+/// Simplify the positions of a trail after the last final point
+///
+/// The points up to the second last kept one are final. The positions after
+/// it are kept as they are, a straight line may still go on and drop them.
+fn simplify_trail(rec: &mut AircraftRecord, tolerance_nm: f64) {
+    let start = rec
+        .simplified_until
+        .map_or(0, |until| rec.positions.partition_point(|p| p.time < until));
+    let mut keep = douglas_peucker(&rec.positions[start..], tolerance_nm);
+    let kept: Vec<usize> = keep.iter().enumerate().filter(|(_, keep)| **keep).map(|(i, _)| i).collect();
+    let [.., settled, _] = kept[..] else {
+        return;
+    };
+    keep[settled..].fill(true);
+    rec.simplified_until = Some(rec.positions[start + settled].time);
+
+    let tail: Vec<AircraftPositionRecord> = rec
+        .positions
+        .drain(start..)
+        .zip(keep)
+        .filter(|(_, keep)| *keep)
+        .map(|(p, _)| p)
+        .collect();
+    rec.positions.extend(tail);
+}
+
+/// Which positions of a trail the Douglas-Peucker algorithm keeps
+///
+/// The first and last positions are always kept.
+fn douglas_peucker(positions: &[AircraftPositionRecord], tolerance_nm: f64) -> Vec<bool> {
+    if positions.len() < 3 {
+        return vec![true; positions.len()];
+    }
+
+    // Project onto a plane in nautical miles around the first position
+    let cos_lat = positions[0].position.latitude.to_radians().cos();
+    let points: Vec<(f64, f64)> = positions
+        .iter()
+        .map(|p| (p.position.longitude * 60.0 * cos_lat, p.position.latitude * 60.0))
+        .collect();
+
+    let mut keep = vec![false; points.len()];
+    keep[0] = true;
+    keep[points.len() - 1] = true;
+    let mut segments = vec![(0, points.len() - 1)];
+    while let Some((first, last)) = segments.pop() {
+        let farthest = (first + 1..last)
+            .map(|i| (i, segment_distance(points[i], points[first], points[last])))
+            .max_by(|a, b| a.1.total_cmp(&b.1))
+            .filter(|(_, distance)| *distance > tolerance_nm);
+        if let Some((i, _)) = farthest {
+            keep[i] = true;
+            segments.push((first, i));
+            segments.push((i, last));
+        }
+    }
+    keep
+}
+
+/// Distance from a point to the segment between `a` and `b`
+fn segment_distance(p: (f64, f64), a: (f64, f64), b: (f64, f64)) -> f64 {
+    let (dx, dy) = (b.0 - a.0, b.1 - a.1);
+    let length_squared = dx * dx + dy * dy;
+    let t = if length_squared == 0.0 {
+        0.0
+    } else {
+        (((p.0 - a.0) * dx + (p.1 - a.1) * dy) / length_squared).clamp(0.0, 1.0)
+    };
+    let (x, y) = (a.0 + t * dx, a.1 + t * dy);
+    (p.0 - x).hypot(p.1 - y)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        AircraftAddress, AircraftPosition, AircraftVelocity, AircraftVelocityRecord, AircraftVerticalRateSource,
+        PositionQuality,
+    };
+
+    fn position(latitude: f64, longitude: f64, time: SystemTime) -> AircraftPositionRecord {
+        AircraftPositionRecord {
+            position: AircraftPosition {
+                latitude,
+                longitude,
+                altitude: None,
+                type_code: 11,
+                ground_speed: None,
+                track: None,
+                quality: PositionQuality::default(),
+            },
+            time,
+            phase: None,
+        }
+    }
+
+    #[test]
+    fn test_douglas_peucker() {
+        let now = SystemTime::now();
+        // A straight line north with a small wiggle, then a turn east
+        let trail = vec![
+            position(52.0, 4.0, now),
+            position(52.1, 4.0001, now),
+            position(52.2, 4.0, now),
+            position(52.3, 4.0, now),
+            position(52.3, 4.2, now),
+        ];
+        assert_eq!(douglas_peucker(&trail, 0.1), vec![true, false, false, true, true]);
+
+        // The wiggle is kept with a tolerance below it
+        assert_eq!(douglas_peucker(&trail, 0.002), vec![true, true, false, true, true]);
+    }
+
+    fn record(now: SystemTime, count: u64) -> AircraftRecord {
+        let address = AircraftAddress::icao(adsb_deku::ICAO([0x48, 0x40, 0xd6]));
+        let mut rec = AircraftRecord::new(&address, now);
+        for i in 0..count {
+            let time = now - Duration::from_secs(count - 1 - i);
+            rec.positions.push(position(52.0 + i as f64 * 0.01, 4.0 + (i % 2) as f64 * 0.01, time));
+            rec.velocities.push(AircraftVelocityRecord {
+                velocity: AircraftVelocity {
+                    subtype: 1,
+                    heading: Some(0.0),
+                    ground_speed: Some(300.0),
+                    magnetic_heading: None,
+                    indicated_airspeed: None,
+                    true_airspeed: None,
+                    vertical_rate: None,
+                    vertical_rate_source: AircraftVerticalRateSource::BarometricPressureAltitude,
+                    geo_minus_baro: None,
+                },
+                time,
+            });
+        }
+        rec
+    }
+
+    #[test]
+    fn test_apply_limits() {
+        let now = SystemTime::now();
+        let max_age = RetentionConfig {
+            max_age: Some(Duration::from_secs(4)),
+            ..Default::default()
+        };
+        let mut rec = record(now, 10);
+        max_age.apply(&mut rec, now);
+        assert_eq!(rec.positions.len(), 5);
+        assert_eq!(rec.velocities.len(), 5);
+        assert_eq!(rec.positions[0].time, now - Duration::from_secs(4));
+
+        let max_points = RetentionConfig {
+            max_points: Some(3),
+            ..Default::default()
+        };
+        let mut rec = record(now, 10);
+        max_points.apply(&mut rec, now);
+        assert_eq!(rec.positions.len(), 3);
+        assert_eq!(rec.velocities.len(), 3);
+        assert_eq!(rec.positions[2].time, now);
+
+        // Both limits, the stricter one applies
+        let both = RetentionConfig {
+            max_age: Some(Duration::from_secs(4)),
+            max_points: Some(8),
+            ..Default::default()
+        };
+        let mut rec = record(now, 10);
+        both.apply(&mut rec, now);
+        assert_eq!(rec.positions.len(), 5);
+        let both = RetentionConfig {
+            max_points: Some(2),
+            ..both
+        };
+        both.apply(&mut rec, now);
+        assert_eq!(rec.positions.len(), 2);
+        assert_eq!(rec.velocities.len(), 2);
+    }
+
+    #[test]
+    fn test_apply_simplifies_once() {
+        let now = SystemTime::now();
+        let config = RetentionConfig {
+            simplify_tolerance_nm: Some(0.1),
+            ..Default::default()
+        };
+        let mut rec = AircraftRecord::new(&AircraftAddress::icao(adsb_deku::ICAO([0x48, 0x40, 0xd6])), now);
+        // A slow curve, each point deviates less than the tolerance from the
+        // line through its neighbours, but the curve as a whole does not
+        let trail: Vec<AircraftPositionRecord> = (0..40)
+            .map(|i| {
+                let angle = (i as f64).to_radians();
+                position(52.0 + angle.sin() * 0.5, 4.0 + (1.0 - angle.cos()) * 0.8, now + Duration::from_secs(i))
+            })
+            .collect();
+        for p in &trail {
+            rec.positions.push(p.clone());
+            config.apply(&mut rec, p.time);
+        }
+        assert!(rec.positions.len() < trail.len());
+        assert_eq!(rec.positions.first().unwrap().time, trail[0].time);
+        assert_eq!(rec.positions.last().unwrap().time, trail[39].time);
+
+        // Every dropped point is within the tolerance of the simplified trail
+        let cos_lat = 52.0_f64.to_radians().cos();
+        let project = |p: &AircraftPositionRecord| (p.position.longitude * 60.0 * cos_lat, p.position.latitude * 60.0);
+        for p in &trail {
+            let distance = rec
+                .positions
+                .windows(2)
+                .filter(|w| w[0].time <= p.time && p.time <= w[1].time)
+                .map(|w| segment_distance(project(p), project(&w[0]), project(&w[1])))
+                .fold(f64::INFINITY, f64::min);
+            assert!(distance < 0.11, "{} NM off the trail", distance);
+        }
+    }
+
+    #[test]
+    fn test_segment_distance() {
+        assert_eq!(segment_distance((0.0, 1.0), (-1.0, 0.0), (1.0, 0.0)), 1.0);
+        // Beyond the end of the segment, the distance is to the end point
+        assert_eq!(segment_distance((4.0, 4.0), (0.0, 0.0), (1.0, 0.0)), 5.0);
+    }
+}
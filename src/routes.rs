@@ -0,0 +1,233 @@
+//! Callsign routes and airlines
+//!
+//! When an aircraft reports its callsign, the tracker adds the airline and the
+//! route of the flight from two local files. The routes file maps callsigns
+//! to airports, either as separate origin and destination columns or as a
+//! route with the airports separated by `-`, like the `routes.csv` of the
+//! Virtual Radar Server standing data:
+//! ```text
+//! Callsign,Code,Number,AirlineCode,AirportCodes
+//! KLM1023,KL,1023,KLM,EHAM-EGLL
+//! ```
+//! Without a header the columns are `callsign,origin,destination` or
+//! `callsign,route`. The airline table maps the three letter ICAO designator
+//! that starts a callsign to the airline name. It reads CSV files with `icao`
+//! and `name` columns, the OpenFlights `airlines.dat`, and `icao,name` lines
+//! without a header.
+
+use crate::database::split_csv;
+use anyhow::{Context, Result, bail};
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::fs;
+use std::path::Path;
+
+/// Airline and route of a flight
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq)]
+pub struct FlightRoute {
+    /// The callsign the route was looked up for
+    pub callsign: String,
+    pub airline: Option<String>,
+    /// ICAO code of the departure airport
+    pub origin: Option<String>,
+    /// ICAO code of the arrival airport
+    pub destination: Option<String>,
+}
+
+/// Column of a header with one of the names
+fn column(header: &[String], names: &[&str]) -> Option<usize> {
+    header
+        .iter()
+        .position(|column| names.iter().any(|name| column.trim().eq_ignore_ascii_case(name)))
+}
+
+/// Split the non-empty lines of a CSV file into fields
+fn rows(data: &str) -> impl Iterator<Item = Vec<String>> + '_ {
+    let separator = if data.lines().next().is_some_and(|line| line.contains(';')) { ';' } else { ',' };
+    data.lines()
+        .filter(|line| !line.trim().is_empty())
+        .map(move |line| split_csv(line, separator))
+}
+
+/// The ICAO airline designator at the start of a callsign, `None` for
+/// callsigns that are not a designator followed by a flight number, such as
+/// registrations
+fn airline_designator(callsign: &str) -> Option<&str> {
+    let bytes = callsign.as_bytes();
+    (bytes.len() > 3 && bytes[..3].iter().all(u8::is_ascii_alphabetic) && bytes[3].is_ascii_digit())
+        .then(|| &callsign[..3])
+}
+
+/// Routes by callsign and airlines by ICAO designator
+#[derive(Clone, Debug, Default)]
+pub struct RouteDatabase {
+    /// Origin and destination by callsign
+    routes: HashMap<String, (Option<String>, Option<String>)>,
+    /// Airline name by ICAO designator
+    airlines: HashMap<String, String>,
+}
+
+impl RouteDatabase {
+    /// Load the routes from a CSV file
+    pub fn load_routes(&mut self, path: &Path) -> Result<()> {
+        let data = fs::read_to_string(path).with_context(|| format!("Failed to read {}", path.display()))?;
+        self.parse_routes(&data)
+            .with_context(|| format!("Invalid routes file {}", path.display()))
+    }
+
+    /// Load the airlines from a CSV file
+    pub fn load_airlines(&mut self, path: &Path) -> Result<()> {
+        let data = fs::read_to_string(path).with_context(|| format!("Failed to read {}", path.display()))?;
+        self.parse_airlines(&data)
+            .with_context(|| format!("Invalid airlines file {}", path.display()))
+    }
+
+    /// Add the routes of a CSV file
+    pub fn parse_routes(&mut self, data: &str) -> Result<()> {
+        let mut rows = rows(data).peekable();
+        let Some(first) = rows.peek() else {
+            return Ok(());
+        };
+        // Columns of the callsign, origin, destination and route
+        let columns = match column(first, &["callsign", "call"]) {
+            Some(callsign) => {
+                let columns = (
+                    callsign,
+                    column(first, &["origin", "from", "departure"]),
+                    column(first, &["destination", "to", "arrival"]),
+                    column(first, &["airportcodes", "airports", "route"]),
+                );
+                if columns.3.is_none() && (columns.1.is_none() || columns.2.is_none()) {
+                    bail!("Expected origin and destination, or route columns in the header");
+                }
+                rows.next();
+                columns
+            }
+            None if first.len() == 2 => (0, None, None, Some(1)),
+            None => (0, Some(1), Some(2), None),
+        };
+
+        let (callsign, origin, destination, route) = columns;
+        for fields in rows {
+            let field = |column: Option<usize>| {
+                column
+                    .and_then(|column| fields.get(column))
+                    .map(|field| field.trim().to_uppercase())
+                    .filter(|field| !field.is_empty())
+            };
+            let Some(callsign) = field(Some(callsign)) else {
+                continue;
+            };
+            let airports = match field(route) {
+                Some(route) => {
+                    let airports: Vec<&str> = route.split('-').map(str::trim).filter(|a| !a.is_empty()).collect();
+                    (airports.first().map(|a| a.to_string()), airports.last().map(|a| a.to_string()))
+                }
+                None => (field(origin), field(destination)),
+            };
+            self.routes.insert(callsign, airports);
+        }
+        Ok(())
+    }
+
+    /// Add the airlines of a CSV file
+    pub fn parse_airlines(&mut self, data: &str) -> Result<()> {
+        let mut rows = rows(data).peekable();
+        let Some(first) = rows.peek() else {
+            return Ok(());
+        };
+        let (icao, name) = match column(first, &["icao"]) {
+            Some(icao) => {
+                let name = column(first, &["name", "airline"]).context("No name column in the header")?;
+                rows.next();
+                (icao, name)
+            }
+            // OpenFlights: id, name, alias, IATA, ICAO, callsign, country, active
+            None if first.len() >= 8 => (4, 1),
+            None => (0, 1),
+        };
+
+        for fields in rows {
+            let designator = fields.get(icao).map(|icao| icao.trim().to_uppercase());
+            let Some(designator) = designator.filter(|icao| icao.len() == 3 && icao.bytes().all(|b| b.is_ascii_alphabetic())) else {
+                continue;
+            };
+            let Some(name) = fields.get(name).map(|name| name.trim()).filter(|name| !name.is_empty()) else {
+                continue;
+            };
+            self.airlines.insert(designator, name.to_string());
+        }
+        Ok(())
+    }
+
+    /// Number of routes
+    pub fn route_count(&self) -> usize {
+        self.routes.len()
+    }
+
+    /// Number of airlines
+    pub fn airline_count(&self) -> usize {
+        self.airlines.len()
+    }
+
+    /// The airline and route of a callsign, `None` if neither is known
+    pub fn lookup(&self, callsign: &str) -> Option<FlightRoute> {
+        if self.routes.is_empty() && self.airlines.is_empty() {
+            return None;
+        }
+        let callsign = callsign.trim().to_uppercase();
+        let airline = airline_designator(&callsign).and_then(|designator| self.airlines.get(designator)).cloned();
+        let (origin, destination) = self.routes.get(&callsign).cloned().unwrap_or_default();
+        if airline.is_none() && origin.is_none() && destination.is_none() {
+            return None;
+        }
+        Some(FlightRoute {
+            callsign,
+            airline,
+            origin,
+            destination,
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_routes() {
+        let mut database = RouteDatabase::default();
+        database
+            .parse_routes("Callsign,Code,Number,AirlineCode,AirportCodes\nKLM1023,KL,1023,KLM,EHAM-EGLL\nBAW15,BA,15,BAW,EGLL-WSSS-YSSY\n")
+            .unwrap();
+        database.parse_routes("ezy45hv,LGRP,EGKK\n").unwrap();
+        assert_eq!(database.route_count(), 3);
+
+        let route = database.lookup("BAW15  ").unwrap();
+        assert_eq!(route.callsign, "BAW15");
+        assert_eq!(route.origin.as_deref(), Some("EGLL"));
+        assert_eq!(route.destination.as_deref(), Some("YSSY"));
+        assert_eq!(route.airline, None);
+        assert_eq!(database.lookup("EZY45HV").unwrap().origin.as_deref(), Some("LGRP"));
+        assert_eq!(database.lookup("KLM1024"), None);
+
+        assert!(RouteDatabase::default().parse_routes("callsign,origin\nKLM1023,EHAM\n").is_err());
+    }
+
+    #[test]
+    fn test_airlines() {
+        let mut database = RouteDatabase::default();
+        database
+            .parse_airlines("137,\"Air France\",\\N,\"AF\",\"AFR\",\"AIRFRANS\",\"France\",\"Y\"\n1,\"Private flight\",\\N,\"-\",\"N/A\",\"\",\"\",\"Y\"\n")
+            .unwrap();
+        database.parse_airlines("Code,Name,ICAO,IATA\nKL,KLM Royal Dutch Airlines,KLM,KL\n").unwrap();
+        assert_eq!(database.airline_count(), 2);
+
+        let route = database.lookup("AFR1234").unwrap();
+        assert_eq!(route.airline.as_deref(), Some("Air France"));
+        assert_eq!(route.origin, None);
+        assert_eq!(database.lookup("KLM1023").unwrap().airline.as_deref(), Some("KLM Royal Dutch Airlines"));
+        // Registrations are not airline callsigns
+        assert_eq!(database.lookup("KLMAB"), None);
+    }
+}
@@ -8,6 +8,9 @@
 //! 
 //! The SBS-1 format consists of comma-separated values with the following structure:
 //! MSG,{transmission_type},{session_id},{aircraft_id},{hex_ident},{flight_id},{date_generated},{time_generated},{date_logged},{time_logged},{callsign},{altitude},{ground_speed},{track},{lat},{lon},{vertical_rate},{squawk},{alert},{emergency},{spi},{is_on_ground}
+//!
+//! Non-ICAO addresses (anonymous ADS-B, TIS-B track file numbers) are written
+//! with a `~` prefix in the hex_ident column, matching dump1090.
 //! 
 //! ## References
 //! - BaseStation Protocol Reference: http://woodair.net/sbs/article/barebones42_socket_data.htm
@@ -16,22 +19,28 @@
 //! 
 //! ## Message Types
 //! - MSG,1: Aircraft identification
+//! - MSG,2: Surface position
 //! - MSG,3: Airborne position  
 //! - MSG,4: Airborne velocity
 //! - MSG,5: Surveillance altitude
-//! - MSG,6: Squawk change
+//! - MSG,6: Squawk change, with the alert flag set for emergency squawks and the
+//!   emergency flag set when an emergency is declared or squawked, and the SPI
+//!   flag set while the crew presses IDENT
 //! - MSG,7: Air-to-air altitude
 //! - MSG,8: All-call reply
+//!
+//! ## Extended Mode
+//! With the `extended` option set to `true`, eight columns with the autopilot
+//! intent from TC 29 are appended to every message:
+//! {selected_altitude},{qnh},{selected_heading},{autopilot},{vnav},{altitude_hold},{approach},{lnav}
+//! Plain BaseStation clients may not accept the extra columns.
 
 use crate::decoder::DecoderMetaData;
-use crate::output_module::{OutputModuleBase, StateOutputModule};
-use crate::{AdsbIcao, AircraftRecord};
+use crate::output_module::{LineBroadcaster, LineMessage, LineServer, OutputModuleBase, StateOutputModule};
+use crate::{is_emergency_squawk, AircraftAddress, AircraftNavigation, AircraftRecord};
 use anyhow::Result;
 use std::time::{SystemTime, UNIX_EPOCH};
-use tokio::io::AsyncWriteExt;
-use tokio::net::{TcpListener, TcpStream};
-use tokio::sync::broadcast;
-use tracing::{debug, error, info, warn};
+use tracing::error;
 
 /// An SBS-1/BaseStation format message containing ADS-B data
 #[derive(Debug, Clone)]
@@ -58,6 +67,8 @@ pub struct Sbs1Message {
     pub emergency: bool,
     pub spi: bool,
     pub is_on_ground: bool,
+    /// Additional columns appended after `is_on_ground` in extended mode
+    pub extra_columns: Vec<String>,
 }
 
 impl Sbs1Message {
@@ -103,6 +114,7 @@ impl Sbs1Message {
             emergency: false,
             spi: false,
             is_on_ground: false,
+            extra_columns: Vec::new(),
         }
     }
 
@@ -141,6 +153,7 @@ impl Sbs1Message {
             emergency: false,
             spi: false,
             is_on_ground: true,
+            extra_columns: Vec::new(),
         }
     }
 
@@ -177,15 +190,16 @@ impl Sbs1Message {
             emergency: false,
             spi: false,
             is_on_ground: false,
+            extra_columns: Vec::new(),
         }
     }
 
     /// Create MSG,4: ES Airborne Velocity Message
     pub fn airborne_velocity(
         icao: &str,
-        ground_speed: f64,
-        track: f64,
-        vertical_rate: i16,
+        ground_speed: Option<f64>,
+        track: Option<f64>,
+        vertical_rate: Option<i16>,
         timestamp: SystemTime,
     ) -> Self {
         let (date_str, time_str) = Self::format_timestamp(timestamp);
@@ -203,16 +217,17 @@ impl Sbs1Message {
             time_logged: time_str,
             callsign: None,
             altitude: None,
-            ground_speed: Some(ground_speed),
-            track: Some(track),
+            ground_speed,
+            track,
             latitude: None,
             longitude: None,
-            vertical_rate: Some(vertical_rate),
+            vertical_rate,
             squawk: None,
             alert: false,
             emergency: false,
             spi: false,
             is_on_ground: false,
+            extra_columns: Vec::new(),
         }
     }
 
@@ -247,6 +262,7 @@ impl Sbs1Message {
             emergency: false,
             spi: false,
             is_on_ground: false,
+            extra_columns: Vec::new(),
         }
     }
 
@@ -281,14 +297,93 @@ impl Sbs1Message {
             emergency: false,
             spi: false,
             is_on_ground: false,
+            extra_columns: Vec::new(),
+        }
+    }
+
+    /// Set the special position identification (IDENT) flag of the message
+    pub fn with_spi(mut self, spi: bool) -> Self {
+        self.spi = spi;
+        self
+    }
+
+    /// Set the alert (emergency squawk) and emergency flags of the message
+    pub fn with_status(mut self, alert: bool, emergency: bool) -> Self {
+        self.alert = alert;
+        self.emergency = emergency;
+        self
+    }
+
+    /// Append additional columns to the message (extended mode)
+    pub fn with_extra_columns(mut self, columns: Vec<String>) -> Self {
+        self.extra_columns = columns;
+        self
+    }
+
+    /// Format autopilot intent as extended columns: selected altitude, QNH,
+    /// selected heading, then the autopilot, VNAV, altitude hold, approach and
+    /// LNAV mode flags
+    pub fn navigation_columns(navigation: Option<&AircraftNavigation>) -> Vec<String> {
+        let Some(nav) = navigation else {
+            return vec![String::new(); 8];
+        };
+        let flag = |mode: Option<bool>| match mode {
+            Some(true) => "1".to_string(),
+            Some(false) => "0".to_string(),
+            None => String::new(),
+        };
+        vec![
+            nav.selected_altitude.map_or(String::new(), |a| a.to_string()),
+            nav.qnh.map_or(String::new(), |q| format!("{:.1}", q)),
+            nav.selected_heading.map_or(String::new(), |h| format!("{:.1}", h)),
+            flag(nav.autopilot),
+            flag(nav.vnav),
+            flag(nav.altitude_hold),
+            flag(nav.approach),
+            flag(nav.lnav),
+        ]
+    }
+
+    /// Create MSG,8: All Call Reply
+    pub fn all_call_reply(
+        icao: &str,
+        is_on_ground: bool,
+        timestamp: SystemTime,
+    ) -> Self {
+        let (date_str, time_str) = Self::format_timestamp(timestamp);
+
+        Self {
+            message_type: 8,
+            transmission_type: 8,
+            session_id: 1,
+            aircraft_id: 1,
+            hex_ident: icao.to_string(),
+            flight_id: 1,
+            date_generated: date_str.clone(),
+            time_generated: time_str.clone(),
+            date_logged: date_str,
+            time_logged: time_str,
+            callsign: None,
+            altitude: None,
+            ground_speed: None,
+            track: None,
+            latitude: None,
+            longitude: None,
+            vertical_rate: None,
+            squawk: None,
+            alert: false,
+            emergency: false,
+            spi: false,
+            is_on_ground,
+            extra_columns: Vec::new(),
         }
     }
 
     /// Encode the message in SBS-1 CSV format
     /// Format: MSG,{transmission_type},{session_id},{aircraft_id},{hex_ident},{flight_id},{date_generated},{time_generated},{date_logged},{time_logged},{callsign},{altitude},{ground_speed},{track},{lat},{lon},{vertical_rate},{squawk},{alert},{emergency},{spi},{is_on_ground}
     pub fn encode(&self) -> String {
-        format!(
-            "MSG,{},{},{},{},{},{},{},{},{},{},{},{},{},{},{},{},{},{},{},{},{}\r\n",
+        let mut line = format!(
+            "MSG,{},{},{},{},{},{},{},{},{},{},{},{},{},{},{},{},{},{},{},{},{}",
             self.transmission_type,
             self.session_id,
             self.aircraft_id,
@@ -310,104 +405,29 @@ impl Sbs1Message {
             if self.emergency { "1" } else { "0" },
             if self.spi { "1" } else { "0" },
             if self.is_on_ground { "1" } else { "0" }
-        )
-    }
-}
-
-/// SBS-1 format TCP server
-pub struct Sbs1Server {
-    listener: TcpListener,
-    receiver: broadcast::Receiver<Sbs1Message>,
-}
-
-impl Sbs1Server {
-    /// Create a new SBS-1 server listening on the specified port
-    pub async fn new(port: u16, receiver: broadcast::Receiver<Sbs1Message>) -> Result<Self> {
-        let addr = format!("0.0.0.0:{}", port);
-        let listener = TcpListener::bind(&addr).await?;
-        info!("SBS-1 BaseStation server listening on {}", addr);
-
-        Ok(Self { listener, receiver })
-    }
-
-    /// Run the SBS-1 server, accepting connections and streaming data
-    pub async fn run(self) -> Result<()> {
-        loop {
-            match self.listener.accept().await {
-                Ok((stream, addr)) => {
-                    info!("SBS-1 client connected from {}", addr);
-                    let mut receiver = self.receiver.resubscribe();
-                    
-                    tokio::spawn(async move {
-                        if let Err(e) = Self::handle_client(stream, &mut receiver).await {
-                            debug!("SBS-1 client {} disconnected: {}", addr, e);
-                        }
-                    });
-                }
-                Err(e) => {
-                    error!("Failed to accept SBS-1 connection: {}", e);
-                }
-            }
-        }
-    }
-
-    /// Handle a single SBS-1 client connection
-    async fn handle_client(
-        mut stream: TcpStream,
-        receiver: &mut broadcast::Receiver<Sbs1Message>,
-    ) -> Result<()> {
-        loop {
-            match receiver.recv().await {
-                Ok(message) => {
-                    let encoded = message.encode();
-                    if let Err(e) = stream.write_all(encoded.as_bytes()).await {
-                        return Err(e.into());
-                    }
-                }
-                Err(broadcast::error::RecvError::Lagged(skipped)) => {
-                    warn!("SBS-1 client lagged, skipped {} messages", skipped);
-                    continue;
-                }
-                Err(broadcast::error::RecvError::Closed) => {
-                    debug!("SBS-1 message channel closed");
-                    return Ok(());
-                }
-            }
+        );
+        for column in &self.extra_columns {
+            line.push(',');
+            line.push_str(column);
         }
+        line.push_str("\r\n");
+        line
     }
 }
 
-/// SBS-1 format message broadcaster
-pub struct Sbs1Broadcaster {
-    sender: broadcast::Sender<Sbs1Message>,
-}
+impl LineMessage for Sbs1Message {
+    const FORMAT: &'static str = "SBS-1";
 
-impl Sbs1Broadcaster {
-    /// Create a new SBS-1 broadcaster with the specified channel capacity
-    pub fn new(capacity: usize) -> (Self, broadcast::Receiver<Sbs1Message>) {
-        let (sender, receiver) = broadcast::channel(capacity);
-        (Self { sender }, receiver)
+    fn encode(&self) -> String {
+        Sbs1Message::encode(self)
     }
+}
 
-    /// Broadcast an SBS-1 message
-    pub fn broadcast_message(&self, message: Sbs1Message) -> Result<()> {
-        match self.sender.send(message) {
-            Ok(receiver_count) => {
-                debug!("Broadcasted SBS-1 message to {} clients", receiver_count);
-                Ok(())
-            }
-            Err(_) => {
-                // No receivers, which is fine
-                Ok(())
-            }
-        }
-    }
+/// SBS-1 format TCP server
+pub type Sbs1Server = LineServer<Sbs1Message>;
 
-    /// Get the number of active SBS-1 clients
-    pub fn client_count(&self) -> usize {
-        self.sender.receiver_count()
-    }
-}
+/// SBS-1 format message broadcaster
+pub type Sbs1Broadcaster = LineBroadcaster<Sbs1Message>;
 
 /// SBS-1 output module implementing the OutputModule trait
 pub struct Sbs1Output {
@@ -415,6 +435,8 @@ pub struct Sbs1Output {
     port: u16,
     broadcaster: Sbs1Broadcaster,
     is_running: bool,
+    /// Append navigation columns to every message (`extended` extra option)
+    extended: bool,
 }
 
 impl Sbs1Output {
@@ -430,13 +452,26 @@ impl Sbs1Output {
             }
         });
 
+        let extended = config.extra.get("extended").is_some_and(|v| v == "true");
+
         Ok(Self {
             name: config.name,
             port: config.port,
             broadcaster,
             is_running: true,
+            extended,
         })
     }
+
+    /// Broadcast a message for an aircraft, adding the extended columns if enabled
+    fn send(&self, msg: Sbs1Message, record: &AircraftRecord) -> Result<()> {
+        let msg = if self.extended {
+            msg.with_extra_columns(Sbs1Message::navigation_columns(record.navigation.as_ref()))
+        } else {
+            msg
+        };
+        self.broadcaster.broadcast_message(msg)
+    }
 }
 
 // Implement the base trait for common functionality
@@ -470,8 +505,8 @@ impl OutputModuleBase for Sbs1Output {
 // Implement the state output trait for broadcasting aircraft state updates
 #[async_trait::async_trait]
 impl StateOutputModule for Sbs1Output {
-    fn broadcast_aircraft_update(&self, icao: &AdsbIcao, record: &AircraftRecord) -> Result<()> {
-        let icao_str = format!("{:02X}{:02X}{:02X}", icao.0[0], icao.0[1], icao.0[2]);
+    fn broadcast_aircraft_update(&self, address: &AircraftAddress, record: &AircraftRecord) -> Result<()> {
+        let icao_str = address.hex_ident();
 
         // MSG,1: Aircraft identification (if callsign available)
         if let Some(ref callsign) = record.callsign {
@@ -480,19 +515,40 @@ impl StateOutputModule for Sbs1Output {
                 callsign,
                 record.last_seen,
             );
-            self.broadcaster.broadcast_message(msg)?;
+            self.send(msg, record)?;
+        }
+
+        // MSG,6: Squawk and emergency status (if squawk available)
+        if let Some(squawk) = record.squawk {
+            let msg = Sbs1Message::squawk_change(&icao_str, squawk, record.last_seen)
+                .with_status(is_emergency_squawk(squawk), record.has_emergency())
+                .with_spi(record.spi);
+            self.send(msg, record)?;
         }
 
-        // MSG,3: Airborne position (if position available)
+        // MSG,2/MSG,3: Surface or airborne position (if position available)
         if let Some(pos_record) = record.positions.last() {
-            let msg = Sbs1Message::airborne_position(
-                &icao_str,
-                pos_record.position.latitude,
-                pos_record.position.longitude,
-                pos_record.position.altitude,
-                pos_record.time,
-            );
-            self.broadcaster.broadcast_message(msg)?;
+            let position = &pos_record.position;
+            let msg = if (5..=8).contains(&position.type_code) {
+                Sbs1Message::surface_position(
+                    &icao_str,
+                    position.latitude,
+                    position.longitude,
+                    position.altitude,
+                    position.ground_speed,
+                    position.track,
+                    pos_record.time,
+                )
+            } else {
+                Sbs1Message::airborne_position(
+                    &icao_str,
+                    position.latitude,
+                    position.longitude,
+                    position.altitude,
+                    pos_record.time,
+                )
+            };
+            self.send(msg, record)?;
         }
 
         // MSG,4: Airborne velocity (if velocity available)
@@ -504,11 +560,23 @@ impl StateOutputModule for Sbs1Output {
                 vel_record.velocity.vertical_rate,
                 vel_record.time,
             );
-            self.broadcaster.broadcast_message(msg)?;
+            self.send(msg, record)?;
         }
 
         Ok(())
     }
+
+    fn broadcast_all_call(&self, address: &AircraftAddress, record: &AircraftRecord) -> Result<()> {
+        let icao_str = address.hex_ident();
+
+        // MSG,8: All-call reply, CA 4 means the transponder reports being on the ground
+        let msg = Sbs1Message::all_call_reply(
+            &icao_str,
+            record.capability == Some(4),
+            record.last_seen,
+        );
+        self.send(msg, record)
+    }
 }
 
 // Keep legacy trait implementation for backward compatibility during migration
@@ -577,6 +645,7 @@ mod tests {
             emergency: false,
             spi: false,
             is_on_ground: false,
+            extra_columns: Vec::new(),
         };
         
         let encoded = message.encode();
@@ -617,9 +686,9 @@ mod tests {
         let now = SystemTime::now();
         let message = Sbs1Message::airborne_velocity(
             "ABC123",
-            450.5,
-            270.0,
-            -800,
+            Some(450.5),
+            Some(270.0),
+            Some(-800),
             now,
         );
 
@@ -629,4 +698,57 @@ mod tests {
         assert_eq!(message.track, Some(270.0));
         assert_eq!(message.vertical_rate, Some(-800));
     }
-}
\ No newline at end of file
+
+    #[test]
+    fn test_sbs1_all_call_reply_constructor() {
+        let now = SystemTime::now();
+        let message = Sbs1Message::all_call_reply("ABC123", true, now);
+
+        assert_eq!(message.hex_ident, "ABC123");
+        assert_eq!(message.transmission_type, 8);
+        assert!(message.is_on_ground);
+        assert!(message.encode().starts_with("MSG,8,1,1,ABC123,"));
+        assert!(message.encode().ends_with(",,0,0,0,1\r\n"));
+    }
+
+    #[test]
+    fn test_non_icao_hex_ident() {
+        let icao = adsb_deku::ICAO([0xAB, 0xC1, 0x23]);
+        assert_eq!(AircraftAddress::icao(icao).hex_ident(), "ABC123");
+
+        let address = AircraftAddress::non_icao(icao);
+        let message = Sbs1Message::identification(&address.hex_ident(), "TEST123", SystemTime::now());
+        assert!(message.encode().starts_with("MSG,1,1,1,~ABC123,"));
+    }
+
+    #[test]
+    fn test_sbs1_squawk_status_flags() {
+        let message = Sbs1Message::squawk_change("ABC123", 7700, SystemTime::now()).with_status(true, true);
+
+        assert_eq!(message.transmission_type, 6);
+        assert!(message.encode().ends_with(",7700,1,1,0,0\r\n"));
+    }
+
+    #[test]
+    fn test_sbs1_extended_navigation_columns() {
+        let navigation = AircraftNavigation {
+            selected_altitude: Some(16992),
+            selected_altitude_source: None,
+            qnh: Some(1012.8),
+            selected_heading: Some(66.796875),
+            autopilot: Some(true),
+            vnav: Some(true),
+            altitude_hold: Some(false),
+            approach: None,
+            lnav: Some(true),
+            tcas_operational: true,
+        };
+        let message = Sbs1Message::identification("ABC123", "TEST123", SystemTime::now())
+            .with_extra_columns(Sbs1Message::navigation_columns(Some(&navigation)));
+        assert!(message.encode().ends_with(",0,0,0,0,16992,1012.8,66.8,1,1,0,,1\r\n"));
+
+        let empty = Sbs1Message::navigation_columns(None);
+        assert_eq!(empty.len(), 8);
+        assert!(empty.iter().all(|c| c.is_empty()));
+    }
+}
@@ -0,0 +1,208 @@
+//! Register snapshots
+//!
+//! The tracker writes the aircraft register to disk periodically and when it
+//! stops, and restores it on startup, so that a restart of the service keeps
+//! the aircraft, their trails and their flights. The snapshot is the register
+//! in the JSON format of the control port `aircraft` command, wrapped with a
+//! version and the time it was taken:
+//! ```json
+//! {"version":1,"time":{...},"register":{"4840d6":{"icao":"4840d6",...}}}
+//! ```
+//! Fields that are not serialized, such as the last CPR frames and the
+//! Kalman filter, start over after a restore. Aircraft that were last seen
+//! longer ago than the prune lifetime are not restored.
+
+use crate::{AircraftAddress, AircraftRecord, AircraftRegister, icao};
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::fs::{self, File};
+use std::io::{self, Write};
+use std::path::PathBuf;
+use std::time::{Duration, Instant, SystemTime};
+
+/// How often the register is saved if the tracker does not configure it
+pub const DEFAULT_SNAPSHOT_INTERVAL: Duration = Duration::from_secs(60);
+
+/// Version of the snapshot format
+const SNAPSHOT_VERSION: u32 = 1;
+
+/// Configuration of the register snapshots
+#[derive(Debug, Clone)]
+pub struct SnapshotConfig {
+    /// File the register is saved to
+    pub path: PathBuf,
+    /// Time between snapshots
+    pub interval: Duration,
+}
+
+impl SnapshotConfig {
+    /// Save the register to `path` every minute
+    pub fn new(path: PathBuf) -> Self {
+        Self {
+            path,
+            interval: DEFAULT_SNAPSHOT_INTERVAL,
+        }
+    }
+}
+
+/// A snapshot as it is written
+#[derive(Serialize)]
+struct SnapshotRef<'a> {
+    version: u32,
+    time: SystemTime,
+    register: &'a AircraftRegister,
+}
+
+/// A snapshot as it is read
+#[derive(Deserialize)]
+struct Snapshot {
+    version: u32,
+    register: AircraftRegister,
+}
+
+/// Periodic snapshots of the aircraft register
+pub struct RegisterSnapshot {
+    config: SnapshotConfig,
+    last_save: Instant,
+}
+
+impl RegisterSnapshot {
+    pub fn new(config: SnapshotConfig) -> Self {
+        Self {
+            config,
+            last_save: Instant::now(),
+        }
+    }
+
+    /// Load the aircraft of the last snapshot that were seen within
+    /// `max_age` of `now`, all of them if `None`
+    ///
+    /// A missing or unreadable snapshot restores no aircraft.
+    pub fn load(&self, now: SystemTime, max_age: Option<Duration>) -> HashMap<AircraftAddress, AircraftRecord> {
+        let path = &self.config.path;
+        let snapshot = match fs::read_to_string(path) {
+            Ok(content) => match serde_json::from_str::<Snapshot>(&content) {
+                Ok(snapshot) if snapshot.version == SNAPSHOT_VERSION => snapshot,
+                Ok(snapshot) => {
+                    tracing::warn!("Snapshot in {} has unknown version {}, starting over", path.display(), snapshot.version);
+                    return HashMap::new();
+                }
+                Err(e) => {
+                    tracing::warn!("Could not parse snapshot in {}: {}", path.display(), e);
+                    return HashMap::new();
+                }
+            },
+            Err(e) if e.kind() == io::ErrorKind::NotFound => return HashMap::new(),
+            Err(e) => {
+                tracing::warn!("Could not read snapshot from {}: {}", path.display(), e);
+                return HashMap::new();
+            }
+        };
+
+        let mut register = snapshot.register.register;
+        register.retain(|_, rec| max_age.is_none_or(|age| rec.last_seen + age >= now));
+        for (address, rec) in register.iter_mut() {
+            // The country is a static string, looked up again
+            rec.country = icao::country(address);
+            rec.military = icao::is_military(address);
+        }
+        register
+    }
+
+    /// Save the register to disk, replacing the previous snapshot atomically
+    pub fn save(&mut self, register: &AircraftRegister) -> io::Result<()> {
+        let snapshot = SnapshotRef {
+            version: SNAPSHOT_VERSION,
+            time: SystemTime::now(),
+            register,
+        };
+        let mut temp_path = self.config.path.clone().into_os_string();
+        temp_path.push(".tmp");
+        let mut file = File::create(&temp_path)?;
+        file.write_all(&serde_json::to_vec(&snapshot)?)?;
+        // The data must be on disk before it replaces the previous snapshot
+        file.sync_all()?;
+        fs::rename(&temp_path, &self.config.path)?;
+        self.last_save = Instant::now();
+        Ok(())
+    }
+
+    /// Save the register if the last snapshot is older than the interval
+    pub fn save_if_due(&mut self, register: &AircraftRegister) {
+        if self.last_save.elapsed() < self.config.interval {
+            return;
+        }
+        if let Err(e) = self.save(register) {
+            tracing::warn!("Could not save snapshot: {}", e);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{AircraftPosition, AircraftPositionRecord, PositionQuality};
+
+    #[test]
+    fn test_snapshot_restore() {
+        let path = std::env::temp_dir().join(format!("airjedi-snapshot-{}.json", std::process::id()));
+        let now = SystemTime::now();
+        let recent = AircraftAddress::icao(adsb_deku::ICAO([0x48, 0x40, 0xd6]));
+        let old = AircraftAddress::non_icao(adsb_deku::ICAO([0x00, 0x12, 0x34]));
+
+        let mut rec = AircraftRecord::new(&recent, now);
+        rec.callsign = Some("KLM1023".to_string());
+        rec.positions.push(AircraftPositionRecord {
+            position: AircraftPosition {
+                latitude: 52.3,
+                longitude: 4.76,
+                altitude: Some(3000),
+                type_code: 11,
+                ground_speed: None,
+                track: None,
+                quality: PositionQuality::default(),
+            },
+            time: now,
+            phase: None,
+        });
+        let register = AircraftRegister {
+            register: HashMap::from([
+                (recent, rec),
+                (old, AircraftRecord::new(&old, now - Duration::from_secs(3600))),
+            ]),
+        };
+
+        let mut snapshot = RegisterSnapshot::new(SnapshotConfig::new(path.clone()));
+        snapshot.save(&register).unwrap();
+        assert_eq!(snapshot.load(now, None).len(), 2);
+
+        let restored = snapshot.load(now, Some(Duration::from_secs(300)));
+        assert_eq!(restored.len(), 1);
+        let rec = &restored[&recent];
+        assert_eq!(rec.callsign.as_deref(), Some("KLM1023"));
+        assert_eq!(rec.country, Some("Netherlands"));
+        assert_eq!(rec.positions.len(), 1);
+        assert_eq!(rec.positions[0].position.altitude, Some(3000));
+        assert_eq!(rec.last_seen, now);
+        fs::remove_file(&path).unwrap();
+    }
+
+    #[test]
+    fn test_address_from_str() {
+        let address: AircraftAddress = "4840d6".parse().unwrap();
+        assert_eq!(address, AircraftAddress::icao(adsb_deku::ICAO([0x48, 0x40, 0xd6])));
+        let address: AircraftAddress = "~001234".parse().unwrap();
+        assert_eq!(address, AircraftAddress::non_icao(adsb_deku::ICAO([0x00, 0x12, 0x34])));
+
+        for invalid in ["", "~", "4840d", "4840d6a", "4840dz", "+4840d", "~~4840d6", " 4840d6"] {
+            assert!(invalid.parse::<AircraftAddress>().is_err(), "{:?} parsed", invalid);
+        }
+    }
+
+    #[test]
+    fn test_snapshot_missing() {
+        let path = std::env::temp_dir().join(format!("airjedi-snapshot-missing-{}.json", std::process::id()));
+        let snapshot = RegisterSnapshot::new(SnapshotConfig::new(path));
+        assert!(snapshot.load(SystemTime::now(), None).is_empty());
+    }
+}
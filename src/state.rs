@@ -0,0 +1,307 @@
+//! Current state of an aircraft
+//!
+//! An aircraft record keeps the history of positions and velocities, and the
+//! latest value of the other fields. The current state flattens both into the
+//! latest value of each field, with the time and the kind of message it was
+//! last updated from, similar to the `seen` and `seen_pos` fields of tar1090.
+//! Fields that were not updated for longer than the stale age are marked.
+
+use crate::AircraftRecord;
+use serde::Serialize;
+use std::time::{Duration, SystemTime};
+
+/// Age after which a field is stale if the tracker does not configure one
+pub const DEFAULT_STALE_AFTER: Duration = Duration::from_secs(60);
+
+/// Kind of message a field of the current state was last updated from
+#[derive(Serialize, Clone, Copy, Debug, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum StateSource {
+    /// Aircraft identification (TC 1-4)
+    Identification,
+    /// Surface position (TC 5-8)
+    SurfacePosition,
+    /// Airborne position with barometric altitude (TC 9-18)
+    AirbornePosition,
+    /// Airborne position with GNSS altitude (TC 20-22)
+    AirbornePositionGnss,
+    /// Airborne velocity (TC 19)
+    AirborneVelocity,
+    /// Aircraft status (TC 28)
+    AircraftStatus,
+    /// All-call reply (DF11)
+    AllCall,
+    /// Surveillance or Comm-B reply (DF4, DF5, DF20, DF21)
+    SurveillanceReply,
+}
+
+impl StateSource {
+    /// Source of a position with the given type code
+    pub fn position(type_code: u8) -> Self {
+        match type_code {
+            5..=8 => Self::SurfacePosition,
+            20..=22 => Self::AirbornePositionGnss,
+            _ => Self::AirbornePosition,
+        }
+    }
+}
+
+/// When and from which message a field was last updated
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct FieldUpdate {
+    pub time: SystemTime,
+    pub source: StateSource,
+}
+
+impl FieldUpdate {
+    pub fn new(time: SystemTime, source: StateSource) -> Self {
+        Self { time, source }
+    }
+}
+
+/// When the fields of an aircraft record without a history were last updated
+#[derive(Clone, Debug, Default)]
+pub struct FieldUpdates {
+    /// Callsign and emitter category
+    pub identification: Option<FieldUpdate>,
+    pub squawk: Option<FieldUpdate>,
+    pub on_ground: Option<FieldUpdate>,
+    pub geometric_altitude: Option<FieldUpdate>,
+}
+
+/// A field of the current state
+#[derive(Serialize, Clone, Debug, PartialEq)]
+pub struct StateField<T> {
+    pub value: T,
+    /// When the field was last updated
+    pub updated: SystemTime,
+    /// Seconds since the last update
+    pub age: f64,
+    /// Kind of message the field was last updated from
+    pub source: StateSource,
+    /// Whether the last update is older than the stale age
+    pub stale: bool,
+}
+
+/// A position in degrees
+#[derive(Serialize, Clone, Copy, Debug, PartialEq)]
+pub struct StatePosition {
+    pub latitude: f64,
+    pub longitude: f64,
+}
+
+/// Flattened current state of an aircraft
+#[derive(Serialize, Clone, Debug, PartialEq)]
+pub struct AircraftState {
+    /// Seconds since any message was received from the aircraft
+    pub seen: f64,
+    /// Whether no message was received for longer than the stale age
+    pub stale: bool,
+    pub position: Option<StateField<StatePosition>>,
+    /// Barometric altitude in feet
+    pub altitude: Option<StateField<i32>>,
+    /// Geometric (GNSS) altitude in feet
+    pub geometric_altitude: Option<StateField<i32>>,
+    /// Ground speed in knots
+    pub ground_speed: Option<StateField<f64>>,
+    /// Ground track in degrees
+    pub track: Option<StateField<f64>>,
+    /// Vertical rate in ft/min, positive when climbing
+    pub vertical_rate: Option<StateField<i16>>,
+    pub callsign: Option<StateField<String>>,
+    pub emitter_category: Option<StateField<u8>>,
+    /// Mode A code, written with its octal digits as decimal digits
+    pub squawk: Option<StateField<u16>>,
+    pub on_ground: Option<StateField<bool>>,
+}
+
+/// Ages the fields of a state
+struct StateClock {
+    now: SystemTime,
+    stale_after: Duration,
+}
+
+impl StateClock {
+    fn age(&self, time: SystemTime) -> Duration {
+        self.now.duration_since(time).unwrap_or_default()
+    }
+
+    fn field<T>(&self, value: T, update: FieldUpdate) -> StateField<T> {
+        let age = self.age(update.time);
+        StateField {
+            value,
+            updated: update.time,
+            age: age.as_secs_f64(),
+            source: update.source,
+            stale: age > self.stale_after,
+        }
+    }
+}
+
+impl AircraftRecord {
+    /// The current state of the aircraft, with fields older than
+    /// `stale_after` marked as stale
+    pub fn state(&self, now: SystemTime, stale_after: Duration) -> AircraftState {
+        let clock = StateClock { now, stale_after };
+
+        let position = self.positions.last().map(|p| {
+            let value = StatePosition {
+                latitude: p.position.latitude,
+                longitude: p.position.longitude,
+            };
+            clock.field(value, FieldUpdate::new(p.time, StateSource::position(p.position.type_code)))
+        });
+        // GNSS altitude positions do not carry the barometric altitude
+        let altitude = self
+            .positions
+            .iter()
+            .rev()
+            .filter(|p| StateSource::position(p.position.type_code) == StateSource::AirbornePosition)
+            .find_map(|p| {
+                let update = FieldUpdate::new(p.time, StateSource::AirbornePosition);
+                p.position.altitude.map(|altitude| clock.field(i32::from(altitude), update))
+            });
+        let geometric_altitude = self
+            .geometric_altitude
+            .zip(self.updates.geometric_altitude)
+            .map(|(altitude, update)| clock.field(altitude, update));
+
+        // Speed and track come from airborne velocities or surface positions,
+        // whichever is more recent
+        let airborne = self.velocities.iter().rev().find_map(|v| {
+            let update = FieldUpdate::new(v.time, StateSource::AirborneVelocity);
+            v.velocity.ground_speed.map(|speed| (speed, v.velocity.heading, update))
+        });
+        let surface = self.positions.iter().rev().find_map(|p| {
+            let update = FieldUpdate::new(p.time, StateSource::SurfacePosition);
+            p.position.ground_speed.map(|speed| (speed, p.position.track, update))
+        });
+        let motion = [airborne, surface].into_iter().flatten().max_by_key(|(_, _, update)| update.time);
+        let vertical_rate = self.velocities.iter().rev().find_map(|v| {
+            let update = FieldUpdate::new(v.time, StateSource::AirborneVelocity);
+            v.velocity.vertical_rate.map(|rate| clock.field(rate, update))
+        });
+
+        let seen = clock.age(self.last_seen);
+        AircraftState {
+            seen: seen.as_secs_f64(),
+            stale: seen > stale_after,
+            position,
+            altitude,
+            geometric_altitude,
+            ground_speed: motion.map(|(speed, _, update)| clock.field(speed, update)),
+            track: motion.and_then(|(_, track, update)| track.map(|track| clock.field(track, update))),
+            vertical_rate,
+            callsign: self
+                .callsign
+                .clone()
+                .zip(self.updates.identification)
+                .map(|(callsign, update)| clock.field(callsign, update)),
+            emitter_category: self
+                .emitter_category
+                .zip(self.updates.identification)
+                .map(|(category, update)| clock.field(category, update)),
+            squawk: self.squawk.zip(self.updates.squawk).map(|(squawk, update)| clock.field(squawk, update)),
+            on_ground: self
+                .on_ground
+                .zip(self.updates.on_ground)
+                .map(|(on_ground, update)| clock.field(on_ground, update)),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        AircraftAddress, AircraftPosition, AircraftPositionRecord, AircraftVelocity, AircraftVelocityRecord,
+        AircraftVerticalRateSource, PositionQuality,
+    };
+
+    fn position(type_code: u8, altitude: Option<u16>, ground_speed: Option<f64>, time: SystemTime) -> AircraftPositionRecord {
+        AircraftPositionRecord {
+            position: AircraftPosition {
+                latitude: 52.0,
+                longitude: 4.0,
+                altitude,
+                type_code,
+                ground_speed,
+                track: ground_speed.map(|_| 90.0),
+                quality: PositionQuality::default(),
+            },
+            time,
+            phase: None,
+        }
+    }
+
+    fn velocity(ground_speed: f64, time: SystemTime) -> AircraftVelocityRecord {
+        AircraftVelocityRecord {
+            velocity: AircraftVelocity {
+                subtype: 1,
+                heading: Some(180.0),
+                ground_speed: Some(ground_speed),
+                magnetic_heading: None,
+                indicated_airspeed: None,
+                true_airspeed: None,
+                vertical_rate: Some(-832),
+                vertical_rate_source: AircraftVerticalRateSource::BarometricPressureAltitude,
+                geo_minus_baro: None,
+            },
+            time,
+        }
+    }
+
+    #[test]
+    fn test_state_fields() {
+        let now = SystemTime::now();
+        let earlier = now - Duration::from_secs(90);
+        let address = AircraftAddress::icao(adsb_deku::ICAO([0x48, 0x50, 0x20]));
+        let mut rec = AircraftRecord::new(&address, now);
+        rec.positions.push(position(11, Some(38000), None, earlier));
+        rec.positions.push(position(20, Some(38500), None, now));
+        rec.velocities.push(velocity(450.0, now - Duration::from_secs(10)));
+        rec.callsign = Some("KLM1023".to_string());
+        rec.emitter_category = Some(3);
+        rec.updates.identification = Some(FieldUpdate::new(earlier, StateSource::Identification));
+
+        let state = rec.state(now, DEFAULT_STALE_AFTER);
+        assert!(!state.stale);
+        let position = state.position.unwrap();
+        assert_eq!(position.source, StateSource::AirbornePositionGnss);
+        assert!(!position.stale);
+        // The barometric altitude is from the older position
+        let altitude = state.altitude.unwrap();
+        assert_eq!(altitude.value, 38000);
+        assert_eq!(altitude.source, StateSource::AirbornePosition);
+        assert!(altitude.stale);
+        let ground_speed = state.ground_speed.unwrap();
+        assert_eq!(ground_speed.value, 450.0);
+        assert_eq!(ground_speed.source, StateSource::AirborneVelocity);
+        assert_eq!(ground_speed.age, 10.0);
+        assert_eq!(state.track.unwrap().value, 180.0);
+        assert_eq!(state.vertical_rate.unwrap().value, -832);
+        let callsign = state.callsign.unwrap();
+        assert_eq!(callsign.value, "KLM1023");
+        assert!(callsign.stale);
+        assert_eq!(state.emitter_category.unwrap().source, StateSource::Identification);
+        // Fields without an update time are left out
+        assert!(state.squawk.is_none());
+    }
+
+    #[test]
+    fn test_state_surface_motion() {
+        let now = SystemTime::now();
+        let address = AircraftAddress::icao(adsb_deku::ICAO([0x48, 0x50, 0x20]));
+        let mut rec = AircraftRecord::new(&address, now - Duration::from_secs(120));
+        rec.velocities.push(velocity(140.0, now - Duration::from_secs(60)));
+        rec.positions.push(position(7, None, Some(12.0), now));
+
+        let state = rec.state(now, Duration::from_secs(30));
+        assert!(state.stale);
+        let ground_speed = state.ground_speed.unwrap();
+        assert_eq!(ground_speed.value, 12.0);
+        assert_eq!(ground_speed.source, StateSource::SurfacePosition);
+        assert_eq!(state.track.unwrap().value, 90.0);
+        assert!(state.altitude.is_none());
+    }
+}
@@ -16,38 +16,183 @@ use futuresdr::tracing::debug;
 use futuresdr::tracing::info;
 use futuresdr::tracing::warn;
 use std::cmp::Ordering;
+use std::collections::VecDeque;
 use std::sync::atomic::Ordering as AtomicOrdering;
 use std::time::{Duration, Instant};
 
+use crate::coverage::Coverage;
+use crate::cpr;
+use crate::geo;
+use crate::kalman;
 use crate::decoder::DecoderMetaData;
 use crate::metrics;
+use crate::mode_s;
+use crate::mode_s::{AircraftStatusReport, SurfacePositionReport, SurveillanceReply};
 use crate::output_module::OutputModuleManager;
 use crate::rate_limiter::{RateLimitConfig, RateLimitResult, UpdateType};
 use crate::rate_limited_manager::RateLimitedStateManager;
+use crate::flight::DEFAULT_FLIGHT_GAP;
+use crate::snapshot::RegisterSnapshot;
+use crate::state::DEFAULT_STALE_AFTER;
 use crate::*;
 
+mod commands;
+
 /// The duration considered to be recent when decoding CPR frames
 const ADSB_TIME_RECENT: Duration = Duration::new(10, 0);
+/// How long a position stays usable as reference for local CPR decoding. An
+/// airliner covers far less than the 180 NM half zone in this time.
+const LOCAL_CPR_MAX_AGE: Duration = Duration::from_secs(60);
+
+/// Range of the receiver in NM beyond which positions decoded relative to it
+/// are rejected, if the tracker does not configure one. A decode relative to
+/// the receiver always lands within half a zone (about 180 NM) of it, so this
+/// cannot detect an aircraft further away that decodes into the wrong zone:
+/// the fallback is only right for aircraft within range of the receiver. A
+/// smaller range, matching the antenna, rejects more of the aliased fixes,
+/// and a global fix replaces the position once an even/odd pair arrives.
+const DEFAULT_MAX_RECEIVER_RANGE_NM: f64 = 180.0;
+
+/// Half the size in NM of a surface CPR zone, beyond which a surface
+/// position decoded relative to the receiver is ambiguous
+const SURFACE_HALF_ZONE_NM: f64 = 45.0;
+
+/// Highest ground speed in knots that a position fix may imply
+const MAX_PLAUSIBLE_SPEED: f64 = 1000.0;
+/// Distance in NM a position may always move, to allow for CPR resolution
+const POSITION_SLACK_NM: f64 = 1.0;
+/// Consecutive implausible fixes after which the position is re-initialised
+const MAX_REJECTED_POSITIONS: u32 = 3;
+
+/// The number of previous resolution advisories kept per aircraft
+const MAX_RA_HISTORY: usize = 20;
+
+/// The number of recent alert events kept for the control port
+const MAX_EVENTS: usize = 100;
+
+/// The number of finished flights kept for the control port
+const MAX_FINISHED_FLIGHTS: usize = 500;
 
 /// Data types that can be rate limited in the tracker
 #[derive(Debug, Clone)]
 pub enum TrackerUpdateData {
     Identification(AdsbIdentification),
     Position(AdsbPosition, DecoderMetaData),
-    Velocity(AdsbVelocity),
+    SurfacePosition(SurfacePositionReport, DecoderMetaData),
+    Velocity(AircraftVelocity),
+    AllCall(u8),
+}
+
+/// Configuration of the tracker
+#[derive(Debug, Clone, Default)]
+pub struct TrackerConfig {
+    /// When to prune aircraft from the register
+    pub prune_after: Option<Duration>,
+    /// Rate limiting of updates, disabled if `None`
+    pub rate_limit: Option<RateLimitConfig>,
+    /// Location of the receiver, used as reference to decode positions and
+    /// to compute the range, bearing and elevation of aircraft
+    pub receiver: Option<ReceiverLocation>,
+    /// Range of the receiver in NM, positions decoded relative to it further
+    /// away are rejected, 180 NM if `None`
+    pub max_receiver_range_nm: Option<f64>,
+    /// Minimum NIC for a position to be trusted, all positions are if `None`
+    pub min_nic: Option<u8>,
+    /// What to do with positions below `min_nic`
+    pub integrity_policy: IntegrityPolicy,
+    /// Limits on the position and velocity history of each aircraft
+    pub retention: RetentionConfig,
+    /// Maximum number of tracked aircraft, the least recently seen is
+    /// evicted to make room for a new one
+    pub max_aircraft: Option<usize>,
+    /// Polar coverage of the receiver, requires `receiver`
+    pub coverage: Option<CoverageConfig>,
+    /// Periodic snapshots of the register, restored on startup
+    pub snapshot: Option<SnapshotConfig>,
+    /// Age after which fields of the current aircraft state are stale,
+    /// 60 seconds if `None`
+    pub stale_after: Option<Duration>,
+    /// Kalman filtering of aircraft tracks, disabled if `None`
+    pub kalman: Option<KalmanConfig>,
+    /// Time without positions after which a new flight starts, 30 minutes
+    /// if `None`
+    pub flight_gap: Option<Duration>,
+    /// Geofences checked on every position
+    pub geofences: Vec<Geofence>,
+    /// Aircraft of interest, flagged and reported when they appear, change
+    /// or disappear
+    pub watchlist: Watchlist,
+    /// Registrations, types and operators added to new aircraft
+    pub database: AircraftDatabase,
+    /// Airlines and routes added to flights by callsign
+    pub routes: RouteDatabase,
+}
+
+/// What to do with positions below the minimum integrity
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum IntegrityPolicy {
+    /// Keep the position and mark it as low integrity
+    #[default]
+    Tag,
+    /// Drop the position before it reaches the register and the outputs
+    Filter,
 }
 
 pub struct Tracker {
     /// When to prune aircraft from the register.
     prune_after: Option<Duration>,
+    /// Location of the receiver.
+    receiver: Option<ReceiverLocation>,
+    /// Range of the receiver for positions decoded relative to it.
+    max_receiver_range_nm: f64,
+    /// Minimum NIC for a position to be trusted.
+    min_nic: Option<u8>,
+    /// What to do with positions below the minimum NIC.
+    integrity_policy: IntegrityPolicy,
+    /// Limits on the history of each aircraft.
+    retention: RetentionConfig,
+    /// Maximum number of tracked aircraft.
+    max_aircraft: Option<usize>,
+    /// Polar coverage of the receiver.
+    coverage: Option<Coverage>,
+    /// Snapshots of the register.
+    snapshot: Option<RegisterSnapshot>,
+    /// Age after which fields of the current state are stale.
+    stale_after: Duration,
+    /// Kalman filtering of aircraft tracks.
+    kalman: Option<KalmanConfig>,
+    /// Time without positions after which a new flight starts.
+    flight_gap: Duration,
+    /// Identifier of the next flight.
+    next_flight_id: u64,
+    /// Recently finished flights, oldest first
+    finished_flights: VecDeque<FinishedFlight>,
+    /// Geofences and the aircraft inside them.
+    geofences: Geofences,
+    /// Aircraft of interest.
+    watchlist: Watchlist,
+    /// Registrations, types and operators of known aircraft.
+    database: AircraftDatabase,
+    /// Airlines and routes by callsign.
+    routes: RouteDatabase,
+    /// Longest distance of a position from the receiver, in NM.
+    max_range_nm: f64,
+    /// Sum of the distances of all positions from the receiver, in NM.
+    range_sum_nm: f64,
+    /// Number of positions in the range sum.
+    range_count: u64,
     /// A register of the received aircraft.
     aircraft_register: AircraftRegister,
     /// Dynamic output module manager for all broadcast formats
     output_manager: OutputModuleManager,
     /// Rate limiter for managing update frequencies
-    rate_limiter: Option<RateLimitedStateManager<AdsbIcao, TrackerUpdateData>>,
+    rate_limiter: Option<RateLimitedStateManager<AircraftAddress, TrackerUpdateData>>,
     /// Track when we last logged statistics
     last_stats_log: Instant,
+    /// Recent alert events, oldest first
+    events: VecDeque<TrackerEvent>,
+    /// Active special-condition alerts
+    alerts: AlertManager,
 }
 
 impl Tracker {
@@ -83,13 +228,42 @@ impl Tracker {
         output_manager: OutputModuleManager,
         rate_config: Option<RateLimitConfig>
     ) -> TypedBlock<Self> {
-        let aircraft_register = AircraftRegister {
-            register: HashMap::new(),
+        let config = TrackerConfig {
+            prune_after,
+            rate_limit: rate_config,
+            ..Default::default()
         };
+        Self::with_config(config, output_manager)
+    }
+
+    /// Creates a new tracker from a configuration
+    pub fn with_config(config: TrackerConfig, output_manager: OutputModuleManager) -> TypedBlock<Self> {
+        // Restore the aircraft of the last snapshot that would not have been
+        // pruned yet
+        let snapshot = config.snapshot.map(RegisterSnapshot::new);
+        let mut register = snapshot
+            .as_ref()
+            .map(|snapshot| snapshot.load(SystemTime::now(), config.prune_after))
+            .unwrap_or_default();
+        for (address, rec) in register.iter_mut() {
+            rec.info = config.database.lookup(address).unwrap_or_default();
+        }
+        if !register.is_empty() {
+            info!("Restored {} aircraft from the snapshot", register.len());
+        }
+        // Keep the flight identifiers unique
+        let next_flight_id = register
+            .values()
+            .filter_map(|rec| rec.flight.as_ref())
+            .map(|flight| flight.id + 1)
+            .max()
+            .unwrap_or(1);
+        metrics().aircraft_tracked.store(register.len() as u64, AtomicOrdering::Relaxed);
+        let aircraft_register = AircraftRegister { register };
 
-        let rate_limiter = rate_config.map(|config| {
-            RateLimitedStateManager::with_config(config)
-                .with_eviction_timeout(prune_after.unwrap_or(Duration::from_secs(300)))
+        let rate_limiter = config.rate_limit.map(|rate_config| {
+            RateLimitedStateManager::with_config(rate_config)
+                .with_eviction_timeout(config.prune_after.unwrap_or(Duration::from_secs(300)))
         });
 
         TypedBlock::new(
@@ -100,11 +274,33 @@ impl Tracker {
                 .add_input("ctrl_port", Self::handle_ctrl_port)
                 .build(),
             Self {
-                prune_after,
+                prune_after: config.prune_after,
+                receiver: config.receiver,
+                max_receiver_range_nm: config.max_receiver_range_nm.unwrap_or(DEFAULT_MAX_RECEIVER_RANGE_NM),
+                min_nic: config.min_nic,
+                integrity_policy: config.integrity_policy,
+                retention: config.retention,
+                max_aircraft: config.max_aircraft,
+                coverage: config.coverage.filter(|_| config.receiver.is_some()).map(Coverage::new),
+                snapshot,
+                stale_after: config.stale_after.unwrap_or(DEFAULT_STALE_AFTER),
+                kalman: config.kalman,
+                flight_gap: config.flight_gap.unwrap_or(DEFAULT_FLIGHT_GAP),
+                next_flight_id,
+                finished_flights: VecDeque::new(),
+                geofences: Geofences::new(config.geofences),
+                watchlist: config.watchlist,
+                database: config.database,
+                routes: config.routes,
+                max_range_nm: 0.0,
+                range_sum_nm: 0.0,
+                range_count: 0,
                 aircraft_register,
                 output_manager,
                 rate_limiter,
                 last_stats_log: Instant::now(),
+                events: VecDeque::new(),
+                alerts: AlertManager::default(),
             },
         )
     }
@@ -124,28 +320,7 @@ impl Tracker {
                 let json = serde_json::to_string(&self.aircraft_register).unwrap();
                 Ok(Pmt::String(json))
             }
-            Pmt::String(cmd) => {
-                match cmd.as_str() {
-                    "stats" => {
-                        // Return rate limiting statistics if available
-                        if let Some(stats) = self.get_rate_limit_stats() {
-                            let json = serde_json::to_string(&stats).unwrap();
-                            Ok(Pmt::String(json))
-                        } else {
-                            Ok(Pmt::String("{\"rate_limiting\": \"disabled\"}".to_string()))
-                        }
-                    }
-                    "aircraft" => {
-                        // Return aircraft register (same as Pmt::Null for backward compatibility)
-                        let json = serde_json::to_string(&self.aircraft_register).unwrap();
-                        Ok(Pmt::String(json))
-                    }
-                    _ => {
-                        warn!("Unknown control port command: {}", cmd);
-                        Ok(Pmt::String(format!("{{\"error\": \"Unknown command: {}\"}}", cmd)))
-                    }
-                }
-            }
+            Pmt::String(cmd) => Ok(Pmt::String(self.command(&cmd))),
             Pmt::Finished => {
                 io.finished = true;
                 Ok(Pmt::Ok)
@@ -157,6 +332,16 @@ impl Tracker {
         }
     }
 
+    /// Save the coverage and the register, when the tracker stops
+    fn save_state(&mut self) {
+        if let Some(Err(e)) = self.coverage.as_mut().map(Coverage::save) {
+            warn!("Could not save coverage: {}", e);
+        }
+        if let Some(Err(e)) = self.snapshot.as_mut().map(|snapshot| snapshot.save(&self.aircraft_register)) {
+            warn!("Could not save snapshot: {}", e);
+        }
+    }
+
     /// This function handles received packets passed to the block.
     #[message_handler]
     async fn packet_received(
@@ -171,49 +356,61 @@ impl Tracker {
                 if let Some(adsb_packet) = a.downcast_ref::<AdsbPacket>() {
                     // We received a packet. Update the register.
                     debug!("Received {:?}", adsb_packet);
-                    if let adsb_deku::DF::ADSB(adsb) = &adsb_packet.message.df {
-                        let metadata = &adsb_packet.decoder_metadata;
+                    let metadata = &adsb_packet.decoder_metadata;
 
-                        // Broadcast messages if enabled (always immediate for external consumers)
-                        self.broadcast_output_messages(adsb_packet);
+                    // Broadcast messages if enabled (always immediate for external consumers)
+                    self.broadcast_output_messages(adsb_packet);
 
-                        // Update metrics based on message type
-                        match &adsb.me {
-                            adsb_deku::adsb::ME::AircraftIdentification(_) => {
-                                metrics().msg_identification.fetch_add(1, AtomicOrdering::Relaxed);
-                            }
-                            adsb_deku::adsb::ME::AirbornePositionBaroAltitude(_)
-                            | adsb_deku::adsb::ME::AirbornePositionGNSSAltitude(_) => {
-                                metrics().msg_position.fetch_add(1, AtomicOrdering::Relaxed);
-                            }
-                            adsb_deku::adsb::ME::AirborneVelocity(_) => {
-                                metrics().msg_velocity.fetch_add(1, AtomicOrdering::Relaxed);
+                    // Duplicate frames only go to the raw outputs that keep them
+                    if metadata.duplicate {
+                        return Ok(Pmt::Ok);
+                    }
+
+                    match &adsb_packet.message.df {
+                        adsb_deku::DF::ADSB(adsb) => {
+                            let address = AircraftAddress::icao(adsb.icao);
+                            self.extended_squitter_received(&address, AircraftSource::Adsb, &adsb.me, adsb_packet);
+                        }
+                        adsb_deku::DF::TisB { cf, .. } => {
+                            match mode_s::extended_squitter_kind(&adsb_packet.raw_bytes) {
+                                Some((address_type, source)) => {
+                                    let address = AircraftAddress {
+                                        icao: cf.aa,
+                                        address_type,
+                                    };
+                                    self.extended_squitter_received(&address, source, &cf.me, adsb_packet);
+                                }
+                                None => {
+                                    metrics().msg_other.fetch_add(1, AtomicOrdering::Relaxed);
+                                }
                             }
-                            _ => {
-                                metrics().msg_other.fetch_add(1, AtomicOrdering::Relaxed);
+                        }
+                        adsb_deku::DF::AllCallReply { icao, .. } => {
+                            metrics().msg_all_call.fetch_add(1, AtomicOrdering::Relaxed);
+                            let address = AircraftAddress::icao(*icao);
+                            let capability = mode_s::capability(&adsb_packet.raw_bytes);
+                            if self.rate_limiter.is_some() {
+                                self.process_all_call_with_rate_limiting(&address, capability, metadata);
+                            } else {
+                                self.all_call_received(&address, capability, metadata);
                             }
                         }
-
-                        // Process messages through rate limiter if enabled, otherwise process directly
-                        if self.rate_limiter.is_some() {
-                            self.process_message_with_rate_limiting(&adsb.icao, &adsb.me, metadata);
-                        } else {
-                            // Direct processing without rate limiting (legacy behavior)
-                            match &adsb.me {
-                                adsb_deku::adsb::ME::AircraftIdentification(identification) => self
-                                    .aircraft_identification_received(
-                                        &adsb.icao,
-                                        identification,
-                                        metadata,
-                                    ),
-                                adsb_deku::adsb::ME::AirbornePositionBaroAltitude(altitude)
-                                | adsb_deku::adsb::ME::AirbornePositionGNSSAltitude(altitude) => {
-                                    self.airborne_position_received(&adsb.icao, altitude, metadata)
+                        _ => {
+                            // Surveillance replies, ACAS reports in DF16 and Comm-B replies are
+                            // decoded from the raw frame
+                            let address = adsb_packet.address.map(AircraftAddress::icao);
+                            let reply = mode_s::surveillance_reply(&adsb_packet.raw_bytes);
+                            if let Some((address, reply)) = address.zip(reply) {
+                                self.surveillance_reply_received(&address, &reply, metadata);
+                            }
+                            match address.zip(mode_s::resolution_advisory(&adsb_packet.raw_bytes)) {
+                                Some((address, advisory)) => {
+                                    metrics().msg_acas.fetch_add(1, AtomicOrdering::Relaxed);
+                                    self.resolution_advisory_received(&address, advisory, metadata);
                                 }
-                                adsb_deku::adsb::ME::AirborneVelocity(velocity) => {
-                                    self.airborne_velocity_received(&adsb.icao, velocity, metadata)
+                                None => {
+                                    metrics().msg_other.fetch_add(1, AtomicOrdering::Relaxed);
                                 }
-                                _ => (),
                             }
                         }
                     }
@@ -229,30 +426,26 @@ impl Tracker {
         Ok(Pmt::Ok)
     }
 
-    fn update_last_seen(&mut self, icao: &AdsbIcao) {
-        if let Some(rec) = self.aircraft_register.register.get_mut(icao) {
+    fn update_last_seen(&mut self, address: &AircraftAddress) {
+        if let Some(rec) = self.aircraft_register.register.get_mut(address) {
             // Update the time stamp in the register record
             rec.last_seen = SystemTime::now();
         }
     }
 
-    fn register_aircraft(&mut self, icao: &AdsbIcao) {
+    fn register_aircraft(&mut self, address: &AircraftAddress) {
         // Add an aircraft record to our register map
-        let now = SystemTime::now();
-        let record = AircraftRecord {
-            icao: *icao,
-            callsign: None,
-            emitter_category: None,
-            positions: Vec::new(),
-            velocities: Vec::new(),
-            last_cpr_even: None,
-            last_cpr_odd: None,
-            last_seen: now,
-        };
-        if self.aircraft_register.register.contains_key(icao) {
-            warn!("Aircraft {} is already registered and will be reset", icao);
+        let mut record = AircraftRecord::new(address, SystemTime::now());
+        record.info = self.database.lookup(address).unwrap_or_default();
+        if self.aircraft_register.register.contains_key(address) {
+            warn!("Aircraft {} is already registered and will be reset", address);
+        } else if self
+            .max_aircraft
+            .is_some_and(|max| self.aircraft_register.register.len() >= max)
+        {
+            self.evict_least_recently_seen();
         }
-        self.aircraft_register.register.insert(*icao, record);
+        self.aircraft_register.register.insert(*address, record);
 
         // Update aircraft count metric
         metrics().aircraft_tracked.store(
@@ -261,12 +454,58 @@ impl Tracker {
         );
     }
 
+    /// Remove the least recently seen aircraft to stay within the aircraft cap
+    fn evict_least_recently_seen(&mut self) {
+        let oldest = self
+            .aircraft_register
+            .register
+            .iter()
+            .min_by_key(|(_, rec)| rec.last_seen)
+            .map(|(address, _)| *address);
+        if let Some(address) = oldest {
+            debug!("Evicting aircraft {} to stay within the aircraft cap", address);
+            self.finish_flight(&address, FlightEnd::Lost);
+            self.geofences.forget(&address);
+            self.watchlist_lost(&address);
+            self.aircraft_register.register.remove(&address);
+            metrics().aircraft_evicted.fetch_add(1, AtomicOrdering::Relaxed);
+        }
+    }
+
+    /// Apply the retention policy to all aircraft and update the memory metrics
+    fn apply_retention(&mut self) {
+        let now = SystemTime::now();
+        let (mut positions, mut velocities, mut bytes) = (0, 0, 0);
+        for rec in self.aircraft_register.register.values_mut() {
+            if self.retention.is_enabled() {
+                self.retention.apply(rec, now);
+            }
+            positions += rec.positions.len();
+            velocities += rec.velocities.len();
+            bytes += rec.memory_size();
+        }
+        bytes += self.finished_flights.iter().map(FinishedFlight::memory_size).sum::<usize>();
+        metrics().register_positions.store(positions as u64, AtomicOrdering::Relaxed);
+        metrics().register_velocities.store(velocities as u64, AtomicOrdering::Relaxed);
+        metrics().register_bytes.store(bytes as u64, AtomicOrdering::Relaxed);
+    }
+
     fn prune_records(&mut self) {
         if let Some(prune_time) = self.prune_after {
             let now = SystemTime::now();
-            self.aircraft_register
+            let expired: Vec<AircraftAddress> = self
+                .aircraft_register
                 .register
-                .retain(|_, v| v.last_seen + prune_time >= now);
+                .iter()
+                .filter(|(_, v)| v.last_seen + prune_time < now)
+                .map(|(address, _)| *address)
+                .collect();
+            for address in &expired {
+                self.finish_flight(address, FlightEnd::Lost);
+                self.geofences.forget(address);
+                self.watchlist_lost(address);
+                self.aircraft_register.register.remove(address);
+            }
 
             // Update aircraft count metric after pruning
             metrics().aircraft_tracked.store(
@@ -278,36 +517,56 @@ impl Tracker {
 
     fn aircraft_identification_received(
         &mut self,
-        icao: &AdsbIcao,
+        address: &AircraftAddress,
         identification: &AdsbIdentification,
         _metadata: &DecoderMetaData,
     ) {
-        if !self.aircraft_register.register.contains_key(icao) {
-            self.register_aircraft(icao);
+        if !self.aircraft_register.register.contains_key(address) {
+            self.register_aircraft(address);
         }
-        let rec = self.aircraft_register.register.get_mut(icao)
+        let now = SystemTime::now();
+        self.check_flight_boundary(address, now, Some(&identification.cn), None);
+        let rec = self.aircraft_register.register.get_mut(address)
             .expect("Aircraft record should exist after registration");
         rec.callsign = Some(identification.cn.clone());
         rec.emitter_category = Some(identification.ca);
-        self.update_last_seen(icao);
+        rec.updates.identification = Some(FieldUpdate::new(now, StateSource::Identification));
+        let flight = Self::current_flight(rec, &mut self.next_flight_id, now);
+        flight.callsign = Some(identification.cn.clone());
+        flight.last_seen = now;
+        // Look the route up again for a new flight or callsign
+        let renamed = flight
+            .route
+            .as_ref()
+            .is_none_or(|route| route.callsign != identification.cn.trim());
+        let route = renamed.then(|| self.routes.lookup(&identification.cn)).flatten();
+        if renamed {
+            flight.route = route.clone();
+        }
+        let flight_id = flight.id;
+        self.update_last_seen(address);
 
         // Broadcast state update to state-based outputs (SBS-1)
-        if let Some(record) = self.aircraft_register.register.get(icao) {
-            self.output_manager.broadcast_state(icao, record);
+        if let Some(record) = self.aircraft_register.register.get(address) {
+            self.output_manager.broadcast_state(address, record);
+        }
+        if let Some(route) = route {
+            self.raise_event(TrackerEvent::new(*address, TrackerEventKind::RouteChanged { flight: flight_id, route }));
         }
     }
 
     fn airborne_position_received(
         &mut self,
-        icao: &AdsbIcao,
+        address: &AircraftAddress,
         altitude: &AdsbPosition,
         _metadata: &DecoderMetaData,
     ) {
-        if !self.aircraft_register.register.contains_key(icao) {
-            self.register_aircraft(icao);
+        if !self.aircraft_register.register.contains_key(address) {
+            self.register_aircraft(address);
         }
         let now = SystemTime::now();
-        let rec = self.aircraft_register.register.get_mut(icao).unwrap();
+        self.check_flight_boundary(address, now, None, Some(false));
+        let rec = self.aircraft_register.register.get_mut(address).unwrap();
 
         // Update record
         let cpr_rec = CprFrameRecord {
@@ -319,90 +578,946 @@ impl Tracker {
             adsb_deku::CPRFormat::Odd => rec.last_cpr_odd = Some(cpr_rec),
         }
 
-        // Check if we can calculate the position. This requires both an odd
-        // and an even frame.
-        // Make rec immutable
-        let rec = self.aircraft_register.register.get(icao).unwrap();
-        if rec.last_cpr_even.is_some() && rec.last_cpr_odd.is_some() {
-            // The frames must be recent
-            let even_cpr_rec = rec.last_cpr_even.as_ref().unwrap();
-            let odd_cpr_rec = rec.last_cpr_odd.as_ref().unwrap();
-            if even_cpr_rec.time < now + ADSB_TIME_RECENT
-                && odd_cpr_rec.time < now + ADSB_TIME_RECENT
-            {
-                // The CPR frames must be orderd by time
-                let (cpr1, cpr2) = match even_cpr_rec.time.cmp(&odd_cpr_rec.time) {
-                    Ordering::Less => (even_cpr_rec, odd_cpr_rec),
-                    Ordering::Greater | Ordering::Equal => (odd_cpr_rec, even_cpr_rec),
-                };
-                if let Some(pos) = adsb_deku::cpr::get_position((&cpr1.cpr_frame, &cpr2.cpr_frame))
-                {
-                    // We got a position!
-                    // Add it to the record
-                    let new_pos = AircraftPosition {
-                        latitude: pos.latitude,
-                        longitude: pos.longitude,
-                        altitude: altitude.alt,
-                        type_code: altitude.tc,
-                    };
-                    let new_rec = AircraftPositionRecord {
-                        position: new_pos,
+        // Resolve the position from this frame, locally or globally
+        let rec = self.aircraft_register.register.get(address).unwrap();
+        let Some((latitude, longitude, receiver_relative)) =
+            Self::resolve_airborne_position(rec, altitude, self.receiver, self.max_receiver_range_nm, now)
+        else {
+            self.update_last_seen(address);
+            return;
+        };
+
+        // A global fix may correct a position relative to the receiver, which
+        // can then jump by a whole zone
+        let confirms_receiver_fix = rec.receiver_relative && !receiver_relative;
+        if !confirms_receiver_fix && !Self::is_plausible(rec, latitude, longitude, now) {
+            self.position_rejected(address);
+            self.update_last_seen(address);
+            return;
+        }
+
+        // We got a position!
+        let quality = Self::position_quality(rec, altitude.tc, self.min_nic);
+        if quality.low_integrity && self.integrity_policy == IntegrityPolicy::Filter {
+            debug!("Dropping position of {} with NIC {}", address, quality.nic);
+        } else {
+            // Add it to the record
+            let new_pos = AircraftPosition {
+                latitude,
+                longitude,
+                altitude: altitude.alt,
+                type_code: altitude.tc,
+                ground_speed: None,
+                track: None,
+                quality,
+            };
+            let new_rec = AircraftPositionRecord {
+                position: new_pos,
+                time: now,
+                phase: None,
+            };
+            let rec = self.aircraft_register.register.get_mut(address)
+                .expect("Aircraft record should exist after position calculation");
+            rec.positions.push(new_rec);
+            rec.on_ground = Some(false);
+            rec.updates.on_ground = Some(FieldUpdate::new(now, StateSource::position(altitude.tc)));
+            rec.receiver_relative = receiver_relative;
+            rec.rejected_positions = 0;
+            rec.position_reset = false;
+            Self::update_geometric_altitude(rec);
+            Self::filter_position(rec, self.kalman.as_ref());
+            Self::update_flight(rec, &mut self.next_flight_id, now);
+            self.update_receiver_range(address);
+            self.check_geofences(address, now);
+
+            // Broadcast state update to state-based outputs (SBS-1)
+            if let Some(record) = self.aircraft_register.register.get(address) {
+                self.output_manager.broadcast_state(address, record);
+            }
+        }
+        self.update_last_seen(address);
+    }
+
+    /// Compute the distance, bearing and elevation of the last position from
+    /// the receiver, and update the range statistics
+    fn update_receiver_range(&mut self, address: &AircraftAddress) {
+        let Some(receiver) = self.receiver else {
+            return;
+        };
+        let Some(rec) = self.aircraft_register.register.get_mut(address) else {
+            return;
+        };
+        let Some(position) = rec.positions.last().map(|p| &p.position) else {
+            return;
+        };
+        let distance_nm = geo::distance_nm(receiver.latitude, receiver.longitude, position.latitude, position.longitude);
+        let bearing = geo::bearing(receiver.latitude, receiver.longitude, position.latitude, position.longitude);
+        // Prefer the geometric altitude, the receiver altitude is above sea level too
+        let altitude = rec.geometric_altitude.map(f64::from).or(position.altitude.map(f64::from));
+        rec.receiver_range = Some(ReceiverRange {
+            distance_nm,
+            bearing,
+            elevation: altitude.map(|altitude| geo::elevation_angle(distance_nm, altitude - receiver.altitude)),
+        });
+        // Positions relative to the receiver may be aliased, keep them out of the outline
+        if let Some(coverage) = self.coverage.as_mut().filter(|_| !rec.receiver_relative) {
+            coverage.record(bearing, distance_nm, altitude, SystemTime::now());
+        }
+
+        self.max_range_nm = self.max_range_nm.max(distance_nm);
+        self.range_sum_nm += distance_nm;
+        self.range_count += 1;
+        metrics().max_range_nm.store(self.max_range_nm.to_bits(), AtomicOrdering::Relaxed);
+        metrics().avg_range_nm.store(
+            (self.range_sum_nm / self.range_count as f64).to_bits(),
+            AtomicOrdering::Relaxed,
+        );
+    }
+
+    /// Check the last position of an aircraft against the geofences and
+    /// raise an event with the aircraft state for every transition
+    fn check_geofences(&mut self, address: &AircraftAddress, now: SystemTime) {
+        let Some(rec) = self.aircraft_register.register.get(address) else {
+            return;
+        };
+        let Some(position) = rec.positions.last().map(|p| &p.position) else {
+            return;
+        };
+        let altitude = position.altitude.map(i32::from).or(rec.geometric_altitude);
+        let changes = self
+            .geofences
+            .update(*address, position.latitude, position.longitude, altitude, now);
+        if changes.is_empty() {
+            return;
+        }
+
+        let state = Box::new(rec.state(now, self.stale_after));
+        for change in changes {
+            info!("Aircraft {} geofence {}: {:?}", address, change.fence, change.transition);
+            let (fence, duration, state) = (change.fence, change.duration.as_secs_f64(), state.clone());
+            let kind = match change.transition {
+                GeofenceTransition::Enter => TrackerEventKind::GeofenceEnter { fence, state },
+                GeofenceTransition::Exit => TrackerEventKind::GeofenceExit { fence, duration, state },
+                GeofenceTransition::Dwell => TrackerEventKind::GeofenceDwell { fence, duration, state },
+            };
+            self.raise_event(TrackerEvent::new(*address, kind));
+        }
+    }
+
+    /// Flag the aircraft on the watchlist and raise an event when one
+    /// appears, changes or no longer matches
+    fn check_watchlist(&mut self, now: SystemTime) {
+        if self.watchlist.is_empty() {
+            return;
+        }
+        let mut events = Vec::new();
+        for (address, rec) in self.aircraft_register.register.iter_mut() {
+            let entry = self.watchlist.find(address, rec.callsign.as_deref()).map(WatchEntry::name);
+            let watched = WatchedState::new(rec);
+            let kind = match (&entry, &rec.watchlist) {
+                (Some(entry), None) => Some(TrackerEventKind::WatchlistAppeared {
+                    entry: entry.clone(),
+                    state: Box::new(rec.state(now, self.stale_after)),
+                }),
+                (Some(entry), Some(previous)) if entry != previous || rec.watched.as_ref() != Some(&watched) => {
+                    Some(TrackerEventKind::WatchlistChanged {
+                        entry: entry.clone(),
+                        state: Box::new(rec.state(now, self.stale_after)),
+                    })
+                }
+                (None, Some(previous)) => Some(TrackerEventKind::WatchlistDisappeared { entry: previous.clone() }),
+                _ => None,
+            };
+            rec.watched = entry.is_some().then_some(watched);
+            rec.watchlist = entry;
+            events.extend(kind.map(|kind| TrackerEvent::new(*address, kind)));
+        }
+        for event in events {
+            info!("Watchlist event for aircraft {}", event.address);
+            self.raise_event(event);
+        }
+    }
+
+    /// Report that an aircraft on the watchlist is removed from the register
+    fn watchlist_lost(&mut self, address: &AircraftAddress) {
+        let entry = self
+            .aircraft_register
+            .register
+            .get_mut(address)
+            .and_then(|rec| rec.watchlist.take());
+        if let Some(entry) = entry {
+            info!("Watchlist aircraft {} lost", address);
+            self.raise_event(TrackerEvent::new(*address, TrackerEventKind::WatchlistDisappeared { entry }));
+        }
+    }
+
+    /// Resolve the position of an airborne CPR frame
+    ///
+    /// The frame is decoded locally relative to the last position while that
+    /// is recent. Without one, an even/odd pair is decoded globally, and as a
+    /// last resort the receiver location serves as the reference. Positions
+    /// relative to the receiver are replaced by a global fix once a pair is
+    /// available, and rejected beyond `max_range_nm` of the receiver. Returns
+    /// latitude, longitude and whether the position is relative to the
+    /// receiver.
+    fn resolve_airborne_position(
+        rec: &AircraftRecord,
+        frame: &AdsbPosition,
+        receiver: Option<ReceiverLocation>,
+        max_range_nm: f64,
+        now: SystemTime,
+    ) -> Option<(f64, f64, bool)> {
+        let cpr_frame = cpr::CprFrame {
+            lat_cpr: frame.lat_cpr,
+            lon_cpr: frame.lon_cpr,
+            odd: matches!(frame.odd_flag, adsb_deku::CPRFormat::Odd),
+        };
+        let reference = rec
+            .positions
+            .last()
+            .filter(|p| !rec.position_reset && now.duration_since(p.time).is_ok_and(|age| age < LOCAL_CPR_MAX_AGE))
+            .map(|p| (p.position.latitude, p.position.longitude));
+
+        if let Some((ref_lat, ref_lon)) = reference.filter(|_| !rec.receiver_relative) {
+            let (lat, lon) = cpr::local_position(&cpr_frame, false, ref_lat, ref_lon);
+            return Some((lat, lon, false));
+        }
+        if let Some((lat, lon)) = Self::global_airborne_position(rec, now) {
+            return Some((lat, lon, false));
+        }
+        reference
+            .or(receiver.map(|r| (r.latitude, r.longitude)))
+            .map(|(ref_lat, ref_lon)| cpr::local_position(&cpr_frame, false, ref_lat, ref_lon))
+            .filter(|&(lat, lon)| {
+                receiver.is_none_or(|r| geo::distance_nm(r.latitude, r.longitude, lat, lon) <= max_range_nm)
+            })
+            .map(|(lat, lon)| (lat, lon, true))
+    }
+
+    /// Resolve the position of a surface position frame
+    ///
+    /// Surface frames are decoded locally, relative to the last position
+    /// while that is recent, or else relative to the receiver. Surface zones
+    /// are a quarter of the airborne ones, so positions relative to the
+    /// receiver are rejected beyond the 45 NM half zone. Returns latitude,
+    /// longitude and whether the position is relative to the receiver.
+    fn resolve_surface_position(
+        rec: &AircraftRecord,
+        frame: &cpr::CprFrame,
+        receiver: Option<ReceiverLocation>,
+        now: SystemTime,
+    ) -> Option<(f64, f64, bool)> {
+        let reference = rec
+            .positions
+            .last()
+            .filter(|p| !rec.position_reset && now.duration_since(p.time).is_ok_and(|age| age < LOCAL_CPR_MAX_AGE))
+            .map(|p| (p.position.latitude, p.position.longitude));
+
+        if let Some((ref_lat, ref_lon)) = reference.filter(|_| !rec.receiver_relative) {
+            let (lat, lon) = cpr::local_position(frame, true, ref_lat, ref_lon);
+            return Some((lat, lon, false));
+        }
+        reference
+            .or(receiver.map(|r| (r.latitude, r.longitude)))
+            .map(|(ref_lat, ref_lon)| cpr::local_position(frame, true, ref_lat, ref_lon))
+            .filter(|&(lat, lon)| {
+                receiver.is_none_or(|r| geo::distance_nm(r.latitude, r.longitude, lat, lon) <= SURFACE_HALF_ZONE_NM)
+            })
+            .map(|(lat, lon)| (lat, lon, true))
+    }
+
+    /// Check that a position fix does not imply an impossible ground speed
+    /// from the last position
+    fn is_plausible(rec: &AircraftRecord, latitude: f64, longitude: f64, now: SystemTime) -> bool {
+        let Some(last) = rec.positions.last().filter(|_| !rec.position_reset) else {
+            return true;
+        };
+        let elapsed = now.duration_since(last.time).unwrap_or_default().as_secs_f64();
+        let distance = geo::distance_nm(last.position.latitude, last.position.longitude, latitude, longitude);
+        distance <= POSITION_SLACK_NM + MAX_PLAUSIBLE_SPEED * elapsed / 3600.0
+    }
+
+    /// Count an implausible position fix, and start over from a fresh global
+    /// decode when they keep coming
+    fn position_rejected(&mut self, address: &AircraftAddress) {
+        metrics().positions_rejected.fetch_add(1, AtomicOrdering::Relaxed);
+        let Some(rec) = self.aircraft_register.register.get_mut(address) else {
+            return;
+        };
+        rec.rejected_positions += 1;
+        debug!("Rejected implausible position of {} ({} in a row)", address, rec.rejected_positions);
+        if rec.rejected_positions >= MAX_REJECTED_POSITIONS {
+            info!("Re-initialising position of {} after {} implausible fixes", address, rec.rejected_positions);
+            // The trail is kept, the next position is a fresh decode
+            rec.position_reset = true;
+            rec.track_filter = None;
+            rec.track_estimate = None;
+            rec.last_cpr_even = None;
+            rec.last_cpr_odd = None;
+            rec.receiver_relative = false;
+            rec.rejected_positions = 0;
+        }
+    }
+
+    /// Decode the position globally from the last even and odd frames
+    fn global_airborne_position(rec: &AircraftRecord, now: SystemTime) -> Option<(f64, f64)> {
+        // The frames must be recent
+        let even_cpr_rec = rec.last_cpr_even.as_ref()?;
+        let odd_cpr_rec = rec.last_cpr_odd.as_ref()?;
+        let age = |time: SystemTime| now.duration_since(time).unwrap_or_default();
+        let apart = even_cpr_rec.time.duration_since(odd_cpr_rec.time)
+            .or_else(|_| odd_cpr_rec.time.duration_since(even_cpr_rec.time))
+            .unwrap_or_default();
+        // Both frames must be recent and close to each other, or an old frame
+        // could be paired with a new one from far away
+        if age(even_cpr_rec.time) <= ADSB_TIME_RECENT
+            && age(odd_cpr_rec.time) <= ADSB_TIME_RECENT
+            && apart <= ADSB_TIME_RECENT
+        {
+            // The CPR frames must be orderd by time
+            let (cpr1, cpr2) = match even_cpr_rec.time.cmp(&odd_cpr_rec.time) {
+                Ordering::Less => (even_cpr_rec, odd_cpr_rec),
+                Ordering::Greater | Ordering::Equal => (odd_cpr_rec, even_cpr_rec),
+            };
+            adsb_deku::cpr::get_position((&cpr1.cpr_frame, &cpr2.cpr_frame))
+                .map(|pos| (pos.latitude, pos.longitude))
+        } else {
+            None
+        }
+    }
+
+    fn airborne_velocity_received(
+        &mut self,
+        address: &AircraftAddress,
+        velocity: &AircraftVelocity,
+        _metadata: &DecoderMetaData,
+    ) {
+        if !self.aircraft_register.register.contains_key(address) {
+            self.register_aircraft(address);
+        }
+        let now = SystemTime::now();
+        let rec = self.aircraft_register.register.get_mut(address)
+            .expect("Aircraft record should exist after registration");
+        rec.velocities.push(AircraftVelocityRecord {
+            velocity: velocity.clone(),
+            time: now,
+        });
+        Self::update_geometric_altitude(rec);
+        Self::filter_velocity(rec);
+
+        // Broadcast state update to state-based outputs (SBS-1)
+        if let Some(record) = self.aircraft_register.register.get(address) {
+            self.output_manager.broadcast_state(address, record);
+        }
+        self.update_last_seen(address);
+    }
+
+    /// The current flight of an aircraft, starting a new one if it has none
+    fn current_flight<'a>(rec: &'a mut AircraftRecord, next_flight_id: &mut u64, now: SystemTime) -> &'a mut Flight {
+        rec.flight.get_or_insert_with(|| {
+            let id = *next_flight_id;
+            *next_flight_id += 1;
+            Flight::new(id, rec.callsign.clone(), now)
+        })
+    }
+
+    /// Classify the phase of the last position and record it in the flight
+    fn update_flight(rec: &mut AircraftRecord, next_flight_id: &mut u64, now: SystemTime) {
+        let Some(last) = rec.positions.last() else {
+            return;
+        };
+        let velocity = rec
+            .velocities
+            .last()
+            .filter(|v| now.duration_since(v.time).unwrap_or_default() <= ADSB_TIME_RECENT)
+            .map(|v| &v.velocity);
+        let on_ground = rec.on_ground == Some(true);
+        let altitude = last.position.altitude.map(i32::from);
+        let ground_speed = last.position.ground_speed.or(velocity.and_then(|v| v.ground_speed));
+        let vertical_rate = velocity.and_then(|v| v.vertical_rate);
+
+        let flight = Self::current_flight(rec, next_flight_id, now);
+        let phase = FlightPhase::classify(on_ground, altitude, vertical_rate, ground_speed, flight.phase);
+        flight.record(phase, on_ground, altitude, now);
+        if let Some(last) = rec.positions.last_mut() {
+            last.phase = Some(phase);
+        }
+    }
+
+    /// Finish the current flight of an aircraft if an update at `now`, with
+    /// a callsign or air/ground state, starts a new one
+    fn check_flight_boundary(
+        &mut self,
+        address: &AircraftAddress,
+        now: SystemTime,
+        callsign: Option<&str>,
+        on_ground: Option<bool>,
+    ) {
+        let end = self
+            .aircraft_register
+            .register
+            .get(address)
+            .and_then(|rec| rec.flight.as_ref())
+            .and_then(|flight| flight.end(now, self.flight_gap, callsign, on_ground));
+        if let Some(end) = end {
+            self.finish_flight(address, end);
+        }
+    }
+
+    /// Move the current flight of an aircraft and its positions to the
+    /// finished-flight log
+    fn finish_flight(&mut self, address: &AircraftAddress, end: FlightEnd) {
+        let Some(rec) = self.aircraft_register.register.get_mut(address) else {
+            return;
+        };
+        let Some(flight) = rec.flight.take() else {
+            return;
+        };
+        // The trail of a finished flight is kept within the same limits
+        if self.retention.is_enabled() {
+            self.retention.apply(rec, SystemTime::now());
+        }
+        let mut positions = std::mem::take(&mut rec.positions);
+        positions.shrink_to_fit();
+        // The next flight starts a new track
+        rec.track_filter = None;
+        rec.track_estimate = None;
+        rec.receiver_relative = false;
+
+        debug!("Flight {} of {} ended: {:?}", flight.id, address, end);
+        let kind = TrackerEventKind::FlightFinished {
+            flight: flight.id,
+            callsign: flight.callsign.clone(),
+            end,
+        };
+        self.raise_event(TrackerEvent::new(*address, kind));
+        self.finished_flights.push_back(FinishedFlight {
+            address: address.to_string(),
+            flight,
+            end,
+            positions,
+        });
+        if self.finished_flights.len() > MAX_FINISHED_FLIGHTS {
+            self.finished_flights.pop_front();
+        }
+    }
+
+    /// Feed the last position to the track filter, starting a new filter if
+    /// there is none or it has not been updated for too long
+    fn filter_position(rec: &mut AircraftRecord, config: Option<&KalmanConfig>) {
+        let Some(config) = config else {
+            return;
+        };
+        let Some(last) = rec.positions.last() else {
+            return;
+        };
+        let position = &last.position;
+        let noise = position
+            .quality
+            .nac_p
+            .and_then(kalman::nac_p_noise_nm)
+            .unwrap_or(config.position_noise_nm);
+        // The vertical axis tracks the barometric altitude, GNSS altitude
+        // positions (TC 20-22) leave it alone
+        let altitude = position
+            .altitude
+            .filter(|_| !(20..=22).contains(&position.type_code))
+            .map(f64::from);
+        match rec.track_filter.as_mut().filter(|filter| !filter.is_expired(last.time)) {
+            Some(filter) => filter.update_position(position.latitude, position.longitude, altitude, noise, last.time),
+            None => {
+                rec.track_filter = Some(TrackFilter::new(
+                    position.latitude,
+                    position.longitude,
+                    altitude,
+                    noise,
+                    last.time,
+                    config.clone(),
+                ));
+            }
+        }
+        // Surface positions carry the movement too
+        if let Some((filter, (speed, track))) = rec
+            .track_filter
+            .as_mut()
+            .zip(position.ground_speed.zip(position.track))
+        {
+            filter.update_velocity(speed, track, None, last.time);
+        }
+        rec.track_estimate = rec.track_filter.as_ref().map(TrackFilter::estimate);
+    }
+
+    /// Feed the last velocity to the track filter, airspeeds are not used
+    fn filter_velocity(rec: &mut AircraftRecord) {
+        let Some(last) = rec.velocities.last() else {
+            return;
+        };
+        let Some(filter) = rec.track_filter.as_mut().filter(|filter| !filter.is_expired(last.time)) else {
+            return;
+        };
+        let velocity = &last.velocity;
+        if let Some((speed, track)) = velocity.ground_speed.zip(velocity.heading) {
+            filter.update_velocity(speed, track, velocity.vertical_rate.map(f64::from), last.time);
+            rec.track_estimate = Some(filter.estimate());
+        }
+    }
+
+    /// Update the geometric altitude from the latest position and velocity
+    ///
+    /// GNSS altitude positions (TC 20-22) report it directly, otherwise it is
+    /// the barometric altitude plus the difference from the velocity message.
+    fn update_geometric_altitude(rec: &mut AircraftRecord) {
+        let Some(last) = rec.positions.last() else {
+            return;
+        };
+        let Some(altitude) = last.position.altitude.map(i32::from) else {
+            return;
+        };
+        let (geometric_altitude, update) = if (20..=22).contains(&last.position.type_code) {
+            (Some(altitude), FieldUpdate::new(last.time, StateSource::AirbornePositionGnss))
+        } else {
+            // An estimate is as recent as the later of both messages
+            let velocity = rec.velocities.last();
+            let time = velocity.map_or(last.time, |v| v.time.max(last.time));
+            (
+                velocity
+                    .and_then(|v| v.velocity.geo_minus_baro)
+                    .map(|difference| altitude + i32::from(difference)),
+                FieldUpdate::new(time, StateSource::AirborneVelocity),
+            )
+        };
+        rec.geometric_altitude = geometric_altitude;
+        rec.updates.geometric_altitude = geometric_altitude.map(|_| update);
+    }
+
+    fn surface_position_received(
+        &mut self,
+        address: &AircraftAddress,
+        report: &SurfacePositionReport,
+        _metadata: &DecoderMetaData,
+    ) {
+        if !self.aircraft_register.register.contains_key(address) {
+            self.register_aircraft(address);
+        }
+        let now = SystemTime::now();
+        self.check_flight_boundary(address, now, None, Some(true));
+        let rec = self.aircraft_register.register.get_mut(address)
+            .expect("Aircraft record should exist after registration");
+        rec.on_ground = Some(true);
+        rec.updates.on_ground = Some(FieldUpdate::new(now, StateSource::SurfacePosition));
+
+        let quality = Self::position_quality(rec, report.type_code, self.min_nic);
+        match Self::resolve_surface_position(rec, &report.frame, self.receiver, now) {
+            Some(_) if quality.low_integrity && self.integrity_policy == IntegrityPolicy::Filter => {
+                debug!("Dropping surface position of {} with NIC {}", address, quality.nic);
+            }
+            Some((latitude, longitude, receiver_relative)) => {
+                if !Self::is_plausible(rec, latitude, longitude, now) {
+                    self.position_rejected(address);
+                } else {
+                    rec.positions.push(AircraftPositionRecord {
+                        position: AircraftPosition {
+                            latitude,
+                            longitude,
+                            altitude: None,
+                            type_code: report.type_code,
+                            ground_speed: report.ground_speed,
+                            track: report.track,
+                            quality,
+                        },
                         time: now,
-                    };
-                    let rec = self.aircraft_register.register.get_mut(icao)
-                        .expect("Aircraft record should exist after position calculation");
-                    rec.positions.push(new_rec);
-
-                    // Broadcast state update to state-based outputs (SBS-1)
-                    if let Some(record) = self.aircraft_register.register.get(icao) {
-                        self.output_manager.broadcast_state(icao, record);
+                        phase: None,
+                    });
+                    rec.receiver_relative = receiver_relative;
+                    rec.rejected_positions = 0;
+                    rec.position_reset = false;
+                    Self::filter_position(rec, self.kalman.as_ref());
+                    Self::update_flight(rec, &mut self.next_flight_id, now);
+                    self.update_receiver_range(address);
+                    self.check_geofences(address, now);
+
+                    // Broadcast state update to state-based outputs (SBS-1 MSG,2)
+                    if let Some(record) = self.aircraft_register.register.get(address) {
+                        self.output_manager.broadcast_state(address, record);
                     }
                 }
             }
+            None => {
+                debug!("No usable reference to decode the surface position of {}", address);
+            }
         }
-        self.update_last_seen(icao);
+        self.update_last_seen(address);
     }
 
-    fn airborne_velocity_received(
+    fn aircraft_status_received(
         &mut self,
-        icao: &AdsbIcao,
-        velocity: &AdsbVelocity,
+        address: &AircraftAddress,
+        report: &AircraftStatusReport,
         _metadata: &DecoderMetaData,
     ) {
-        if !self.aircraft_register.register.contains_key(icao) {
-            self.register_aircraft(icao);
+        if !self.aircraft_register.register.contains_key(address) {
+            self.register_aircraft(address);
         }
+        let rec = self.aircraft_register.register.get_mut(address)
+            .expect("Aircraft record should exist after registration");
+
         let now = SystemTime::now();
-        // Calculate the velocity
-        if let Some((heading, ground_speed, vertical_rate)) = velocity.calculate() {
-            // Add it to the record
-            let new_velocity = AircraftVelocity {
-                heading: heading as f64,
-                ground_speed,
-                vertical_rate,
-                vertical_rate_source: match velocity.vrate_src {
-                    adsb_deku::adsb::VerticalRateSource::BarometricPressureAltitude => {
-                        AircraftVerticalRateSource::BarometricPressureAltitude
-                    }
-                    adsb_deku::adsb::VerticalRateSource::GeometricAltitude => {
-                        AircraftVerticalRateSource::GeometricAltitude
-                    }
-                },
-            };
-            let new_record = AircraftVelocityRecord {
-                velocity: new_velocity,
-                time: now,
-            };
-            let rec = self.aircraft_register.register.get_mut(icao)
-                .expect("Aircraft record should exist after velocity calculation");
-            rec.velocities.push(new_record);
+        rec.squawk = Some(report.squawk);
+        rec.updates.squawk = Some(FieldUpdate::new(now, StateSource::AircraftStatus));
+        rec.emergency = Some(report.emergency);
+        self.update_last_seen(address);
 
-            // Broadcast state update to state-based outputs (SBS-1)
-            if let Some(record) = self.aircraft_register.register.get(icao) {
-                self.output_manager.broadcast_state(icao, record);
+        let squawk = Some(AlertCondition::EmergencySquawk { squawk: report.squawk })
+            .filter(|_| is_emergency_squawk(report.squawk));
+        self.report_alert(address, AlertKind::Squawk, squawk, now);
+        let emergency = Some(AlertCondition::EmergencyStatus { state: report.emergency })
+            .filter(|_| report.emergency.is_emergency());
+        self.report_alert(address, AlertKind::EmergencyStatus, emergency, now);
+
+        // Broadcast state update to state-based outputs (SBS-1 MSG,6)
+        if let Some(record) = self.aircraft_register.register.get(address) {
+            self.output_manager.broadcast_state(address, record);
+        }
+    }
+
+    fn target_state_received(
+        &mut self,
+        address: &AircraftAddress,
+        navigation: AircraftNavigation,
+        _metadata: &DecoderMetaData,
+    ) {
+        if !self.aircraft_register.register.contains_key(address) {
+            self.register_aircraft(address);
+        }
+        let rec = self.aircraft_register.register.get_mut(address)
+            .expect("Aircraft record should exist after registration");
+
+        // The message repeats while the intent is unchanged, only notify changes
+        let changed = rec.navigation.as_ref() != Some(&navigation);
+        rec.navigation = Some(navigation.clone());
+        self.update_last_seen(address);
+
+        if changed {
+            let event = TrackerEvent::new(*address, TrackerEventKind::NavigationChanged(navigation));
+            self.raise_event(event);
+
+            if let Some(record) = self.aircraft_register.register.get(address) {
+                self.output_manager.broadcast_state(address, record);
+            }
+        }
+    }
+
+    /// Derive the quality indicators of a position from the aircraft's
+    /// operational status and the supplements it has reported
+    fn position_quality(rec: &AircraftRecord, type_code: u8, min_nic: Option<u8>) -> PositionQuality {
+        let status = rec.operational_status.as_ref();
+        let nic = mode_s::nic(
+            type_code,
+            status.map_or(0, |s| s.version),
+            status.is_some_and(|s| s.nic_supplement_a),
+            rec.nic_supplement_b,
+            status.and_then(|s| s.nic_supplement_c).unwrap_or(false),
+        );
+        let surface = (5..=8).contains(&type_code);
+        PositionQuality {
+            nic,
+            nac_p: status.and_then(|s| s.nac_p),
+            nac_v: if surface {
+                status.and_then(|s| s.nac_v)
+            } else {
+                rec.velocity_nac
+            },
+            sil: status.and_then(|s| s.sil),
+            sda: status.and_then(|s| s.sda),
+            version: status.map(|s| s.version),
+            low_integrity: min_nic.is_some_and(|min_nic| nic < min_nic),
+        }
+    }
+
+    fn operational_status_received(
+        &mut self,
+        address: &AircraftAddress,
+        status: OperationalStatus,
+        _metadata: &DecoderMetaData,
+    ) {
+        if !self.aircraft_register.register.contains_key(address) {
+            self.register_aircraft(address);
+        }
+        let rec = self.aircraft_register.register.get_mut(address)
+            .expect("Aircraft record should exist after registration");
+        if rec.operational_status.as_ref().is_none_or(|s| s.version != status.version) {
+            debug!("Aircraft {} reports ADS-B version {}", address, status.version);
+        }
+        rec.operational_status = Some(status);
+        self.update_last_seen(address);
+    }
+
+    fn all_call_received(
+        &mut self,
+        address: &AircraftAddress,
+        capability: u8,
+        _metadata: &DecoderMetaData,
+    ) {
+        if !self.aircraft_register.register.contains_key(address) {
+            self.register_aircraft(address);
+        }
+        let rec = self.aircraft_register.register.get_mut(address)
+            .expect("Aircraft record should exist after registration");
+        rec.capability = Some(capability);
+        // CA 4 and 5 report the air/ground state, other values leave it open
+        let on_ground = match capability {
+            4 => Some(true),
+            5 => Some(false),
+            _ => None,
+        };
+        if on_ground.is_some() {
+            rec.on_ground = on_ground;
+            rec.updates.on_ground = Some(FieldUpdate::new(SystemTime::now(), StateSource::AllCall));
+        }
+        self.update_last_seen(address);
+
+        // Broadcast the reply to state-based outputs (SBS-1 MSG,8)
+        if let Some(record) = self.aircraft_register.register.get(address) {
+            self.output_manager.broadcast_all_call(address, record);
+        }
+    }
+
+    fn resolution_advisory_received(
+        &mut self,
+        address: &AircraftAddress,
+        advisory: ResolutionAdvisory,
+        _metadata: &DecoderMetaData,
+    ) {
+        if !self.aircraft_register.register.contains_key(address) {
+            self.register_aircraft(address);
+        }
+        let rec = self.aircraft_register.register.get_mut(address)
+            .expect("Aircraft record should exist after registration");
+
+        // The report is repeated while the RA is active, only act on changes
+        let changed = rec
+            .resolution_advisory
+            .as_ref()
+            .is_none_or(|current| current.advisory != advisory);
+        if changed {
+            if let Some(previous) = rec.resolution_advisory.take() {
+                rec.resolution_advisory_history.push(previous);
+                if rec.resolution_advisory_history.len() > MAX_RA_HISTORY {
+                    rec.resolution_advisory_history.remove(0);
+                }
             }
+            rec.resolution_advisory = Some(ResolutionAdvisoryRecord {
+                advisory: advisory.clone(),
+                time: SystemTime::now(),
+            });
+        }
+        self.update_last_seen(address);
+
+        if changed {
+            info!(
+                "Aircraft {} reported ACAS RA: {:?}{}",
+                address,
+                advisory.advisories,
+                if advisory.terminated { " (terminated)" } else { "" }
+            );
+            let event = TrackerEvent::new(*address, TrackerEventKind::ResolutionAdvisory(advisory));
+            self.raise_event(event);
+        }
+    }
+
+    fn surveillance_reply_received(
+        &mut self,
+        address: &AircraftAddress,
+        reply: &SurveillanceReply,
+        _metadata: &DecoderMetaData,
+    ) {
+        if !self.aircraft_register.register.contains_key(address) {
+            self.register_aircraft(address);
+        }
+        let rec = self.aircraft_register.register.get_mut(address)
+            .expect("Aircraft record should exist after registration");
+
+        let now = SystemTime::now();
+        let update = FieldUpdate::new(now, StateSource::SurveillanceReply);
+        let mut changed = rec.spi != reply.spi();
+        rec.spi = reply.spi();
+        if let Some(squawk) = reply.squawk {
+            changed |= rec.squawk != Some(squawk);
+            rec.squawk = Some(squawk);
+            rec.updates.squawk = Some(update);
+        }
+        if let Some(on_ground) = reply.on_ground() {
+            changed |= rec.on_ground != Some(on_ground);
+            rec.on_ground = Some(on_ground);
+            rec.updates.on_ground = Some(update);
+        }
+        self.update_last_seen(address);
+
+        // Altitude replies do not carry the squawk, so they leave its alert alone
+        if let Some(squawk) = reply.squawk {
+            let condition = Some(AlertCondition::EmergencySquawk { squawk }).filter(|_| is_emergency_squawk(squawk));
+            self.report_alert(address, AlertKind::Squawk, condition, now);
+        }
+        let spi = Some(AlertCondition::Spi).filter(|_| reply.spi());
+        self.report_alert(address, AlertKind::Spi, spi, now);
+
+        // The reply is repeated on every interrogation, only broadcast changes
+        if !changed {
+            return;
+        }
+        if let Some(record) = self.aircraft_register.register.get(address) {
+            self.output_manager.broadcast_state(address, record);
+        }
+    }
+
+    /// Report whether a special condition is present and raise the events of
+    /// the alerts that started or ended
+    fn report_alert(
+        &mut self,
+        address: &AircraftAddress,
+        kind: AlertKind,
+        condition: Option<AlertCondition>,
+        now: SystemTime,
+    ) {
+        for change in self.alerts.report(*address, kind, condition, now) {
+            self.alert_changed(change);
+        }
+    }
+
+    fn alert_changed(&mut self, change: AlertChange) {
+        let event = match change {
+            AlertChange::Started(alert) => {
+                warn!("Aircraft {} alert started: {:?}", alert.address, alert.condition);
+                let kind = TrackerEventKind::AlertStarted {
+                    condition: alert.condition,
+                    start: alert.start,
+                };
+                TrackerEvent::new(alert.address, kind)
+            }
+            AlertChange::Ended(alert) => {
+                info!("Aircraft {} alert ended: {:?}", alert.address, alert.condition);
+                let kind = TrackerEventKind::AlertEnded {
+                    condition: alert.condition,
+                    start: alert.start,
+                    end: alert.end.unwrap_or(alert.last_seen),
+                };
+                TrackerEvent::new(alert.address, kind)
+            }
+        };
+        self.raise_event(event);
+    }
+
+    /// Pass an event to the output modules and keep alerts in the event log
+    fn raise_event(&mut self, event: TrackerEvent) {
+        self.output_manager.broadcast_event(&event);
+        if event.kind.is_alert() {
+            self.events.push_back(event);
+            if self.events.len() > MAX_EVENTS {
+                self.events.pop_front();
+            }
+        }
+    }
+
+    /// Handle the ME field of a DF17 or DF18 extended squitter
+    fn extended_squitter_received(
+        &mut self,
+        address: &AircraftAddress,
+        source: AircraftSource,
+        me: &adsb_deku::adsb::ME,
+        adsb_packet: &AdsbPacket,
+    ) {
+        let metadata = &adsb_packet.decoder_metadata;
+
+        // Update metrics based on message type
+        match me {
+            adsb_deku::adsb::ME::AircraftIdentification(_) => {
+                metrics().msg_identification.fetch_add(1, AtomicOrdering::Relaxed);
+            }
+            adsb_deku::adsb::ME::AirbornePositionBaroAltitude(_)
+            | adsb_deku::adsb::ME::AirbornePositionGNSSAltitude(_)
+            | adsb_deku::adsb::ME::SurfacePosition(_) => {
+                metrics().msg_position.fetch_add(1, AtomicOrdering::Relaxed);
+            }
+            adsb_deku::adsb::ME::AirborneVelocity(_) => {
+                metrics().msg_velocity.fetch_add(1, AtomicOrdering::Relaxed);
+            }
+            adsb_deku::adsb::ME::AircraftStatus(_)
+            | adsb_deku::adsb::ME::TargetStateAndStatusInformation(_)
+            | adsb_deku::adsb::ME::AircraftOperationStatus(_) => {
+                metrics().msg_status.fetch_add(1, AtomicOrdering::Relaxed);
+            }
+            _ => {
+                metrics().msg_other.fetch_add(1, AtomicOrdering::Relaxed);
+            }
+        }
+
+        // Take the quality supplements that adsb_deku does not decode before
+        // the message is handled, so that they qualify the position or
+        // velocity of this frame
+        let supplement_b = mode_s::nic_supplement_b(&adsb_packet.raw_bytes);
+        let nac_v = mode_s::velocity_nac(&adsb_packet.raw_bytes);
+        if supplement_b.is_some() || nac_v.is_some() {
+            if !self.aircraft_register.register.contains_key(address) {
+                self.register_aircraft(address);
+            }
+            let rec = self.aircraft_register.register.get_mut(address)
+                .expect("Aircraft record should exist after registration");
+            if let Some(supplement_b) = supplement_b {
+                rec.nic_supplement_b = supplement_b;
+            }
+            if nac_v.is_some() {
+                rec.velocity_nac = nac_v;
+            }
+        }
+
+        // Process messages through rate limiter if enabled, otherwise process directly.
+        // Status and intent messages are infrequent and never delayed by the rate limiter.
+        match me {
+            adsb_deku::adsb::ME::AircraftStatus(_) => {
+                if let Some(report) = mode_s::aircraft_status(&adsb_packet.raw_bytes) {
+                    self.aircraft_status_received(address, &report, metadata);
+                }
+            }
+            adsb_deku::adsb::ME::TargetStateAndStatusInformation(_) => {
+                if let Some(navigation) = mode_s::target_state(&adsb_packet.raw_bytes) {
+                    self.target_state_received(address, navigation, metadata);
+                }
+            }
+            adsb_deku::adsb::ME::AircraftOperationStatus(_) => {
+                if let Some(status) = mode_s::operational_status(&adsb_packet.raw_bytes) {
+                    self.operational_status_received(address, status, metadata);
+                }
+            }
+            _ if self.rate_limiter.is_some() => {
+                self.process_message_with_rate_limiting(address, me, adsb_packet);
+            }
+            // Direct processing without rate limiting (legacy behavior)
+            adsb_deku::adsb::ME::AircraftIdentification(identification) => {
+                self.aircraft_identification_received(address, identification, metadata)
+            }
+            adsb_deku::adsb::ME::AirbornePositionBaroAltitude(altitude)
+            | adsb_deku::adsb::ME::AirbornePositionGNSSAltitude(altitude) => {
+                self.airborne_position_received(address, altitude, metadata)
+            }
+            adsb_deku::adsb::ME::AirborneVelocity(_) => {
+                // Airspeed subtypes are decoded from the raw message
+                if let Some(velocity) = mode_s::airborne_velocity(&adsb_packet.raw_bytes) {
+                    self.airborne_velocity_received(address, &velocity, metadata)
+                }
+            }
+            adsb_deku::adsb::ME::SurfacePosition(_) => {
+                // Movement and track are decoded from the raw message
+                if let Some(report) = mode_s::surface_position(&adsb_packet.raw_bytes) {
+                    self.surface_position_received(address, &report, metadata)
+                }
+            }
+            _ => (),
+        }
+
+        // Remember where the latest extended squitter for this target came from
+        if let Some(rec) = self.aircraft_register.register.get_mut(address) {
+            rec.source = source;
         }
-        self.update_last_seen(icao);
     }
 
     /// Broadcast an ADS-B packet via all enabled output modules
@@ -413,17 +1528,18 @@ impl Tracker {
     /// Process a message through the rate limiter
     fn process_message_with_rate_limiting(
         &mut self,
-        icao: &AdsbIcao,
+        address: &AircraftAddress,
         me: &adsb_deku::adsb::ME,
-        metadata: &DecoderMetaData,
+        adsb_packet: &AdsbPacket,
     ) {
+        let metadata = &adsb_packet.decoder_metadata;
         let rate_limiter = self.rate_limiter.as_mut().unwrap();
         match me {
             adsb_deku::adsb::ME::AircraftIdentification(identification) => {
                 let update_data = TrackerUpdateData::Identification(identification.clone());
-                match rate_limiter.process_update(*icao, UpdateType::Identification, update_data) {
+                match rate_limiter.process_update(*address, UpdateType::Identification, update_data) {
                     RateLimitResult::Allowed(TrackerUpdateData::Identification(id)) => {
-                        self.aircraft_identification_received(icao, &id, metadata);
+                        self.aircraft_identification_received(address, &id, metadata);
                     }
                     RateLimitResult::RateLimited => {
                         // Will be processed later when rate limit allows
@@ -434,9 +1550,9 @@ impl Tracker {
             adsb_deku::adsb::ME::AirbornePositionBaroAltitude(altitude)
             | adsb_deku::adsb::ME::AirbornePositionGNSSAltitude(altitude) => {
                 let update_data = TrackerUpdateData::Position(altitude.clone(), metadata.clone());
-                match rate_limiter.process_update(*icao, UpdateType::Position, update_data) {
+                match rate_limiter.process_update(*address, UpdateType::Position, update_data) {
                     RateLimitResult::Allowed(TrackerUpdateData::Position(pos, meta)) => {
-                        self.airborne_position_received(icao, &pos, &meta);
+                        self.airborne_position_received(address, &pos, &meta);
                     }
                     RateLimitResult::RateLimited => {
                         // Will be processed later when rate limit allows
@@ -444,11 +1560,29 @@ impl Tracker {
                     _ => unreachable!("Mismatched update data type"),
                 }
             }
-            adsb_deku::adsb::ME::AirborneVelocity(velocity) => {
-                let update_data = TrackerUpdateData::Velocity(velocity.clone());
-                match rate_limiter.process_update(*icao, UpdateType::Velocity, update_data) {
+            adsb_deku::adsb::ME::AirborneVelocity(_) => {
+                let Some(velocity) = mode_s::airborne_velocity(&adsb_packet.raw_bytes) else {
+                    return;
+                };
+                let update_data = TrackerUpdateData::Velocity(velocity);
+                match rate_limiter.process_update(*address, UpdateType::Velocity, update_data) {
                     RateLimitResult::Allowed(TrackerUpdateData::Velocity(vel)) => {
-                        self.airborne_velocity_received(icao, &vel, metadata);
+                        self.airborne_velocity_received(address, &vel, metadata);
+                    }
+                    RateLimitResult::RateLimited => {
+                        // Will be processed later when rate limit allows
+                    }
+                    _ => unreachable!("Mismatched update data type"),
+                }
+            }
+            adsb_deku::adsb::ME::SurfacePosition(_) => {
+                let Some(report) = mode_s::surface_position(&adsb_packet.raw_bytes) else {
+                    return;
+                };
+                let update_data = TrackerUpdateData::SurfacePosition(report, metadata.clone());
+                match rate_limiter.process_update(*address, UpdateType::Position, update_data) {
+                    RateLimitResult::Allowed(TrackerUpdateData::SurfacePosition(report, meta)) => {
+                        self.surface_position_received(address, &report, &meta);
                     }
                     RateLimitResult::RateLimited => {
                         // Will be processed later when rate limit allows
@@ -462,33 +1596,47 @@ impl Tracker {
         }
     }
 
+    /// Process an all-call reply through the rate limiter
+    fn process_all_call_with_rate_limiting(
+        &mut self,
+        address: &AircraftAddress,
+        capability: u8,
+        metadata: &DecoderMetaData,
+    ) {
+        let rate_limiter = self.rate_limiter.as_mut().unwrap();
+        let update_data = TrackerUpdateData::AllCall(capability);
+        match rate_limiter.process_update(*address, UpdateType::AllCall, update_data) {
+            RateLimitResult::Allowed(TrackerUpdateData::AllCall(ca)) => {
+                self.all_call_received(address, ca, metadata);
+            }
+            RateLimitResult::RateLimited => {
+                // Will be processed later when rate limit allows
+            }
+            _ => unreachable!("Mismatched update data type"),
+        }
+    }
+
     /// Process pending updates that are now ready
     fn process_pending_updates(&mut self) {
         if let Some(ref mut rate_limiter) = self.rate_limiter {
             let ready_updates = rate_limiter.process_pending_updates();
-            for (icao, _update_type, data) in ready_updates {
+            let synthetic_metadata = DecoderMetaData::synthetic(SystemTime::now());
+            for (address, _update_type, data) in ready_updates {
                 match data {
                     TrackerUpdateData::Identification(identification) => {
-                        // We need a dummy metadata for consistency
-                        let dummy_metadata = DecoderMetaData {
-                            preamble_index: 0,
-                            preamble_correlation: 0.0,
-                            crc_passed: true,
-                            timestamp: std::time::SystemTime::now(),
-                        };
-                        self.aircraft_identification_received(&icao, &identification, &dummy_metadata);
+                        self.aircraft_identification_received(&address, &identification, &synthetic_metadata);
                     }
                     TrackerUpdateData::Position(position, metadata) => {
-                        self.airborne_position_received(&icao, &position, &metadata);
+                        self.airborne_position_received(&address, &position, &metadata);
+                    }
+                    TrackerUpdateData::SurfacePosition(report, metadata) => {
+                        self.surface_position_received(&address, &report, &metadata);
                     }
                     TrackerUpdateData::Velocity(velocity) => {
-                        let dummy_metadata = DecoderMetaData {
-                            preamble_index: 0,
-                            preamble_correlation: 0.0,
-                            crc_passed: true,
-                            timestamp: std::time::SystemTime::now(),
-                        };
-                        self.airborne_velocity_received(&icao, &velocity, &dummy_metadata);
+                        self.airborne_velocity_received(&address, &velocity, &synthetic_metadata);
+                    }
+                    TrackerUpdateData::AllCall(capability) => {
+                        self.all_call_received(&address, capability, &synthetic_metadata);
                     }
                 }
             }
@@ -535,6 +1683,27 @@ impl Kernel for Tracker {
             self.prune_records();
         }
 
+        // Bound the history of each aircraft
+        self.apply_retention();
+
+        // Report changes of the aircraft on the watchlist
+        self.check_watchlist(SystemTime::now());
+
+        // End alerts whose condition is no longer reported
+        for change in self.alerts.expire(SystemTime::now()) {
+            self.alert_changed(change);
+        }
+
+        // Persist the coverage
+        if let Some(coverage) = self.coverage.as_mut() {
+            coverage.save_if_due();
+        }
+
+        // Persist the register
+        if let Some(snapshot) = self.snapshot.as_mut() {
+            snapshot.save_if_due(&self.aircraft_register);
+        }
+
         // Cleanup rate limiter if enabled
         if let Some(ref mut rate_limiter) = self.rate_limiter {
             rate_limiter.cleanup();
@@ -556,4 +1725,344 @@ impl Kernel for Tracker {
 
         Ok(())
     }
+
+    async fn deinit(
+        &mut self,
+        _sio: &mut StreamIo,
+        _mio: &mut MessageIo<Self>,
+        _meta: &mut BlockMeta,
+    ) -> Result<()> {
+        // Keep what was gathered since the last periodic save
+        self.save_state();
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use adsb_deku::deku::DekuContainerRead;
+
+    const ADDRESS: AircraftAddress = AircraftAddress {
+        icao: adsb_deku::ICAO([0x48, 0x40, 0xd6]),
+        address_type: AddressType::Icao,
+    };
+
+    const SCHIPHOL: ReceiverLocation = ReceiverLocation {
+        latitude: 52.3,
+        longitude: 4.76,
+        altitude: 0.0,
+    };
+
+    fn new_tracker(config: TrackerConfig) -> Tracker {
+        Tracker::with_config(config, OutputModuleManager::new()).kernel
+    }
+
+    /// Pass an extended squitter, given in hex, to the tracker
+    fn receive(tracker: &mut Tracker, frame: &str) {
+        let bytes: Vec<u8> = (0..frame.len())
+            .step_by(2)
+            .map(|i| u8::from_str_radix(&frame[i..i + 2], 16).unwrap())
+            .collect();
+        let (_, message) = adsb_deku::Frame::from_bytes((&bytes, 0)).unwrap();
+        let packet = AdsbPacket {
+            message,
+            decoder_metadata: DecoderMetaData::synthetic(SystemTime::now()),
+            raw_bytes: bytes,
+            address: Some(ADDRESS.icao),
+        };
+        let adsb_deku::DF::ADSB(adsb) = &packet.message.df else {
+            panic!("Not an extended squitter: {}", frame);
+        };
+        tracker.extended_squitter_received(&ADDRESS, AircraftSource::Adsb, &adsb.me, &packet);
+    }
+
+    fn record(tracker: &Tracker) -> &AircraftRecord {
+        &tracker.aircraft_register.register[&ADDRESS]
+    }
+
+    #[test]
+    fn test_evict_least_recently_seen() {
+        let mut tracker = new_tracker(TrackerConfig {
+            max_aircraft: Some(2),
+            ..Default::default()
+        });
+        let address = |last| AircraftAddress::icao(adsb_deku::ICAO([0x48, 0x40, last]));
+        tracker.register_aircraft(&address(1));
+        tracker.register_aircraft(&address(2));
+        tracker.aircraft_register.register.get_mut(&address(2)).unwrap().last_seen -= Duration::from_secs(60);
+
+        tracker.register_aircraft(&address(3));
+        let register = &tracker.aircraft_register.register;
+        assert_eq!(register.len(), 2);
+        assert!(register.contains_key(&address(1)));
+        assert!(!register.contains_key(&address(2)));
+        assert!(register.contains_key(&address(3)));
+
+        // Registering an aircraft again does not evict another one
+        tracker.register_aircraft(&address(3));
+        assert_eq!(tracker.aircraft_register.register.len(), 2);
+    }
+
+    #[test]
+    fn test_receiver_range() {
+        let mut tracker = new_tracker(TrackerConfig {
+            receiver: Some(SCHIPHOL),
+            ..Default::default()
+        });
+        tracker.register_aircraft(&ADDRESS);
+        let receive_position = |tracker: &mut Tracker, latitude, altitude| {
+            let rec = tracker.aircraft_register.register.get_mut(&ADDRESS).unwrap();
+            rec.positions.push(AircraftPositionRecord {
+                position: AircraftPosition {
+                    latitude,
+                    longitude: SCHIPHOL.longitude,
+                    altitude,
+                    type_code: 11,
+                    ground_speed: None,
+                    track: None,
+                    quality: PositionQuality::default(),
+                },
+                time: SystemTime::now(),
+                phase: None,
+            });
+            tracker.update_receiver_range(&ADDRESS);
+            record(tracker).receiver_range.unwrap()
+        };
+
+        // 60 NM north at FL360, 5.1 degrees above the horizon after the
+        // curvature of the earth
+        let range = receive_position(&mut tracker, 53.3, Some(36000));
+        assert!((range.distance_nm - 60.04).abs() < 0.01);
+        assert!(range.bearing.abs() < 0.01);
+        assert!((range.elevation.unwrap() - 5.14).abs() < 0.01);
+
+        // 30 NM south without an altitude
+        let range = receive_position(&mut tracker, 51.8, None);
+        assert!((range.distance_nm - 30.02).abs() < 0.01);
+        assert!((range.bearing - 180.0).abs() < 0.01);
+        assert_eq!(range.elevation, None);
+
+        assert!((tracker.max_range_nm - 60.04).abs() < 0.01);
+        assert!((tracker.range_sum_nm / tracker.range_count as f64 - 45.03).abs() < 0.01);
+        // Other tests update the global metrics too
+        let snapshot = metrics().snapshot();
+        assert!(snapshot.max_range_nm >= tracker.max_range_nm);
+        assert!(snapshot.avg_range_nm > 0.0);
+    }
+
+    #[test]
+    fn test_nic_supplement_b_of_first_position() {
+        let mut tracker = new_tracker(TrackerConfig {
+            receiver: Some(SCHIPHOL),
+            ..Default::default()
+        });
+        tracker.register_aircraft(&ADDRESS);
+        tracker.aircraft_register.register.get_mut(&ADDRESS).unwrap().operational_status = Some(OperationalStatus {
+            version: 2,
+            surface: false,
+            nic_supplement_a: true,
+            nic_supplement_c: None,
+            nac_p: Some(9),
+            nac_v: None,
+            gva: None,
+            sil: Some(3),
+            sil_supplement: None,
+            sda: None,
+            nic_baro: None,
+        });
+
+        // TC 11 with NIC-B 1 at 52.45, 4.95, decoded relative to the receiver
+        receive(&mut tracker, "8D4840D6591F02F778FD71F2A4AF");
+        let position = &record(&tracker).positions[0].position;
+        assert!((position.latitude - 52.45).abs() < 0.001);
+        assert_eq!(position.quality.nic, 9);
+    }
+
+    #[test]
+    fn test_receiver_relative_range() {
+        // TC 11 at 54.9, 4.76, 156 NM north of the receiver
+        let frame = "8D4840D658B980999AE62C3E3BB0";
+        let mut tracker = new_tracker(TrackerConfig {
+            receiver: Some(SCHIPHOL),
+            max_receiver_range_nm: Some(100.0),
+            ..Default::default()
+        });
+        receive(&mut tracker, frame);
+        assert!(record(&tracker).positions.is_empty());
+
+        let mut tracker = new_tracker(TrackerConfig {
+            receiver: Some(SCHIPHOL),
+            ..Default::default()
+        });
+        receive(&mut tracker, frame);
+        let position = &record(&tracker).positions[0].position;
+        assert!((position.latitude - 54.9).abs() < 0.001);
+        assert!(record(&tracker).receiver_relative);
+    }
+
+    /// Even and odd TC 11 frames at 52.2572, 3.91937 and an odd frame a degree
+    /// further north
+    const EVEN: &str = "8D4840D658C382D690C8AC510563";
+    const ODD: &str = "8D4840D658C38641ECC319E032DE";
+    const ODD_NORTH: &str = "8D4840D658C386E9BEBD8683C8FC";
+    const EVEN_NORTH: &str = "8D4840D658C383813AC31980EF91";
+
+    #[test]
+    fn test_stale_cpr_pair() {
+        let mut tracker = new_tracker(TrackerConfig::default());
+        receive(&mut tracker, EVEN);
+        let rec = tracker.aircraft_register.register.get_mut(&ADDRESS).unwrap();
+        let even = rec.last_cpr_even.as_mut().unwrap();
+        even.time -= ADSB_TIME_RECENT + Duration::from_secs(1);
+
+        // The even frame is too old to pair with the odd one
+        receive(&mut tracker, ODD);
+        assert!(record(&tracker).positions.is_empty());
+
+        receive(&mut tracker, EVEN);
+        let position = &record(&tracker).positions[0].position;
+        assert!((position.latitude - 52.2572).abs() < 0.001);
+        assert!((position.longitude - 3.91937).abs() < 0.001);
+        assert!(!record(&tracker).receiver_relative);
+    }
+
+    #[test]
+    fn test_implausible_positions() {
+        let mut tracker = new_tracker(TrackerConfig::default());
+        receive(&mut tracker, EVEN);
+        receive(&mut tracker, ODD);
+        assert_eq!(record(&tracker).positions.len(), 1);
+
+        // 60 NM in no time
+        for rejected in 1..MAX_REJECTED_POSITIONS {
+            receive(&mut tracker, ODD_NORTH);
+            assert_eq!(record(&tracker).positions.len(), 1);
+            assert_eq!(record(&tracker).rejected_positions, rejected);
+        }
+
+        // The track starts over from a fresh global decode, keeping the trail
+        receive(&mut tracker, ODD_NORTH);
+        let rec = record(&tracker);
+        assert_eq!(rec.positions.len(), 1);
+        assert!(rec.flight.is_some());
+        assert!(rec.position_reset);
+        assert!(rec.last_cpr_even.is_none() && rec.last_cpr_odd.is_none());
+        assert!(rec.track_filter.is_none());
+        assert_eq!(rec.rejected_positions, 0);
+
+        receive(&mut tracker, EVEN_NORTH);
+        receive(&mut tracker, ODD_NORTH);
+        let rec = record(&tracker);
+        assert_eq!(rec.positions.len(), 2);
+        assert!((rec.positions[1].position.latitude - 53.2572).abs() < 0.001);
+        assert!(!rec.position_reset);
+    }
+
+    #[test]
+    fn test_flight_boundaries() {
+        let mut tracker = new_tracker(TrackerConfig {
+            retention: RetentionConfig {
+                max_points: Some(1),
+                ..Default::default()
+            },
+            ..Default::default()
+        });
+        let finished = |tracker: &Tracker| tracker.finished_flights.iter().map(|f| f.end).collect::<Vec<_>>();
+
+        // Identification of KLM1023, then KLM1024
+        receive(&mut tracker, "8D4840D6202CC371C32CE0576098");
+        receive(&mut tracker, "8D4840D6202CC371C32D205DE8B8");
+        assert_eq!(finished(&tracker), vec![FlightEnd::CallsignChange]);
+        assert_eq!(tracker.finished_flights[0].flight.callsign.as_deref(), Some("KLM1023"));
+
+        // Not heard for longer than the flight gap
+        let flight = tracker.aircraft_register.register.get_mut(&ADDRESS).unwrap().flight.as_mut().unwrap();
+        flight.last_seen -= DEFAULT_FLIGHT_GAP + Duration::from_secs(1);
+        receive(&mut tracker, "8D4840D6202CC371C32D205DE8B8");
+        assert_eq!(finished(&tracker), vec![FlightEnd::CallsignChange, FlightEnd::Gap]);
+
+        // Airborne, then a surface position, then airborne again
+        receive(&mut tracker, EVEN);
+        receive(&mut tracker, ODD);
+        receive(&mut tracker, "8D4840D63010035A4122B1A671C8");
+        assert_eq!(record(&tracker).positions.len(), 2);
+        assert!(record(&tracker).flight.as_ref().unwrap().landed);
+        receive(&mut tracker, EVEN);
+        assert_eq!(finished(&tracker), vec![FlightEnd::CallsignChange, FlightEnd::Gap, FlightEnd::Takeoff]);
+
+        // The trail of the finished flight is within the retention limits
+        let landed = &tracker.finished_flights[2];
+        assert_eq!(landed.flight.callsign.as_deref(), Some("KLM1024"));
+        assert_eq!(landed.positions.len(), 1);
+        assert_eq!(landed.positions[0].position.type_code, 6);
+        assert_eq!(record(&tracker).flight.as_ref().unwrap().callsign.as_deref(), Some("KLM1024"));
+    }
+
+    #[test]
+    fn test_save_on_stop() {
+        let dir = std::env::temp_dir();
+        let snapshot_path = dir.join(format!("airjedi-stop-snapshot-{}.json", std::process::id()));
+        let coverage_path = dir.join(format!("airjedi-stop-coverage-{}.json", std::process::id()));
+        let config = || TrackerConfig {
+            receiver: Some(SCHIPHOL),
+            coverage: Some(CoverageConfig {
+                path: Some(coverage_path.clone()),
+                ..Default::default()
+            }),
+            snapshot: Some(SnapshotConfig::new(snapshot_path.clone())),
+            ..Default::default()
+        };
+
+        // Stopped right after a position, well before the periodic saves
+        let mut tracker = new_tracker(config());
+        receive(&mut tracker, EVEN);
+        receive(&mut tracker, ODD);
+        assert_eq!(record(&tracker).positions.len(), 1);
+        tracker.save_state();
+
+        let tracker = new_tracker(config());
+        assert_eq!(record(&tracker).positions.len(), 1);
+        let coverage = tracker.coverage.as_ref().unwrap().to_json(None, SystemTime::now());
+        let bands = coverage["bands"].as_array().unwrap();
+        assert!(bands.iter().any(|band| band["ranges"].as_array().unwrap().iter().any(|range| !range.is_null())));
+        std::fs::remove_file(&snapshot_path).unwrap();
+        std::fs::remove_file(&coverage_path).unwrap();
+    }
+
+    #[test]
+    fn test_surface_position_reference() {
+        const SURFACE: &str = "8D4840D63010035A4122B1A671C8";
+
+        // A position from hours ago is no reference
+        let mut tracker = new_tracker(TrackerConfig::default());
+        receive(&mut tracker, EVEN);
+        receive(&mut tracker, ODD);
+        tracker.aircraft_register.register.get_mut(&ADDRESS).unwrap().positions[0].time -= Duration::from_secs(7200);
+        receive(&mut tracker, SURFACE);
+        assert_eq!(record(&tracker).positions.len(), 1);
+
+        // Relative to the receiver within half a surface zone
+        let mut tracker = new_tracker(TrackerConfig {
+            receiver: Some(SCHIPHOL),
+            ..Default::default()
+        });
+        receive(&mut tracker, SURFACE);
+        let position = &record(&tracker).positions[0].position;
+        assert!((position.latitude - 52.2572).abs() < 0.001);
+        assert!((position.longitude - 3.9194).abs() < 0.001);
+        assert!(record(&tracker).receiver_relative);
+
+        // The decode relative to a receiver 50 NM away is rejected
+        let mut tracker = new_tracker(TrackerConfig {
+            receiver: Some(ReceiverLocation {
+                latitude: 53.0,
+                longitude: 5.8,
+                altitude: 0.0,
+            }),
+            ..Default::default()
+        });
+        receive(&mut tracker, SURFACE);
+        assert!(record(&tracker).positions.is_empty());
+    }
 }
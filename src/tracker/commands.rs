@@ -0,0 +1,218 @@
+//! Commands of the control port of the tracker
+//!
+//! A command is a string with the command name and an optional argument,
+//! separated by a space. The reply is a JSON document, or a JSON object with
+//! an `error` field if the command or its argument is invalid.
+
+use futuresdr::tracing::info;
+use futuresdr::tracing::warn;
+use serde_json::{Value, json};
+use std::time::Duration;
+
+use super::Tracker;
+use crate::*;
+
+impl Tracker {
+    /// Handle a command of the control port
+    ///
+    /// Commands may take an argument after a space. Replies with JSON.
+    pub(super) fn command(&mut self, cmd: &str) -> String {
+        let (name, arg) = cmd.split_once(' ').unwrap_or((cmd, ""));
+        match name {
+            "stats" => {
+                // Return rate limiting statistics if available
+                if let Some(stats) = self.get_rate_limit_stats() {
+                    serde_json::to_string(&stats).unwrap()
+                } else {
+                    "{\"rate_limiting\": \"disabled\"}".to_string()
+                }
+            }
+            // Return aircraft register (same as Pmt::Null for backward compatibility)
+            "aircraft" => serde_json::to_string(&self.aircraft_register).unwrap(),
+            "state" => {
+                // Return the current state of all aircraft
+                let now = SystemTime::now();
+                let states: HashMap<String, AircraftState> = self
+                    .aircraft_register
+                    .register
+                    .iter()
+                    .map(|(address, rec)| (address.to_string(), rec.state(now, self.stale_after)))
+                    .collect();
+                serde_json::to_string(&states).unwrap()
+            }
+            "query" => self.query_command(arg),
+            "database_reload" => self.reload_database(),
+            "predict" => self.predict_command(arg),
+            // Return the finished-flight log
+            "flights" => serde_json::to_string(&self.finished_flights).unwrap(),
+            // Return the recent event log
+            "alerts" => serde_json::to_string(&self.events).unwrap(),
+            // Return the alerts that have not ended yet
+            "active_alerts" => serde_json::to_string(&self.alerts.active()).unwrap(),
+            "coverage" | "coverage_geojson" | "coverage_reset" => self.coverage_command(name, arg),
+            "geofences" | "geofence_add" | "geofence_remove" => self.geofence_command(name, arg),
+            _ => {
+                warn!("Unknown control port command: {}", cmd);
+                format!("{{\"error\": \"Unknown command: {}\"}}", cmd)
+            }
+        }
+    }
+
+    /// Handle the query command of the control port
+    ///
+    /// Replies with the aircraft matching the filters.
+    fn query_command(&self, arg: &str) -> String {
+        match AircraftQuery::parse(arg) {
+            Ok(query) => {
+                let aircraft: HashMap<String, &AircraftRecord> = self
+                    .aircraft_register
+                    .register
+                    .iter()
+                    .filter(|(_, rec)| query.matches(rec))
+                    .map(|(address, rec)| (address.to_string(), rec))
+                    .collect();
+                serde_json::to_string(&aircraft).unwrap()
+            }
+            Err(e) => json!({"error": format!("Invalid query: {}", e)}).to_string(),
+        }
+    }
+
+    /// Handle the predict command of the control port
+    ///
+    /// Replies with the filtered state of all filtered aircraft, dead-reckoned
+    /// to now or to an optional number of seconds ahead.
+    fn predict_command(&self, arg: &str) -> String {
+        if self.kalman.is_none() {
+            return "{\"kalman\": \"disabled\"}".to_string();
+        }
+        let ahead = if arg.is_empty() { Ok(0.0) } else { arg.parse::<f64>() };
+        let Some(time) = ahead
+            .ok()
+            .filter(|ahead| (0.0..=3600.0).contains(ahead))
+            .map(|ahead| SystemTime::now() + Duration::from_secs_f64(ahead))
+        else {
+            return format!("{{\"error\": \"Invalid number of seconds: {}\"}}", arg);
+        };
+        let predictions: HashMap<String, TrackEstimate> = self
+            .aircraft_register
+            .register
+            .iter()
+            .filter_map(|(address, rec)| rec.predict(time).map(|estimate| (address.to_string(), estimate)))
+            .collect();
+        serde_json::to_string(&predictions).unwrap()
+    }
+
+    /// Handle the coverage commands of the control port
+    ///
+    /// `coverage` and `coverage_geojson` take an optional number of hours to
+    /// restrict the outline to a recent time window.
+    fn coverage_command(&mut self, name: &str, arg: &str) -> String {
+        let Some(coverage) = self.coverage.as_mut() else {
+            return "{\"coverage\": \"disabled\"}".to_string();
+        };
+        let hours = if arg.is_empty() {
+            None
+        } else {
+            match arg.parse::<u64>() {
+                Ok(hours) if hours > 0 => Some(hours),
+                _ => return format!("{{\"error\": \"Invalid number of hours: {}\"}}", arg),
+            }
+        };
+        let now = SystemTime::now();
+        match name {
+            "coverage_reset" => {
+                coverage.reset();
+                "{\"coverage\": \"reset\"}".to_string()
+            }
+            // The coverage is only enabled with a receiver location
+            "coverage_geojson" => coverage.to_geojson(&self.receiver.unwrap(), hours, now).to_string(),
+            _ => coverage.to_json(hours, now).to_string(),
+        }
+    }
+
+    /// Load the aircraft database again and update the details of the
+    /// tracked aircraft
+    fn reload_database(&mut self) -> String {
+        match self.database.reload() {
+            Ok(count) => {
+                for (address, rec) in self.aircraft_register.register.iter_mut() {
+                    rec.info = self.database.lookup(address).unwrap_or_default();
+                }
+                info!("Reloaded the aircraft database with {} aircraft", count);
+                json!({"aircraft": count}).to_string()
+            }
+            Err(e) => {
+                warn!("Failed to reload the aircraft database: {:#}", e);
+                json!({"error": format!("{:#}", e)}).to_string()
+            }
+        }
+    }
+
+    /// Handle the geofence commands of the control port
+    fn geofence_command(&mut self, name: &str, arg: &str) -> String {
+        match name {
+            "geofence_add" => {
+                let fences = serde_json::from_str(arg)
+                    .map_err(anyhow::Error::from)
+                    .and_then(|value| Geofence::parse(&value));
+                match fences {
+                    Ok(fences) => {
+                        let ids: Vec<String> = fences.iter().map(|fence| fence.id.clone()).collect();
+                        for fence in fences {
+                            self.geofences.add(fence);
+                        }
+                        info!("Added geofences {:?}", ids);
+                        json!({"added": ids}).to_string()
+                    }
+                    Err(e) => json!({"error": format!("Invalid geofence: {:#}", e)}).to_string(),
+                }
+            }
+            "geofence_remove" if self.geofences.remove(arg) => {
+                info!("Removed geofence {}", arg);
+                json!({"removed": arg}).to_string()
+            }
+            "geofence_remove" => json!({"error": format!("Unknown geofence: {}", arg)}).to_string(),
+            _ => {
+                let features: Vec<Value> = self.geofences.fences().iter().map(Geofence::to_geojson).collect();
+                json!({"type": "FeatureCollection", "features": features}).to_string()
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::output_module::OutputModuleManager;
+
+    fn new_tracker(config: TrackerConfig) -> Tracker {
+        Tracker::with_config(config, OutputModuleManager::new()).kernel
+    }
+
+    #[test]
+    fn test_disabled_and_unknown_commands() {
+        let mut tracker = new_tracker(TrackerConfig::default());
+        assert_eq!(tracker.command("predict"), "{\"kalman\": \"disabled\"}");
+        assert_eq!(tracker.command("coverage 24"), "{\"coverage\": \"disabled\"}");
+        assert_eq!(tracker.command("stats"), "{\"rate_limiting\": \"disabled\"}");
+        assert_eq!(tracker.command("bogus 1"), "{\"error\": \"Unknown command: bogus 1\"}");
+    }
+
+    #[test]
+    fn test_geofence_commands() {
+        let mut tracker = new_tracker(TrackerConfig::default());
+        let fence = json!({
+            "type": "Feature",
+            "properties": {"id": "eham", "radius_nm": 5.0},
+            "geometry": {"type": "Point", "coordinates": [4.76, 52.31]},
+        });
+        let reply: Value = serde_json::from_str(&tracker.command(&format!("geofence_add {}", fence))).unwrap();
+        assert_eq!(reply, json!({"added": ["eham"]}));
+        let reply: Value = serde_json::from_str(&tracker.command("geofences")).unwrap();
+        assert_eq!(reply["features"].as_array().unwrap().len(), 1);
+        let reply: Value = serde_json::from_str(&tracker.command("geofence_remove eham")).unwrap();
+        assert_eq!(reply, json!({"removed": "eham"}));
+        let reply: Value = serde_json::from_str(&tracker.command("geofence_remove eham")).unwrap();
+        assert_eq!(reply, json!({"error": "Unknown geofence: eham"}));
+    }
+}
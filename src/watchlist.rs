@@ -0,0 +1,214 @@
+//! Watchlist of aircraft of interest
+//!
+//! The watchlist is a text file with one entry per line: an ICAO address, a
+//! range of addresses or a callsign pattern, optionally followed by a label.
+//! Callsign patterns match the whole callsign, `*` stands for any number of
+//! characters and `?` for one. A callsign that looks like an address is
+//! written with a `callsign:` prefix. Empty lines and lines starting with `#`
+//! are ignored:
+//! ```text
+//! # Airframes of interest
+//! ae1234              Air Force One
+//! ae0000-afffff       US military
+//! RCH*                Reach flights
+//! callsign:ABC123
+//! ```
+//!
+//! The tracker flags matching aircraft and reports when they appear, when
+//! their state changes and when they disappear.
+
+use crate::{AddressType, AircraftAddress, AircraftRecord, FlightPhase};
+use anyhow::{Context, Result, bail};
+use std::fmt;
+use std::fs;
+use std::path::Path;
+
+/// What a watchlist entry matches
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum WatchPattern {
+    /// A single ICAO address
+    Address(u32),
+    /// An inclusive range of ICAO addresses
+    Range(u32, u32),
+    /// A callsign pattern in upper case
+    Callsign(String),
+}
+
+impl WatchPattern {
+    fn parse(pattern: &str) -> Result<Self> {
+        let address = |hex: &str| {
+            Some(hex)
+                .filter(|hex| hex.len() == 6 && hex.chars().all(|c| c.is_ascii_hexdigit()))
+                .and_then(|hex| u32::from_str_radix(hex, 16).ok())
+        };
+        if let Some(callsign) = pattern.strip_prefix("callsign:") {
+            return Ok(Self::Callsign(callsign.to_uppercase()));
+        }
+        if let Some(address) = address(pattern) {
+            return Ok(Self::Address(address));
+        }
+        match pattern.split_once('-').map(|(first, last)| (address(first), address(last))) {
+            Some((Some(first), Some(last))) if first <= last => Ok(Self::Range(first, last)),
+            Some(_) => bail!("Invalid address range: {}", pattern),
+            None if pattern.chars().all(|c| c.is_ascii_alphanumeric() || c == '*' || c == '?') => {
+                Ok(Self::Callsign(pattern.to_uppercase()))
+            }
+            None => bail!("Invalid callsign pattern: {}", pattern),
+        }
+    }
+
+    fn matches(&self, address: &AircraftAddress, callsign: Option<&str>) -> bool {
+        // Non-ICAO addresses share the numbers but not the allocation
+        let number = Some(address.number()).filter(|_| address.address_type == AddressType::Icao);
+        match self {
+            Self::Address(watched) => number == Some(*watched),
+            Self::Range(first, last) => number.is_some_and(|number| (*first..=*last).contains(&number)),
+            Self::Callsign(pattern) => {
+                callsign.is_some_and(|callsign| glob_matches(pattern.as_bytes(), callsign.trim().as_bytes()))
+            }
+        }
+    }
+}
+
+impl fmt::Display for WatchPattern {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Address(address) => write!(f, "{:06x}", address),
+            Self::Range(first, last) => write!(f, "{:06x}-{:06x}", first, last),
+            Self::Callsign(pattern) => write!(f, "{}", pattern),
+        }
+    }
+}
+
+/// Whether a text matches a pattern with `*` and `?` wildcards
+pub(crate) fn glob_matches(pattern: &[u8], text: &[u8]) -> bool {
+    match pattern.split_first() {
+        None => text.is_empty(),
+        Some((b'*', rest)) => (0..=text.len()).any(|skip| glob_matches(rest, &text[skip..])),
+        Some((&c, rest)) => text
+            .split_first()
+            .is_some_and(|(&t, text)| (c == b'?' || c == t) && glob_matches(rest, text)),
+    }
+}
+
+/// An entry of the watchlist
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct WatchEntry {
+    pub pattern: WatchPattern,
+    pub label: Option<String>,
+}
+
+impl WatchEntry {
+    /// The label of the entry, or its pattern if it has none
+    pub fn name(&self) -> String {
+        self.label.clone().unwrap_or_else(|| self.pattern.to_string())
+    }
+}
+
+/// The watched fields of an aircraft, a change of any of them is reported
+#[derive(Clone, Debug, PartialEq)]
+pub struct WatchedState {
+    pub callsign: Option<String>,
+    pub squawk: Option<u16>,
+    pub on_ground: Option<bool>,
+    pub phase: Option<FlightPhase>,
+}
+
+impl WatchedState {
+    pub fn new(rec: &AircraftRecord) -> Self {
+        Self {
+            callsign: rec.callsign.clone(),
+            squawk: rec.squawk,
+            on_ground: rec.on_ground,
+            phase: rec.flight.as_ref().and_then(|flight| flight.phase),
+        }
+    }
+}
+
+/// A list of aircraft of interest
+#[derive(Clone, Debug, Default)]
+pub struct Watchlist {
+    entries: Vec<WatchEntry>,
+}
+
+impl Watchlist {
+    /// Read a watchlist file
+    pub fn load(path: &Path) -> Result<Self> {
+        let text = fs::read_to_string(path).with_context(|| format!("Failed to read {}", path.display()))?;
+        Self::parse(&text).with_context(|| format!("Invalid watchlist {}", path.display()))
+    }
+
+    /// Parse the lines of a watchlist
+    pub fn parse(text: &str) -> Result<Self> {
+        let entries = text
+            .lines()
+            .map(str::trim)
+            .enumerate()
+            .filter(|(_, line)| !line.is_empty() && !line.starts_with('#'))
+            .map(|(number, line)| {
+                let (pattern, label) = line.split_once(char::is_whitespace).unwrap_or((line, ""));
+                let pattern = WatchPattern::parse(pattern).with_context(|| format!("Line {}", number + 1))?;
+                let label = Some(label.trim().to_string()).filter(|label| !label.is_empty());
+                Ok(WatchEntry { pattern, label })
+            })
+            .collect::<Result<_>>()?;
+        Ok(Self { entries })
+    }
+
+    pub fn entries(&self) -> &[WatchEntry] {
+        &self.entries
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.entries.is_empty()
+    }
+
+    /// The first entry matching an aircraft
+    pub fn find(&self, address: &AircraftAddress, callsign: Option<&str>) -> Option<&WatchEntry> {
+        self.entries.iter().find(|entry| entry.pattern.matches(address, callsign))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_watchlist() {
+        let watchlist = Watchlist::parse(
+            "# Airframes of interest\n\nAE1234   Air Force One\nae0000-afffff US military\nrch*\ncallsign:ABC123\n",
+        )
+        .unwrap();
+        let patterns: Vec<_> = watchlist.entries().iter().map(|entry| entry.pattern.clone()).collect();
+        assert_eq!(
+            patterns,
+            [
+                WatchPattern::Address(0xae1234),
+                WatchPattern::Range(0xae0000, 0xafffff),
+                WatchPattern::Callsign("RCH*".to_string()),
+                WatchPattern::Callsign("ABC123".to_string()),
+            ]
+        );
+        assert_eq!(watchlist.entries()[0].name(), "Air Force One");
+        assert_eq!(watchlist.entries()[2].name(), "RCH*");
+
+        assert!(Watchlist::parse("af0000-ae0000").is_err());
+        assert!(Watchlist::parse("RCH-1").is_err());
+    }
+
+    #[test]
+    fn test_find() {
+        let watchlist = Watchlist::parse("ae1234 One\nae0000-afffff Military\nRCH?1*\n").unwrap();
+        let address = |icao: [u8; 3]| AircraftAddress::icao(adsb_deku::ICAO(icao));
+        let name = |address, callsign| watchlist.find(&address, callsign).map(WatchEntry::name);
+
+        assert_eq!(name(address([0xae, 0x12, 0x34]), None), Some("One".to_string()));
+        assert_eq!(name(address([0xae, 0x56, 0x78]), None), Some("Military".to_string()));
+        assert_eq!(name(address([0x48, 0x40, 0xd6]), Some("RCH415  ")), Some("RCH?1*".to_string()));
+        assert_eq!(name(address([0x48, 0x40, 0xd6]), Some("RCH4")), None);
+        assert_eq!(name(address([0x48, 0x40, 0xd6]), None), None);
+        // Non-ICAO addresses are not in the allocation
+        let anonymous = AircraftAddress::non_icao(adsb_deku::ICAO([0xae, 0x12, 0x34]));
+        assert_eq!(name(anonymous, None), None);
+    }
+}
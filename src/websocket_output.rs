@@ -18,11 +18,18 @@
 //! ## Message Format
 //! Messages are delivered in SBS-1/BaseStation CSV format:
 //! - MSG,1: Aircraft identification (callsign)
+//! - MSG,2: Surface position (lat, lon, ground speed, track)
 //! - MSG,3: Airborne position (lat, lon, altitude)
 //! - MSG,4: Airborne velocity (speed, heading, vertical rate)
+//! - MSG,6: Squawk and emergency flags
+//! - MSG,8: All-call reply
+//!
+//! Tracker events (such as ACAS resolution advisories, or the airline and
+//! route found for a new callsign) are delivered as a single JSON object per
+//! message instead, so clients can tell them apart by a leading `{`.
 
 use crate::sbs1_output::Sbs1Message;
-use crate::{AdsbIcao, AircraftRecord};
+use crate::{is_emergency_squawk, AircraftAddress, AircraftRecord, TrackerEvent};
 use anyhow::Result;
 use tokio::net::{TcpListener, TcpStream};
 use tokio::sync::broadcast;
@@ -43,6 +50,13 @@ impl WebSocketMessage {
             sbs1_data: sbs1_msg.encode(),
         }
     }
+
+    /// Create a WebSocket message from a tracker event, encoded as JSON
+    pub fn from_event(event: &TrackerEvent) -> Self {
+        Self {
+            sbs1_data: event.to_json(),
+        }
+    }
 }
 
 /// WebSocket server for streaming ADS-B data
@@ -173,7 +187,15 @@ impl WebSocketBroadcaster {
 
     /// Broadcast an SBS-1 message to WebSocket clients
     pub fn broadcast_message(&self, sbs1_msg: Sbs1Message) -> Result<()> {
-        let message = WebSocketMessage::from_sbs1_message(&sbs1_msg);
+        self.send(WebSocketMessage::from_sbs1_message(&sbs1_msg))
+    }
+
+    /// Broadcast a tracker event to WebSocket clients
+    pub fn broadcast_event(&self, event: &TrackerEvent) -> Result<()> {
+        self.send(WebSocketMessage::from_event(event))
+    }
+
+    fn send(&self, message: WebSocketMessage) -> Result<()> {
         match self.sender.send(message) {
             Ok(receiver_count) => {
                 debug!("Broadcasted WebSocket message to {} clients", receiver_count);
@@ -252,8 +274,8 @@ impl crate::output_module::OutputModuleBase for WebSocketOutput {
 
 // Implement the state output trait for broadcasting aircraft state
 impl crate::output_module::StateOutputModule for WebSocketOutput {
-    fn broadcast_aircraft_update(&self, icao: &AdsbIcao, record: &AircraftRecord) -> Result<()> {
-        let icao_str = format!("{:02X}{:02X}{:02X}", icao.0[0], icao.0[1], icao.0[2]);
+    fn broadcast_aircraft_update(&self, address: &AircraftAddress, record: &AircraftRecord) -> Result<()> {
+        let icao_str = address.hex_ident();
 
         // Broadcast identification message if we have a callsign
         if let Some(ref callsign) = record.callsign {
@@ -261,15 +283,36 @@ impl crate::output_module::StateOutputModule for WebSocketOutput {
             self.broadcaster.broadcast_message(msg)?;
         }
 
-        // Broadcast position message if we have position data
+        // Broadcast squawk message if we have a squawk
+        if let Some(squawk) = record.squawk {
+            let msg = Sbs1Message::squawk_change(&icao_str, squawk, record.last_seen)
+                .with_status(is_emergency_squawk(squawk), record.has_emergency())
+                .with_spi(record.spi);
+            self.broadcaster.broadcast_message(msg)?;
+        }
+
+        // Broadcast surface or airborne position message if we have position data
         if let Some(pos_record) = record.positions.last() {
-            let msg = Sbs1Message::airborne_position(
-                &icao_str,
-                pos_record.position.latitude,
-                pos_record.position.longitude,
-                pos_record.position.altitude,
-                pos_record.time,
-            );
+            let position = &pos_record.position;
+            let msg = if (5..=8).contains(&position.type_code) {
+                Sbs1Message::surface_position(
+                    &icao_str,
+                    position.latitude,
+                    position.longitude,
+                    position.altitude,
+                    position.ground_speed,
+                    position.track,
+                    pos_record.time,
+                )
+            } else {
+                Sbs1Message::airborne_position(
+                    &icao_str,
+                    position.latitude,
+                    position.longitude,
+                    position.altitude,
+                    pos_record.time,
+                )
+            };
             self.broadcaster.broadcast_message(msg)?;
         }
 
@@ -287,6 +330,16 @@ impl crate::output_module::StateOutputModule for WebSocketOutput {
 
         Ok(())
     }
+
+    fn broadcast_all_call(&self, address: &AircraftAddress, record: &AircraftRecord) -> Result<()> {
+        let icao_str = address.hex_ident();
+        let msg = Sbs1Message::all_call_reply(&icao_str, record.capability == Some(4), record.last_seen);
+        self.broadcaster.broadcast_message(msg)
+    }
+
+    fn broadcast_event(&self, event: &TrackerEvent) -> Result<()> {
+        self.broadcaster.broadcast_event(event)
+    }
 }
 
 #[cfg(test)]